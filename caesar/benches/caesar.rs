@@ -1,15 +1,111 @@
-use criterion::{Criterion, criterion_group, criterion_main};
+use std::env;
+use std::fs;
 
-use caesar::caesar::{Caesar, Mode};
+use criterion::{BenchmarkId, Criterion, SamplingMode, Throughput, criterion_group, criterion_main};
+use uuid::Uuid;
 
-fn criterion_benchmark(c: &mut Criterion) {
-    let text = "The red fox was trying to hunt the white rabbit in a snow field.
-    The white rabbit entered in a deep hole, hoping the fox would lost his track. But the fox
-    has a very good sense of hearing.";
+use caesar::caesar::{Alphabet, Caesar, Mode};
+use caesar::exec;
 
-    c.bench_function("caesar", |b|
-        b.iter(|| Caesar::exec(text, 15, Mode::Encrypt).unwrap()));
+const KEY: i32 = 15;
+
+const SIZES: &[(&str, usize)] = &[
+    ("1KB", 1024),
+    ("64KB", 64 * 1024),
+    ("1MB", 1024 * 1024),
+    ("100MB", 100 * 1024 * 1024),
+];
+
+const ASCII: &str = "The red fox was trying to catch the white rabbit, but the rabbit \
+was faster. In the end the fox gave up, because the rabbit had a better sense of hearing.\n";
+
+// Almost every char is out of the alphabet and takes more than one byte.
+const UNICODE: &str = "Съешь же ещё этих мягких французских булок, да выпей чаю. \
+敏捷的棕色狐狸跳过了懒狗。 Ξεσκεπάζω την ψυχοφθόρα βδελυγμία. ñandú, pingüino 🦊🐇\n";
+
+const TEXTS: &[(&str, &str)] = &[("ascii", ASCII), ("unicode", UNICODE)];
+
+const MODES: &[(&str, Mode)] = &[("encrypt", Mode::Encrypt), ("decrypt", Mode::Decrypt)];
+
+/// Repeats the sample until it fills the size, cutting it at a char boundary.
+fn text_of_size(sample: &str, size: usize) -> String {
+    let mut text = sample.repeat(size / sample.len() + 1);
+    let mut end = size;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    text.truncate(end);
+    text
+}
+
+fn tmp_path() -> String {
+    format!("{}/caesar-bench-{}.txt", env::temp_dir().display(), Uuid::new_v4())
+}
+
+fn args(input: &str, output: &str, mode: &Mode) -> Vec<String> {
+    let mode = match mode {
+        Mode::Encrypt => "-e",
+        Mode::Decrypt => "-d",
+    };
+    ["-k", &KEY.to_string(), mode, "-i", input, "-o", output].iter().map(|arg| arg.to_string()).collect()
+}
+
+fn sizes_benchmark(c: &mut Criterion) {
+    for (size_name, size) in SIZES {
+        let mut group = c.benchmark_group(format!("caesar/{}", size_name));
+        // The big inputs take long enough per iteration to not need the
+        // default linear sampling, which would run them thousands of times.
+        if *size >= 1024 * 1024 {
+            group.sample_size(10).sampling_mode(SamplingMode::Flat);
+        }
+        for (text_name, sample) in TEXTS {
+            let plain = text_of_size(sample, *size);
+            let cipher = Caesar::exec(&plain, KEY, Mode::Encrypt).unwrap();
+            group.throughput(Throughput::Bytes(plain.len() as u64));
+            for (mode_name, mode) in MODES {
+                let input = match mode {
+                    Mode::Encrypt => &plain,
+                    Mode::Decrypt => &cipher,
+                };
+                let id = format!("{}/{}", text_name, mode_name);
+
+                group.bench_with_input(BenchmarkId::new("in_memory", &id), input, |b, input| {
+                    b.iter(|| Caesar::exec(input, KEY, *mode).unwrap())
+                });
+
+                let input_path = tmp_path();
+                let output_path = tmp_path();
+                fs::write(&input_path, input).unwrap();
+                let args = args(&input_path, &output_path, mode);
+                group.bench_with_input(BenchmarkId::new("exec_file", &id), &args, |b, args| {
+                    b.iter(|| exec::with(args, &b""[..], Vec::new()).unwrap())
+                });
+                fs::remove_file(input_path).unwrap();
+                // Not there if the bench was filtered out.
+                let _ = fs::remove_file(output_path);
+            }
+        }
+        group.finish();
+    }
+}
+
+fn alphabets_benchmark(c: &mut Criterion) {
+    let alphabets = [
+        ("letters", Alphabet::Letters),
+        ("alphanumeric", Alphabet::Alphanumeric),
+        ("printable", Alphabet::Printable),
+        ("custom", Alphabet::custom("abcdefghijklmnñopqrstuvwxyz0123456789").unwrap()),
+    ];
+    let text = text_of_size(ASCII, 1024 * 1024);
+    let mut group = c.benchmark_group("caesar/alphabets");
+    group.throughput(Throughput::Bytes(text.len() as u64));
+    for (name, alphabet) in &alphabets {
+        group.bench_with_input(BenchmarkId::from_parameter(name), alphabet, |b, alphabet| {
+            b.iter(|| Caesar::exec_with(&text, KEY, Mode::Encrypt, alphabet).unwrap())
+        });
+    }
+    group.finish();
 }
 
-criterion_group!(benches, criterion_benchmark);
-criterion_main!(benches);
\ No newline at end of file
+criterion_group!(benches, sizes_benchmark, alphabets_benchmark);
+criterion_main!(benches);
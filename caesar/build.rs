@@ -0,0 +1,136 @@
+//! Renders the help menu and the man page from the CLI definitions.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+// Not all the definitions are needed for rendering, some are only used by completions.
+#[allow(dead_code)]
+#[path = "src/options.rs"]
+mod options;
+
+fn main() {
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("help.txt"), help()).unwrap();
+    fs::write(Path::new(&out_dir).join("caesar.1"), man_page(env!("CARGO_PKG_VERSION"))).unwrap();
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=src/options.rs");
+}
+
+const WARNING: &str = "WARNING: Users are encouraged to use modern cryptography instead of this tool.
+This was made for academic purposes with ❤ 🦀";
+
+const DESCRIPTION: &str = "Only -k argument is mandatory, or -w for the substitution, vigenere, columnar, enigma,
+playfair and hill ciphers. If no other argument is provided stdin/stdout and encryption mode
+are assumed.";
+
+const NOTES: &str = "The output file is only replaced once all the results are written. Using the same file
+as input and output is refused, unless --in-place is used.";
+
+const EXAMPLES: &[&str] = &[
+    "caesar -k 10 -i input.txt -o output.txt -e",
+    "caesar -k 47 --charset printable -i input.txt",
+    "caesar -c substitution -w zebras -i input.txt -d",
+    "caesar -c columnar -w zebras,lemon --pad x -i input.txt",
+    "caesar -c enigma -w \"B II,IV,V BUL BLA AV BS CG DL FU\" -i input.txt",
+    "caesar -c playfair -w \"playfair example\" -i input.txt",
+    "caesar -c hill -w GYBNQKURP -i input.txt -d",
+    "caesar -k 3 --json .users[].email -i users.json",
+    "caesar train -i corpus.txt -o model.txt",
+    "caesar crack -l es -i encrypted.txt",
+    "caesar crack -c substitution -i encrypted.txt",
+    "caesar crack -c vigenere -i encrypted.txt",
+    "caesar crib -p \"Dear\" -i encrypted.txt",
+    "caesar selftest",
+    "caesar completions bash > /etc/bash_completion.d/caesar",
+];
+
+/// Renders the help menu shown with -h or on wrong arguments.
+fn help() -> String {
+    let mut help = String::new();
+    help.push_str("🏛 Caesar Cipher 🏛\n\n");
+    help.push_str(WARNING);
+    help.push_str("\n\n");
+    help.push_str(DESCRIPTION);
+    help.push_str("\n\nCommands (optional, must go first):\n\n");
+    let width = options::COMMANDS.iter().map(|c| c.name.len()).max().unwrap_or(0) + 2;
+    for command in options::COMMANDS {
+        push_entry(&mut help, command.name, command.help, width);
+    }
+    help.push_str("\nArguments:\n\n");
+    let width = options::OPTIONS.iter().map(|o| o.flag.len()).max().unwrap_or(0) + 2;
+    for option in options::OPTIONS {
+        push_entry(&mut help, option.flag, option.help, width);
+    }
+    help.push('\n');
+    help.push_str(NOTES);
+    help.push_str("\n\nHere are some full example commands:\n\n");
+    for example in EXAMPLES {
+        help.push_str("$ ");
+        help.push_str(example);
+        help.push('\n');
+    }
+    help
+}
+
+fn push_entry(help: &mut String, name: &str, description: &str, width: usize) {
+    for (i, line) in description.lines().enumerate() {
+        let name = if i == 0 { name } else { "" };
+        help.push_str(&format!("{:width$}{}\n", name, line, width = width));
+    }
+}
+
+/// Renders the man page in roff format.
+fn man_page(version: &str) -> String {
+    let mut man = String::new();
+    man.push_str(&format!(".TH CAESAR 1 \"\" \"caesar {}\" \"User Commands\"\n", version));
+    man.push_str(".SH NAME\ncaesar \\- the historic caesar cipher\n");
+    man.push_str(".SH SYNOPSIS\n.B caesar\n[\\fICOMMAND\\fR] [\\fIOPTIONS\\fR]\n");
+    man.push_str(".SH DESCRIPTION\n");
+    man.push_str(&roff_escape(DESCRIPTION));
+    man.push_str("\n.SH COMMANDS\n");
+    for command in options::COMMANDS {
+        man.push_str(".TP\n");
+        match &command.value {
+            Some(value) => man.push_str(&format!("\\fB{}\\fR \\fI{}\\fR\n", command.name, value.name())),
+            None => man.push_str(&format!("\\fB{}\\fR\n", command.name)),
+        }
+        man.push_str(&roff_escape(command.help));
+        man.push('\n');
+    }
+    man.push_str(".SH OPTIONS\n");
+    for option in options::OPTIONS {
+        man.push_str(".TP\n");
+        match &option.value {
+            Some(value) => man.push_str(&format!("\\fB{}\\fR \\fI{}\\fR\n", roff_escape(option.flag), value.name())),
+            None => man.push_str(&format!("\\fB{}\\fR\n", roff_escape(option.flag))),
+        }
+        man.push_str(&roff_escape(option.help));
+        man.push('\n');
+    }
+    man.push_str(".SH NOTES\n");
+    man.push_str(&roff_escape(NOTES));
+    man.push_str("\n.PP\n");
+    man.push_str(&roff_escape("Users are encouraged to use modern cryptography instead of this tool."));
+    man.push_str("\n.SH EXAMPLES\n");
+    for example in EXAMPLES {
+        man.push_str(".PP\n.nf\n$ ");
+        man.push_str(&roff_escape(example));
+        man.push_str("\n.fi\n");
+    }
+    man
+}
+
+fn roff_escape(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            let line = line.replace('\\', "\\\\").replace('-', "\\-");
+            // Lines starting with a dot or a quote would be taken as requests.
+            match line.starts_with('.') || line.starts_with('\'') {
+                true => format!("\\&{}", line),
+                false => line,
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
@@ -2,44 +2,50 @@ use std::error::Error;
 use std::fmt;
 use std::fmt::{Display, Formatter};
 
-const HELP: &str = "🏛 Caesar Cipher 🏛
+use crate::caesar::Alphabet;
+use crate::completions::Shell;
+use crate::lang::Language;
 
-WARNING: Users are encouraged to use modern cryptography instead of this tool.
-This was made for academic purposes with ❤ 🦀
-
-Only -k argument is mandatory. If no other argument is provided stdin/stdout and
-encryption mode are assumed.
-
-Arguments:
-
--h     Shows this menu.
--v     Shows the version.
--k     The key, or positive shift number of the cipher (mandatory). Max is a 6 digit number.
--o     Write results to specified file.
--i     Specify path to input file.
--e     Encryption mode. (default).
--d     Decryption mode.
-
-Here's a full example command:
-
-$ caesar -k 10 -i input.txt -o output.txt -e
-";
+// Rendered by the build script from the definitions in the options module.
+const HELP: &str = include_str!(concat!(env!("OUT_DIR"), "/help.txt"));
 
 pub(crate) fn parse(args: &[String]) -> Result<Args, ArgsError> {
     if args.is_empty() {
         return Err(ArgsError);
     }
     let mut parsed_args = Args {
+        command: Command::Cipher,
         help: false,
         version: false,
         key: 0,
+        cipher: Cipher::Caesar,
+        keyword: "".to_string(),
+        padding: None,
         input: "".to_string(),
         output: "".to_string(),
         encrypt: false,
         decrypt: false,
+        language: Language::English,
+        model: "".to_string(),
+        crib: "".to_string(),
+        progress: false,
+        stats: false,
+        in_place: false,
+        no_clobber: false,
+        line_buffered: false,
+        selection: None,
+        alphabet: Alphabet::Letters,
+        shell: None,
     };
 
-    for (i, arg) in args.iter().enumerate() {
+    let mut alphabet_given = false;
+    let mut start = 0;
+    if let Some(command) = Command::from_arg(&args[0]) {
+        parsed_args.command = command;
+        start = 1;
+    }
+
+    for (i, arg) in args.iter().enumerate().skip(start) {
         match arg.as_str() {
             "-h" => return Err(ArgsError),
             "-v" => {
@@ -48,7 +54,7 @@ pub(crate) fn parse(args: &[String]) -> Result<Args, ArgsError> {
             }
             "-k" => {
                 let arg_val = i + 1;
-                if args.get(arg_val) == None {
+                if args.get(arg_val).is_none() {
                     return Err(ArgsError);
                 }
                 match args[arg_val].parse() {
@@ -56,20 +62,105 @@ pub(crate) fn parse(args: &[String]) -> Result<Args, ArgsError> {
                     Err(_error) => return Err(ArgsError)
                 }
             }
+            "-c" => {
+                let arg_val = i + 1;
+                match args.get(arg_val).and_then(|name| Cipher::from_name(name)) {
+                    Some(cipher) => parsed_args.cipher = cipher,
+                    None => return Err(ArgsError)
+                }
+            }
+            "-w" => {
+                let arg_val = i + 1;
+                if args.get(arg_val).is_none() {
+                    return Err(ArgsError);
+                }
+                parsed_args.keyword = args[arg_val].to_string()
+            }
+            "--pad" => {
+                let arg_val = i + 1;
+                let mut chars = args.get(arg_val).map(|padding| padding.chars()).ok_or(ArgsError)?;
+                match (chars.next(), chars.next()) {
+                    (Some(padding), None) => parsed_args.padding = Some(padding),
+                    _ => return Err(ArgsError)
+                }
+            }
             "-i" => {
                 let arg_val = i + 1;
-                if args.get(arg_val) == None {
+                if args.get(arg_val).is_none() {
                     return Err(ArgsError);
                 }
                 parsed_args.input = args[arg_val].to_string()
             }
             "-o" => {
                 let arg_val = i + 1;
-                if args.get(arg_val) == None {
+                if args.get(arg_val).is_none() {
                     return Err(ArgsError);
                 }
                 parsed_args.output = args[arg_val].to_string()
             }
+            "-l" => {
+                let arg_val = i + 1;
+                match args.get(arg_val).and_then(|code| Language::from_code(code)) {
+                    Some(language) => parsed_args.language = language,
+                    None => return Err(ArgsError)
+                }
+            }
+            "-m" => {
+                let arg_val = i + 1;
+                if args.get(arg_val).is_none() {
+                    return Err(ArgsError);
+                }
+                parsed_args.model = args[arg_val].to_string()
+            }
+            "-p" => {
+                let arg_val = i + 1;
+                if args.get(arg_val).is_none() {
+                    return Err(ArgsError);
+                }
+                parsed_args.crib = args[arg_val].to_string()
+            }
+            "--progress" => {
+                parsed_args.progress = true
+            }
+            "--stats" => {
+                parsed_args.stats = true
+            }
+            "--in-place" => {
+                parsed_args.in_place = true
+            }
+            "--no-clobber" => {
+                parsed_args.no_clobber = true
+            }
+            "--line-buffered" => {
+                parsed_args.line_buffered = true
+            }
+            "--charset" | "--alphabet" => {
+                let value = args.get(i + 1).ok_or(ArgsError)?;
+                // Only one of them at a time.
+                if alphabet_given {
+                    return Err(ArgsError);
+                }
+                alphabet_given = true;
+                parsed_args.alphabet = match (arg.as_str(), value.as_str()) {
+                    ("--charset", "letters") => Alphabet::Letters,
+                    ("--charset", "alphanumeric") => Alphabet::Alphanumeric,
+                    ("--charset", "printable") => Alphabet::Printable,
+                    ("--alphabet", chars) => Alphabet::custom(chars).map_err(|_| ArgsError)?,
+                    _ => return Err(ArgsError),
+                }
+            }
+            "--regex" | "--json" | "--csv" => {
+                let value = args.get(i + 1).ok_or(ArgsError)?.to_string();
+                // Only one kind of selection at a time.
+                if parsed_args.selection.is_some() {
+                    return Err(ArgsError);
+                }
+                parsed_args.selection = Some(match arg.as_str() {
+                    "--regex" => Selection::Regex(value),
+                    "--json" => Selection::Json(value),
+                    _ => Selection::Csv(value),
+                })
+            }
             "-e" => {
                 parsed_args.encrypt = true
             }
@@ -82,18 +173,123 @@ pub(crate) fn parse(args: &[String]) -> Result<Args, ArgsError> {
     if parsed_args.encrypt && parsed_args.decrypt {
         return Err(ArgsError);
     }
+    // A JSON document can span several lines, so it cannot be read line by line.
+    if parsed_args.line_buffered && matches!(parsed_args.selection, Some(Selection::Json(_))) {
+        return Err(ArgsError);
+    }
+    let needs_keyword = matches!(parsed_args.cipher, Cipher::Substitution | Cipher::Vigenere | Cipher::Columnar | Cipher::Enigma
+        | Cipher::Playfair | Cipher::Hill);
+    if parsed_args.command == Command::Cipher && needs_keyword && parsed_args.keyword.is_empty() {
+        return Err(ArgsError);
+    }
+    // Only these ciphers have a cracker.
+    let crackable = matches!(parsed_args.cipher, Cipher::Caesar | Cipher::Substitution | Cipher::Vigenere);
+    if parsed_args.command == Command::Crack && !crackable {
+        return Err(ArgsError);
+    }
+    if parsed_args.command == Command::Crib && parsed_args.crib.is_empty() {
+        return Err(ArgsError);
+    }
+    if parsed_args.command == Command::Completions {
+        match args.get(1).and_then(|name| Shell::from_name(name)) {
+            Some(shell) => parsed_args.shell = Some(shell),
+            None => return Err(ArgsError),
+        }
+    }
+    if parsed_args.in_place {
+        if parsed_args.input.is_empty() || !parsed_args.output.is_empty() {
+            return Err(ArgsError);
+        }
+        parsed_args.output = parsed_args.input.to_string();
+    }
     Ok(parsed_args)
 }
 
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Cipher,
+    Crack,
+    Train,
+    Crib,
+    Completions,
+    Man,
+    Selftest,
+}
+
+impl Command {
+    fn from_arg(arg: &str) -> Option<Command> {
+        match arg {
+            "crack" => Some(Command::Crack),
+            "train" => Some(Command::Train),
+            "crib" => Some(Command::Crib),
+            "completions" => Some(Command::Completions),
+            "man" => Some(Command::Man),
+            "selftest" => Some(Command::Selftest),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cipher {
+    Caesar,
+    Substitution,
+    Vigenere,
+    RailFence,
+    Columnar,
+    Enigma,
+    Playfair,
+    Hill,
+}
+
+impl Cipher {
+    fn from_name(name: &str) -> Option<Cipher> {
+        match name {
+            "caesar" => Some(Cipher::Caesar),
+            "substitution" => Some(Cipher::Substitution),
+            "vigenere" => Some(Cipher::Vigenere),
+            "railfence" => Some(Cipher::RailFence),
+            "columnar" => Some(Cipher::Columnar),
+            "enigma" => Some(Cipher::Enigma),
+            "playfair" => Some(Cipher::Playfair),
+            "hill" => Some(Cipher::Hill),
+            _ => None,
+        }
+    }
+}
+
+/// The regions of the text to transform, leaving the rest as it is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selection {
+    Regex(String),
+    Json(String),
+    Csv(String),
+}
+
 #[derive(Debug)]
 pub struct Args {
+    pub command: Command,
     pub version: bool,
     pub help: bool,
     pub key: i32,
+    pub cipher: Cipher,
+    pub keyword: String,
+    pub padding: Option<char>,
     pub output: String,
     pub input: String,
     pub encrypt: bool,
     pub decrypt: bool,
+    pub language: Language,
+    pub model: String,
+    pub crib: String,
+    pub progress: bool,
+    pub stats: bool,
+    pub in_place: bool,
+    pub no_clobber: bool,
+    pub line_buffered: bool,
+    pub selection: Option<Selection>,
+    pub alphabet: Alphabet,
+    pub shell: Option<Shell>,
 }
 
 impl Display for Args {
@@ -101,11 +297,25 @@ impl Display for Args {
         write!(f, "Received arguments are:
         -h {}
         -k {}
+        -c {:?}
+        -w {}
+        --pad {:?}
         -o {}
         -i {}
         -e {}
         -d {}
-        ", self.help, self.key, self.output, self.input, self.encrypt, self.decrypt)
+        -l {:?}
+        -m {}
+        -p {}
+        --progress {}
+        --stats {}
+        --in-place {}
+        --no-clobber {}
+        --line-buffered {}
+        selection {:?}
+        alphabet {:?}
+        ", self.help, self.key, self.cipher, self.keyword, self.padding, self.output, self.input, self.encrypt, self.decrypt, self.language, self.model, self.crib,
+               self.progress, self.stats, self.in_place, self.no_clobber, self.line_buffered, self.selection, self.alphabet)
     }
 }
 
@@ -126,6 +336,8 @@ impl Error for ArgsError {
 
 #[cfg(test)]
 mod test {
+    use crate::options::{COMMANDS, OPTIONS, Value};
+
     use super::*;
 
     #[test]
@@ -145,7 +357,7 @@ mod test {
         assert_eq!(10, result.key);
         assert_eq!("/home/user/in.txt", result.input);
         assert_eq!("/home/user/out.txt", result.output);
-        assert_eq!(true, result.encrypt);
+        assert!(result.encrypt);
     }
 
     #[test]
@@ -158,7 +370,7 @@ mod test {
         let result = parse(&args).unwrap();
 
         assert_eq!(10, result.key);
-        assert_eq!(true, result.decrypt);
+        assert!(result.decrypt);
     }
 
     #[test]
@@ -180,7 +392,7 @@ mod test {
             "10".to_string(),
         ];
         let result = parse(&args).unwrap();
-        assert_eq!(true, result.version);
+        assert!(result.version);
         assert_eq!(0, result.key);
     }
 
@@ -237,5 +449,366 @@ mod test {
         let res = parse(&args).unwrap_err();
         assert_eq!(ArgsError, res)
     }
-}
 
+    #[test]
+    fn it_parses_crack_command() {
+        let args = vec![
+            "crack".to_string(),
+            "-l".to_string(),
+            "es".to_string(),
+            "-i".to_string(),
+            "/home/user/in.txt".to_string(),
+        ];
+        let result = parse(&args).unwrap();
+
+        assert_eq!(Command::Crack, result.command);
+        assert_eq!(Language::Spanish, result.language);
+        assert_eq!("/home/user/in.txt", result.input);
+    }
+
+    #[test]
+    fn it_returns_error_when_cracking_a_cipher_without_cracker() {
+        for cipher in ["railfence", "columnar", "enigma", "playfair", "hill"] {
+            let args = vec![
+                "crack".to_string(),
+                "-c".to_string(),
+                cipher.to_string(),
+            ];
+            assert_eq!(ArgsError, parse(&args).unwrap_err(), "accepted {}", cipher);
+        }
+    }
+
+    #[test]
+    fn it_parses_train_command() {
+        let args = vec![
+            "train".to_string(),
+            "-o".to_string(),
+            "/home/user/model.txt".to_string(),
+        ];
+        let result = parse(&args).unwrap();
+
+        assert_eq!(Command::Train, result.command);
+        assert_eq!("/home/user/model.txt", result.output);
+    }
+
+    #[test]
+    fn it_returns_error_on_unknown_language() {
+        let args = vec![
+            "crack".to_string(),
+            "-l".to_string(),
+            "xx".to_string(),
+        ];
+        let res = parse(&args).unwrap_err();
+        assert_eq!(ArgsError, res)
+    }
+
+    #[test]
+    fn it_parses_crib_command() {
+        let args = vec![
+            "crib".to_string(),
+            "-p".to_string(),
+            "Dear".to_string(),
+        ];
+        let result = parse(&args).unwrap();
+
+        assert_eq!(Command::Crib, result.command);
+        assert_eq!("Dear", result.crib);
+    }
+
+    #[test]
+    fn it_returns_error_when_crib_command_has_no_crib() {
+        let args = vec![
+            "crib".to_string(),
+        ];
+        let res = parse(&args).unwrap_err();
+        assert_eq!(ArgsError, res)
+    }
+
+    #[test]
+    fn it_parses_substitution_cipher() {
+        let args = vec![
+            "-c".to_string(),
+            "substitution".to_string(),
+            "-w".to_string(),
+            "zebras".to_string(),
+        ];
+        let result = parse(&args).unwrap();
+
+        assert_eq!(Cipher::Substitution, result.cipher);
+        assert_eq!("zebras", result.keyword);
+    }
+
+    #[test]
+    fn it_returns_error_when_substitution_cipher_has_no_keyword() {
+        let args = vec![
+            "-c".to_string(),
+            "substitution".to_string(),
+        ];
+        assert_eq!(ArgsError, parse(&args).unwrap_err());
+
+        let args = vec![
+            "crack".to_string(),
+            "-c".to_string(),
+            "substitution".to_string(),
+        ];
+        assert!(parse(&args).is_ok());
+    }
+
+    #[test]
+    fn it_returns_error_when_vigenere_cipher_has_no_keyword() {
+        let args = vec![
+            "-c".to_string(),
+            "vigenere".to_string(),
+        ];
+        let res = parse(&args).unwrap_err();
+        assert_eq!(ArgsError, res)
+    }
+
+    #[test]
+    fn it_parses_line_buffered_mode() {
+        let args = vec![
+            "-k".to_string(),
+            "3".to_string(),
+            "--line-buffered".to_string(),
+        ];
+        let result = parse(&args).unwrap();
+
+        assert!(result.line_buffered);
+    }
+
+    #[test]
+    fn it_returns_error_when_json_selection_is_line_buffered() {
+        let args = vec![
+            "-k".to_string(),
+            "3".to_string(),
+            "--json".to_string(),
+            ".name".to_string(),
+            "--line-buffered".to_string(),
+        ];
+        assert_eq!(ArgsError, parse(&args).unwrap_err());
+    }
+
+    #[test]
+    fn it_parses_the_alphabet() {
+        let args = vec![
+            "-k".to_string(),
+            "47".to_string(),
+            "--charset".to_string(),
+            "printable".to_string(),
+        ];
+        assert_eq!(Alphabet::Printable, parse(&args).unwrap().alphabet);
+
+        let args = vec![
+            "-k".to_string(),
+            "1".to_string(),
+            "--alphabet".to_string(),
+            "0123456789".to_string(),
+        ];
+        assert_eq!(Alphabet::custom("0123456789").unwrap(), parse(&args).unwrap().alphabet);
+    }
+
+    #[test]
+    fn it_returns_error_on_invalid_alphabets() {
+        for alphabet in [["--charset", "digits"], ["--alphabet", "aa"]] {
+            let args: Vec<String> = ["-k", "1"].iter().chain(alphabet.iter()).map(|arg| arg.to_string()).collect();
+            assert_eq!(ArgsError, parse(&args).unwrap_err());
+        }
+        for alphabets in [["--charset", "printable", "--alphabet", "abc"], ["--charset", "letters", "--alphabet", "xyz"]] {
+            let args: Vec<String> = ["-k", "1"].iter().chain(alphabets.iter()).map(|arg| arg.to_string()).collect();
+            assert_eq!(ArgsError, parse(&args).unwrap_err());
+        }
+    }
+
+    #[test]
+    fn it_parses_selections() {
+        let args = vec![
+            "-k".to_string(),
+            "3".to_string(),
+            "--json".to_string(),
+            ".users[].name".to_string(),
+        ];
+        let result = parse(&args).unwrap();
+
+        assert_eq!(Some(Selection::Json(".users[].name".to_string())), result.selection);
+    }
+
+    #[test]
+    fn it_returns_error_on_more_than_one_selection() {
+        let args = vec![
+            "-k".to_string(),
+            "3".to_string(),
+            "--regex".to_string(),
+            "\\d+".to_string(),
+            "--csv".to_string(),
+            "name".to_string(),
+        ];
+        assert_eq!(ArgsError, parse(&args).unwrap_err());
+
+        let args = vec![
+            "--csv".to_string(),
+        ];
+        assert_eq!(ArgsError, parse(&args).unwrap_err());
+    }
+
+    #[test]
+    fn it_returns_error_when_hill_cipher_has_no_keyword() {
+        let args = vec![
+            "-c".to_string(),
+            "hill".to_string(),
+        ];
+        let res = parse(&args).unwrap_err();
+        assert_eq!(ArgsError, res)
+    }
+
+    #[test]
+    fn it_parses_columnar_cipher_with_padding() {
+        let args = vec![
+            "-c".to_string(),
+            "columnar".to_string(),
+            "-w".to_string(),
+            "zebras,lemon".to_string(),
+            "--pad".to_string(),
+            "x".to_string(),
+        ];
+        let result = parse(&args).unwrap();
+
+        assert_eq!(Cipher::Columnar, result.cipher);
+        assert_eq!("zebras,lemon", result.keyword);
+        assert_eq!(Some('x'), result.padding);
+    }
+
+    #[test]
+    fn it_returns_error_when_padding_is_not_a_single_char() {
+        let args = vec![
+            "--pad".to_string(),
+            "xx".to_string(),
+        ];
+        assert_eq!(ArgsError, parse(&args).unwrap_err());
+
+        let args = vec![
+            "--pad".to_string(),
+        ];
+        assert_eq!(ArgsError, parse(&args).unwrap_err());
+    }
+
+    #[test]
+    fn it_returns_error_on_unknown_cipher() {
+        let args = vec![
+            "-c".to_string(),
+            "rot13".to_string(),
+        ];
+        let res = parse(&args).unwrap_err();
+        assert_eq!(ArgsError, res)
+    }
+
+    #[test]
+    fn it_parses_progress_and_stats_flags() {
+        let args = vec![
+            "-k".to_string(),
+            "1".to_string(),
+            "--progress".to_string(),
+            "--stats".to_string(),
+        ];
+        let result = parse(&args).unwrap();
+
+        assert!(result.progress);
+        assert!(result.stats);
+    }
+
+    #[test]
+    fn it_uses_input_as_output_in_place() {
+        let args = vec![
+            "-k".to_string(),
+            "1".to_string(),
+            "-i".to_string(),
+            "/home/user/in.txt".to_string(),
+            "--in-place".to_string(),
+        ];
+        let result = parse(&args).unwrap();
+
+        assert!(result.in_place);
+        assert_eq!("/home/user/in.txt", result.output);
+    }
+
+    #[test]
+    fn it_returns_error_when_in_place_without_input_or_with_output() {
+        let args = vec![
+            "-k".to_string(),
+            "1".to_string(),
+            "--in-place".to_string(),
+        ];
+        assert_eq!(ArgsError, parse(&args).unwrap_err());
+
+        let args = vec![
+            "-i".to_string(),
+            "/home/user/in.txt".to_string(),
+            "-o".to_string(),
+            "/home/user/out.txt".to_string(),
+            "--in-place".to_string(),
+        ];
+        assert_eq!(ArgsError, parse(&args).unwrap_err());
+    }
+
+    #[test]
+    fn it_parses_completions_command() {
+        let args = vec![
+            "completions".to_string(),
+            "zsh".to_string(),
+        ];
+        let result = parse(&args).unwrap();
+
+        assert_eq!(Command::Completions, result.command);
+        assert_eq!(Some(Shell::Zsh), result.shell);
+    }
+
+    #[test]
+    fn it_returns_error_on_unknown_shell() {
+        let args = vec![
+            "completions".to_string(),
+            "cmd".to_string(),
+        ];
+        let res = parse(&args).unwrap_err();
+        assert_eq!(ArgsError, res)
+    }
+
+    #[test]
+    fn it_shows_all_the_arguments_in_the_help() {
+        let help = format!("{}", ArgsError);
+        for option in OPTIONS {
+            assert!(help.contains(&format!("\n{} ", option.flag)), "help misses {}", option.flag);
+        }
+        for command in COMMANDS {
+            assert!(help.contains(&format!("\n{} ", command.name)), "help misses {}", command.name);
+        }
+    }
+
+    #[test]
+    fn it_parses_all_the_arguments_of_the_help() {
+        let base = ["-k", "1", "-w", "abcd", "-i", "/tmp/in.txt"];
+        let parsed = |extra: &[&str]| {
+            let args: Vec<String> = base.iter().chain(extra).map(|arg| arg.to_string()).collect();
+            parse(&args).map(|args| format!("{:?}", args))
+        };
+        let unchanged = parsed(&[]).unwrap();
+        for option in OPTIONS {
+            let value = match &option.value {
+                None => None,
+                Some(Value::Number(_)) => Some("2"),
+                Some(Value::Text("char")) => Some("x"),
+                Some(Value::Text(_)) => Some("xy"),
+                Some(Value::File) => Some("/tmp/other.txt"),
+                Some(Value::Choice(_, choices)) => choices.last().copied(),
+            };
+            let result = parsed(&[option.flag].into_iter().chain(value).collect::<Vec<_>>());
+            if option.flag == "-h" {
+                assert_eq!(Err(ArgsError), result);
+                continue;
+            }
+            let result = result.unwrap_or_else(|_| panic!("{} is rejected", option.flag));
+            assert_ne!(unchanged, result, "{} is not parsed", option.flag);
+        }
+        for command in COMMANDS {
+            assert!(Command::from_arg(command.name).is_some(), "{} is not parsed", command.name);
+        }
+    }
+}
@@ -7,7 +7,15 @@ impl Caesar {
     const A_UPPERCASE: u8 = b'A';
     const A_LOWERCASE: u8 = b'a';
 
+    const ZERO: u8 = b'0';
+    const FIRST_PRINTABLE: u8 = b'!';
+
     pub fn exec(input: &str, key: i32, mode: Mode) -> Result<String, KeyError> {
+        Caesar::exec_with(input, key, mode, &Alphabet::Letters)
+    }
+
+    /// Like [Caesar::exec], but rotating the chars of the given alphabet.
+    pub fn exec_with(input: &str, key: i32, mode: Mode, alphabet: &Alphabet) -> Result<String, KeyError> {
         if key.is_negative() {
             return Err(KeyError);
         }
@@ -18,43 +26,79 @@ impl Caesar {
         let mut result = String::new();
 
         for ic in input.chars() {
-            match ic.is_ascii_alphabetic() {
-                false => result.push(ic),
-                true => {
-                    let mut k = key;
-                    if mode == Mode::Decrypt {
-                        k = -key
-                    }
-                    match ic.is_ascii_uppercase() {
-                        true => {
-                            result.push(Caesar::rotate_with_base_char(Caesar::A_UPPERCASE, ic, k))
-                        }
-                        false => {
-                            result.push(Caesar::rotate_with_base_char(Caesar::A_LOWERCASE, ic, k))
-                        }
-                    }
-                }
+            if !alphabet.contains(ic) {
+                result.push(ic);
+                continue;
             }
+            let mut k = key;
+            if mode == Mode::Decrypt {
+                k = -key
+            }
+            let rotated = match alphabet {
+                Alphabet::Custom(chars) => {
+                    let start_pos = chars.iter().position(|c| *c == ic).unwrap();
+                    chars[Caesar::rotate(start_pos as i32, k, chars.len() as i32) as usize]
+                }
+                Alphabet::Printable => Caesar::rotate_with_base_char(Caesar::FIRST_PRINTABLE, ic, k, 94),
+                _ if ic.is_ascii_digit() => Caesar::rotate_with_base_char(Caesar::ZERO, ic, k, 10),
+                _ if ic.is_ascii_uppercase() => Caesar::rotate_with_base_char(Caesar::A_UPPERCASE, ic, k, 26),
+                _ => Caesar::rotate_with_base_char(Caesar::A_LOWERCASE, ic, k, 26),
+            };
+            result.push(rotated)
         }
         Ok(result)
     }
 
-    fn rotate_with_base_char(base_char: u8, ic: char, key: i32) -> char {
+    fn rotate_with_base_char(base_char: u8, ic: char, key: i32, len: i32) -> char {
         let start_pos = ic as u8 - base_char;
-        let rotated_char = base_char + Caesar::rotate(start_pos as i32, key);
+        let rotated_char = base_char + Caesar::rotate(start_pos as i32, key, len) as u8;
         rotated_char as char
     }
 
-    fn rotate(start_pos: i32, key: i32) -> u8 {
-        let mut result = (start_pos + key) % 26;
+    fn rotate(start_pos: i32, key: i32, len: i32) -> i32 {
+        let mut result = (start_pos + key) % len;
         if result.is_negative() {
-            result += 26
+            result += len
         }
-        result as u8
+        result
     }
 }
 
-#[derive(PartialEq, Eq)]
+/// The chars rotated by the cipher. Any other char is kept as it is.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Alphabet {
+    /// Ascii letters, keeping their case.
+    #[default]
+    Letters,
+    /// Ascii letters, plus the digits rotated among themselves, like in ROT5.
+    Alphanumeric,
+    /// All the printable ascii chars but the space, from "!" to "~", rotated
+    /// together like in ROT47.
+    Printable,
+    /// The given chars, rotated in their order.
+    Custom(Vec<char>),
+}
+
+impl Alphabet {
+    pub fn custom(chars: &str) -> Result<Alphabet, AlphabetError> {
+        let chars: Vec<char> = chars.chars().collect();
+        if chars.len() < 2 || chars.iter().enumerate().any(|(i, c)| chars[..i].contains(c)) {
+            return Err(AlphabetError);
+        }
+        Ok(Alphabet::Custom(chars))
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        match self {
+            Alphabet::Letters => c.is_ascii_alphabetic(),
+            Alphabet::Alphanumeric => c.is_ascii_alphanumeric(),
+            Alphabet::Printable => c.is_ascii_graphic(),
+            Alphabet::Custom(chars) => chars.contains(&c),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Mode {
     Encrypt,
     Decrypt,
@@ -77,6 +121,63 @@ impl Error for KeyError {
     }
 }
 
+#[derive(Debug, PartialEq, Eq)]
+pub struct AlphabetError;
+
+impl Display for AlphabetError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the alphabet must have at least two chars, without repeating any of them.")
+    }
+}
+
+impl Error for AlphabetError {}
+
+/// An input with the output the cipher must give for it with the key and the
+/// alphabet, or the error when the key is not valid.
+pub(crate) struct KnownAnswer {
+    pub(crate) input: &'static str,
+    pub(crate) key: i32,
+    pub(crate) mode: Mode,
+    pub(crate) alphabet: Alphabet,
+    pub(crate) expected: Result<&'static str, KeyError>,
+}
+
+fn answer(input: &'static str, key: i32, mode: Mode, alphabet: Alphabet, expected: Result<&'static str, KeyError>) -> KnownAnswer {
+    KnownAnswer { input, key, mode, alphabet, expected }
+}
+
+/// Checked by the unit tests, and by the selftest command through the command line.
+pub(crate) fn known_answers() -> Vec<KnownAnswer> {
+    vec![
+        answer("ABC", 1, Mode::Encrypt, Alphabet::Letters, Ok("BCD")),
+        answer("BCD", 1, Mode::Decrypt, Alphabet::Letters, Ok("ABC")),
+        // Anything out of the alphabet is kept as it is.
+        answer("(ABC)D", 1, Mode::Encrypt, Alphabet::Letters, Ok("(BCD)E")),
+        answer("A B C", 1, Mode::Encrypt, Alphabet::Letters, Ok("B C D")),
+        answer("A \n B \n C", 1, Mode::Encrypt, Alphabet::Letters, Ok("B \n C \n D")),
+        answer("abc", 1, Mode::Encrypt, Alphabet::Letters, Ok("bcd")),
+        answer("ЗaЗ", 1, Mode::Encrypt, Alphabet::Letters, Ok("ЗbЗ")),
+        answer("ABC", 26, Mode::Encrypt, Alphabet::Letters, Ok("ABC")),
+        answer("ABC", 26, Mode::Decrypt, Alphabet::Letters, Ok("ABC")),
+        // Letters close to the end and displacement exceeds last alpha.
+        answer("XY", 3, Mode::Encrypt, Alphabet::Letters, Ok("AB")),
+        answer("BC", 3, Mode::Decrypt, Alphabet::Letters, Ok("YZ")),
+        // Two times the alphabet + 2 (forward) and + 3 (backward).
+        answer("ABC", 54, Mode::Encrypt, Alphabet::Letters, Ok("CDE")),
+        answer("ABC", 55, Mode::Decrypt, Alphabet::Letters, Ok("XYZ")),
+        answer("ABC", 0, Mode::Encrypt, Alphabet::Letters, Ok("ABC")),
+        answer("ABC", -1, Mode::Encrypt, Alphabet::Letters, Err(KeyError)),
+        answer("ABC", 1_000_000, Mode::Encrypt, Alphabet::Letters, Err(KeyError)),
+        answer("ABC", 999_999, Mode::Encrypt, Alphabet::Letters, Ok("NOP")),
+        answer("Call 555-0199 at 9.", 5, Mode::Encrypt, Alphabet::Alphanumeric, Ok("Hfqq 000-5644 fy 4.")),
+        answer("Hfqq 000-5644 fy 4.", 5, Mode::Decrypt, Alphabet::Alphanumeric, Ok("Call 555-0199 at 9.")),
+        answer("Hello, World! 2024-10-19", 47, Mode::Encrypt, Alphabet::Printable, Ok("w6==@[ (@C=5P a_ac\\`_\\`h")),
+        answer("w6==@[ (@C=5P", 47, Mode::Decrypt, Alphabet::Printable, Ok("Hello, World!")),
+        answer("a bcñ d", 1, Mode::Encrypt, Alphabet::custom("abcñ").unwrap(), Ok("b cña d")),
+        answer("b cña d", 1, Mode::Decrypt, Alphabet::custom("abcñ").unwrap(), Ok("a bcñ d")),
+    ]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -178,6 +279,14 @@ mod tests {
         assert_eq!(KeyError, result);
     }
 
+    #[test]
+    fn it_gives_the_known_answers() {
+        for answer in known_answers() {
+            let result = Caesar::exec_with(answer.input, answer.key, answer.mode, &answer.alphabet);
+            assert_eq!(answer.expected.as_deref(), result.as_deref(), "failed with {:?} and key {}", answer.input, answer.key);
+        }
+    }
+
     #[test]
     fn errors_key_has_display() {
         let error = KeyError {};
@@ -195,4 +304,48 @@ mod tests {
         let result = Caesar::exec("ABC", 999_999, Mode::Encrypt).unwrap();
         assert_eq!("NOP", result);
     }
+
+    #[test]
+    fn it_rotates_digits_with_alphanumeric_alphabet() {
+        let result = Caesar::exec_with("Call 555-0199 at 9.", 5, Mode::Encrypt, &Alphabet::Alphanumeric).unwrap();
+        assert_eq!("Hfqq 000-5644 fy 4.", result);
+    }
+
+    #[test]
+    fn it_rotates_printable_chars_with_printable_alphabet() {
+        let result = Caesar::exec_with("Hello, World! 2024-10-19", 47, Mode::Encrypt, &Alphabet::Printable).unwrap();
+        assert_eq!("w6==@[ (@C=5P a_ac\\`_\\`h", result);
+    }
+
+    #[test]
+    fn it_rotates_the_chars_of_a_custom_alphabet() {
+        let alphabet = Alphabet::custom("abcñ").unwrap();
+        let result = Caesar::exec_with("a bcñ d", 1, Mode::Encrypt, &alphabet).unwrap();
+        assert_eq!("b cña d", result);
+    }
+
+    #[test]
+    fn it_round_trips_with_every_alphabet() {
+        let text = "Phone: +34 600-123-456, date 2024/10/19 (ñ) ~{}|";
+        let alphabets = [
+            Alphabet::Letters,
+            Alphabet::Alphanumeric,
+            Alphabet::Printable,
+            Alphabet::custom("0123456789-/+").unwrap(),
+        ];
+        for alphabet in alphabets {
+            for key in [0, 1, 5, 13, 47, 93, 94, 999_999] {
+                let encrypted = Caesar::exec_with(text, key, Mode::Encrypt, &alphabet).unwrap();
+                let decrypted = Caesar::exec_with(&encrypted, key, Mode::Decrypt, &alphabet).unwrap();
+                assert_eq!(text, decrypted, "failed with {:?} and key {}", alphabet, key);
+            }
+        }
+    }
+
+    #[test]
+    fn it_returns_error_on_invalid_custom_alphabets() {
+        assert_eq!(AlphabetError, Alphabet::custom("a").unwrap_err());
+        assert_eq!(AlphabetError, Alphabet::custom("abca").unwrap_err());
+        assert_eq!("the alphabet must have at least two chars, without repeating any of them.", AlphabetError.to_string());
+    }
 }
@@ -0,0 +1,195 @@
+use crate::options::{COMMANDS, OPTIONS, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    pub(crate) fn from_name(name: &str) -> Option<Shell> {
+        match name {
+            "bash" => Some(Shell::Bash),
+            "zsh" => Some(Shell::Zsh),
+            "fish" => Some(Shell::Fish),
+            _ => None,
+        }
+    }
+}
+
+/// Renders the completion script of the shell from the CLI definitions.
+pub(crate) fn script(shell: Shell) -> String {
+    match shell {
+        Shell::Bash => bash(),
+        Shell::Zsh => zsh(),
+        Shell::Fish => fish(),
+    }
+}
+
+fn bash() -> String {
+    let mut script = String::from("_caesar() {\n");
+    script.push_str("    local cur prev\n");
+    script.push_str("    cur=\"${COMP_WORDS[COMP_CWORD]}\"\n");
+    script.push_str("    prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n");
+    script.push_str("    case \"$prev\" in\n");
+    for option in OPTIONS {
+        if let Some(value) = &option.value {
+            script.push_str(&format!("        {})\n{}            return\n            ;;\n", option.flag, bash_value(value)));
+        }
+    }
+    for command in COMMANDS {
+        if let Some(value) = &command.value {
+            script.push_str(&format!("        {})\n{}            return\n            ;;\n", command.name, bash_value(value)));
+        }
+    }
+    script.push_str("    esac\n");
+    let flags: Vec<&str> = OPTIONS.iter().map(|o| o.flag).collect();
+    let commands: Vec<&str> = COMMANDS.iter().map(|c| c.name).collect();
+    script.push_str(&format!("    local words=\"{}\"\n", flags.join(" ")));
+    script.push_str("    if [ \"$COMP_CWORD\" -eq 1 ]; then\n");
+    script.push_str(&format!("        words=\"{} $words\"\n", commands.join(" ")));
+    script.push_str("    fi\n");
+    script.push_str("    COMPREPLY=($(compgen -W \"$words\" -- \"$cur\"))\n");
+    script.push_str("}\n\ncomplete -F _caesar caesar\n");
+    script
+}
+
+fn bash_value(value: &Value) -> String {
+    match value {
+        Value::File => "            COMPREPLY=($(compgen -f -- \"$cur\"))\n".to_string(),
+        Value::Choice(_, choices) => format!("            COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n", choices.join(" ")),
+        Value::Number(_) | Value::Text(_) => "".to_string(),
+    }
+}
+
+fn zsh() -> String {
+    let mut script = String::from("#compdef caesar\n\n_caesar() {\n");
+    script.push_str("    local -a commands\n    commands=(\n");
+    for command in COMMANDS {
+        script.push_str(&format!("        '{}:{}'\n", command.name, zsh_escape(summary(command.help))));
+    }
+    script.push_str("    )\n");
+    script.push_str("    if (( CURRENT == 2 )) && [[ $words[CURRENT] != -* ]]; then\n");
+    script.push_str("        _describe -t commands 'caesar commands' commands\n");
+    script.push_str("        return\n    fi\n");
+    for command in COMMANDS {
+        if let Some(value) = &command.value {
+            script.push_str(&format!("    if [[ $words[2] == {} ]] && (( CURRENT == 3 )); then\n", command.name));
+            script.push_str(&format!("        {}\n", zsh_value(value)));
+            script.push_str("        return\n    fi\n");
+        }
+    }
+    script.push_str("    _arguments -s \\\n");
+    for option in OPTIONS {
+        let spec = match &option.value {
+            Some(value) => format!("{}[{}]:{}:{}", option.flag, zsh_escape(summary(option.help)), value.name(), zsh_action(value)),
+            None => format!("{}[{}]", option.flag, zsh_escape(summary(option.help))),
+        };
+        script.push_str(&format!("        '{}' \\\n", spec));
+    }
+    script.push_str("        '*::argument: '\n}\n\n_caesar \"$@\"\n");
+    script
+}
+
+fn zsh_action(value: &Value) -> String {
+    match value {
+        Value::File => "_files".to_string(),
+        Value::Choice(_, choices) => format!("({})", choices.join(" ")),
+        Value::Number(_) | Value::Text(_) => " ".to_string(),
+    }
+}
+
+fn zsh_value(value: &Value) -> String {
+    match value {
+        Value::File => "_files".to_string(),
+        Value::Choice(_, choices) => format!("compadd -- {}", choices.join(" ")),
+        Value::Number(name) | Value::Text(name) => format!("_message '{}'", name),
+    }
+}
+
+fn zsh_escape(text: &str) -> String {
+    text.replace('\'', "'\\''").replace('[', "\\[").replace(']', "\\]").replace(':', "\\:")
+}
+
+fn fish() -> String {
+    let mut script = String::from("complete -c caesar -f\n");
+    for command in COMMANDS {
+        script.push_str(&format!("complete -c caesar -n 'test (count (commandline -opc)) -eq 1' -a '{}' -d '{}'\n",
+                                 command.name, fish_escape(summary(command.help))));
+    }
+    for command in COMMANDS {
+        if let Some(value) = &command.value {
+            script.push_str(&format!("complete -c caesar -n 'test (count (commandline -opc)) -eq 2; and __fish_seen_subcommand_from {}' {}\n",
+                                     command.name, fish_value(value)));
+        }
+    }
+    for option in OPTIONS {
+        let flag = match option.flag.strip_prefix("--") {
+            Some(long) => format!("-l {}", long),
+            None => format!("-s {}", option.flag.trim_start_matches('-')),
+        };
+        let value = match &option.value {
+            Some(value) => format!(" {}", fish_value(value)),
+            None => "".to_string(),
+        };
+        script.push_str(&format!("complete -c caesar {}{} -d '{}'\n", flag, value, fish_escape(summary(option.help))));
+    }
+    script
+}
+
+fn fish_value(value: &Value) -> String {
+    match value {
+        Value::File => "-r -F".to_string(),
+        Value::Choice(_, choices) => format!("-x -a '{}'", choices.join(" ")),
+        Value::Number(_) | Value::Text(_) => "-x".to_string(),
+    }
+}
+
+fn fish_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+// Completions only have room for a single line.
+fn summary(help: &str) -> &str {
+    help.lines().next().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_completes_all_the_arguments_in_every_shell() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            let script = script(shell);
+            for option in OPTIONS {
+                assert!(script.contains(option.flag.trim_start_matches('-')), "{:?} misses {}", shell, option.flag);
+            }
+            for command in COMMANDS {
+                assert!(script.contains(command.name), "{:?} misses {}", shell, command.name);
+            }
+        }
+    }
+
+    #[test]
+    fn it_completes_values_in_bash() {
+        let script = script(Shell::Bash);
+        assert!(script.contains("        -l)\n            COMPREPLY=($(compgen -W \"en es fr de\" -- \"$cur\"))\n"));
+        assert!(script.contains("        -i)\n            COMPREPLY=($(compgen -f -- \"$cur\"))\n"));
+        assert!(script.contains("        completions)\n            COMPREPLY=($(compgen -W \"bash zsh fish\" -- \"$cur\"))\n"));
+        assert!(script.ends_with("complete -F _caesar caesar\n"));
+    }
+
+    #[test]
+    fn it_escapes_descriptions() {
+        assert_eq!("it'\\''s \\[x\\]\\: y", zsh_escape("it's [x]: y"));
+        assert_eq!("it\\'s", fish_escape("it's"));
+    }
+
+    #[test]
+    fn it_parses_shell_names() {
+        assert_eq!(Some(Shell::Fish), Shell::from_name("fish"));
+        assert_eq!(None, Shell::from_name("powershell"));
+    }
+}
@@ -0,0 +1,145 @@
+use crate::caesar::{Caesar, Mode};
+use crate::lang::Model;
+
+/// A possible decryption of a ciphertext.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub key: i32,
+    pub score: f64,
+    pub text: String,
+}
+
+/// Tries all the possible keys and returns the resulting decryptions,
+/// ranked from the most to the least likely according to the model.
+pub fn crack(ciphertext: &str, model: &Model) -> Vec<Candidate> {
+    let mut candidates: Vec<Candidate> = (0..26)
+        .map(|key| {
+            let text = Caesar::exec(ciphertext, key, Mode::Decrypt).unwrap();
+            Candidate { key, score: model.score(&text), text }
+        })
+        .collect();
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+    candidates
+}
+
+/// A position of the ciphertext where a known plaintext fragment fits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CribMatch {
+    /// Position of the first character of the match, counted in chars.
+    pub offset: usize,
+    pub key: i32,
+}
+
+/// Known plaintext attack. Looks for all the places in the ciphertext where
+/// the crib could have been encrypted, returning the key used at each one.
+///
+/// Letters in the crib must match letters of the same case shifted by a single
+/// key, while the rest of the characters must be the same in both texts.
+/// Cribs without letters do not reveal any key, so they never match.
+pub fn with_crib(ciphertext: &str, crib: &str) -> Vec<CribMatch> {
+    let ciphertext: Vec<char> = ciphertext.chars().collect();
+    let crib: Vec<char> = crib.chars().collect();
+    if crib.is_empty() || crib.len() > ciphertext.len() {
+        return Vec::new();
+    }
+    (0..=ciphertext.len() - crib.len())
+        .filter_map(|offset| {
+            crib_key(&ciphertext[offset..offset + crib.len()], &crib).map(|key| CribMatch { offset, key })
+        })
+        .collect()
+}
+
+fn crib_key(window: &[char], crib: &[char]) -> Option<i32> {
+    let mut key = None;
+    for (c, p) in window.iter().zip(crib) {
+        if !p.is_ascii_alphabetic() {
+            if c != p {
+                return None;
+            }
+            continue;
+        }
+        if !c.is_ascii_alphabetic() || c.is_ascii_uppercase() != p.is_ascii_uppercase() {
+            return None;
+        }
+        let shift = (*c as i32 - *p as i32).rem_euclid(26);
+        match key {
+            None => key = Some(shift),
+            Some(k) if k != shift => return None,
+            Some(_) => {}
+        }
+    }
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lang::Language;
+
+    use super::*;
+
+    #[test]
+    fn it_cracks_english_text() {
+        let ciphertext = Caesar::exec("The red fox was trying to hunt the white rabbit in a snow field.", 7, Mode::Encrypt).unwrap();
+
+        let candidates = crack(&ciphertext, Model::builtin(Language::English));
+
+        assert_eq!(26, candidates.len());
+        assert_eq!(7, candidates[0].key);
+        assert_eq!("The red fox was trying to hunt the white rabbit in a snow field.", candidates[0].text);
+    }
+
+    #[test]
+    fn it_cracks_spanish_text() {
+        let ciphertext = Caesar::exec("El zorro rojo intentaba cazar al conejo blanco en un campo nevado.", 11, Mode::Encrypt).unwrap();
+
+        let candidates = crack(&ciphertext, Model::builtin(Language::Spanish));
+
+        assert_eq!(11, candidates[0].key);
+    }
+
+    #[test]
+    fn it_cracks_with_a_trained_model() {
+        let model = Model::train("zzz yyy zyzy").unwrap();
+        let ciphertext = Caesar::exec("zyyz", 3, Mode::Encrypt).unwrap();
+
+        let candidates = crack(&ciphertext, &model);
+
+        assert_eq!(3, candidates[0].key);
+        assert_eq!("zyyz", candidates[0].text);
+    }
+
+    #[test]
+    fn it_finds_the_key_with_a_crib() {
+        let ciphertext = Caesar::exec("Dear Bob, the rabbit is in the hole.", 23, Mode::Encrypt).unwrap();
+
+        let matches = with_crib(&ciphertext, "Dear");
+
+        assert_eq!(vec![CribMatch { offset: 0, key: 23 }], matches);
+    }
+
+    #[test]
+    fn it_finds_all_the_crib_matches() {
+        let ciphertext = Caesar::exec("the rabbit saw the fox", 4, Mode::Encrypt).unwrap();
+
+        let matches = with_crib(&ciphertext, "the");
+
+        // Only the two occurrences of "the" share its distances between letters.
+        assert_eq!(vec![CribMatch { offset: 0, key: 4 }, CribMatch { offset: 15, key: 4 }], matches);
+    }
+
+    #[test]
+    fn it_respects_non_letters_and_case_in_the_crib() {
+        let ciphertext = Caesar::exec("Hello, World. hello world", 2, Mode::Encrypt).unwrap();
+
+        let matches = with_crib(&ciphertext, "Hello, ");
+
+        assert_eq!(vec![CribMatch { offset: 0, key: 2 }], matches);
+    }
+
+    #[test]
+    fn it_does_not_match_cribs_without_letters() {
+        assert!(with_crib("a, b, c", ", ").is_empty());
+        assert!(with_crib("abc", "").is_empty());
+        assert!(with_crib("ab", "abc").is_empty());
+    }
+}
@@ -0,0 +1,281 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+const ALPHABET_LEN: usize = 26;
+// Wiring and turnover notches of the rotors I to VIII, as used in the Enigma I and M3.
+const ROTORS: [(&str, &str); 8] = [
+    ("EKMFLGDQVZNTOWYHXUSPAIBRCJ", "Q"),
+    ("AJDKSIRUXBLHWTMCQGZNPYFVOE", "E"),
+    ("BDFHJLCPRTXVZNYEIWGAKMUSQO", "V"),
+    ("ESOVPZJAYQUIRHXLNFTGKDCMWB", "J"),
+    ("VZBRGITYUPSDNHLXAWMJQOFECK", "Z"),
+    ("JPGVOUMFYQBENHZRDKASXLICTW", "ZM"),
+    ("NZJHGRCXMYSWBOUFAIVLPEKQDT", "ZM"),
+    ("FKQHTLXOCBDSZPNIGMYAUJEWVR", "ZM"),
+];
+const REFLECTOR_B: &str = "YRUHQSLDPXNGOKMIEBFZCWVJAT";
+const REFLECTOR_C: &str = "FVPJIAOYEDRZXWGCTKUQSBNMHL";
+const ROTOR_NAMES: [&str; 8] = ["I", "II", "III", "IV", "V", "VI", "VII", "VIII"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reflector {
+    B,
+    C,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rotor {
+    forward: [u8; ALPHABET_LEN],
+    backward: [u8; ALPHABET_LEN],
+    notches: Vec<u8>,
+    ring: u8,
+    position: u8,
+}
+
+impl Rotor {
+    fn new(number: usize, ring: u8, position: u8) -> Rotor {
+        let (wiring, notches) = ROTORS[number - 1];
+        let forward = wiring_table(wiring);
+        let mut backward = [0; ALPHABET_LEN];
+        for (input, output) in forward.iter().enumerate() {
+            backward[*output as usize] = input as u8;
+        }
+        let notches = notches.bytes().map(|b| b - b'A').collect();
+        Rotor { forward, backward, notches, ring, position }
+    }
+
+    fn at_notch(&self) -> bool {
+        self.notches.contains(&self.position)
+    }
+
+    fn step(&mut self) {
+        self.position = (self.position + 1) % ALPHABET_LEN as u8;
+    }
+
+    fn encode(&self, letter: u8, table: &[u8; ALPHABET_LEN]) -> u8 {
+        let offset = (ALPHABET_LEN as u8 + self.position - self.ring) % ALPHABET_LEN as u8;
+        let contact = (letter + offset) % ALPHABET_LEN as u8;
+        (table[contact as usize] + ALPHABET_LEN as u8 - offset) % ALPHABET_LEN as u8
+    }
+}
+
+/// Simulator of the Enigma I and M3 rotor machines.
+///
+/// A Caesar shift is like a single rotor that never moves. The Enigma chains
+/// three rotors, picked from I to VIII, whose wiring shifts every letter
+/// differently. The right rotor steps on every key press, moving the middle
+/// one when it passes its notch, which also moves the left one, with the
+/// double stepping of the middle rotor of the real machines. The signal is
+/// sent back through the rotors by the reflector, which makes the machine
+/// reciprocal: encryption and decryption are the same operation.
+///
+/// The plugboard swaps pairs of letters before and after the rotors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Enigma {
+    rotors: [Rotor; 3],
+    reflector: [u8; ALPHABET_LEN],
+    plugboard: [u8; ALPHABET_LEN],
+}
+
+impl Enigma {
+    /// Rotors are numbered from 1 to 8, and go from left to right, like the
+    /// ring settings and starting positions, which are letters.
+    pub fn new(reflector: Reflector, rotors: [usize; 3], rings: [char; 3], positions: [char; 3], plugboard: &[(char, char)])
+               -> Result<Enigma, SettingsError> {
+        if rotors.iter().any(|r| !(1..=ROTORS.len()).contains(r)) || (0..3).any(|i| rotors[(i + 1) % 3] == rotors[i]) {
+            return Err(SettingsError::Rotors);
+        }
+        let rings = letter_indexes(rings).ok_or(SettingsError::Rings)?;
+        let positions = letter_indexes(positions).ok_or(SettingsError::Positions)?;
+        let reflector = match reflector {
+            Reflector::B => wiring_table(REFLECTOR_B),
+            Reflector::C => wiring_table(REFLECTOR_C),
+        };
+        let mut plugs: [u8; ALPHABET_LEN] = std::array::from_fn(|i| i as u8);
+        for (a, b) in plugboard {
+            let (a, b) = letter_index(*a).zip(letter_index(*b)).ok_or(SettingsError::Plugboard)?;
+            if a == b || plugs[a as usize] != a || plugs[b as usize] != b {
+                return Err(SettingsError::Plugboard);
+            }
+            plugs.swap(a as usize, b as usize);
+        }
+        Ok(Enigma {
+            rotors: std::array::from_fn(|i| Rotor::new(rotors[i], rings[i], positions[i])),
+            reflector,
+            plugboard: plugs,
+        })
+    }
+
+    /// Parses the settings of the machine from a line like
+    /// `B II,IV,V BUL BLA AV BS CG`, made of the reflector, the rotors, the ring
+    /// settings, the starting positions and the optional plugboard pairs.
+    pub fn from_settings(settings: &str) -> Result<Enigma, SettingsError> {
+        let mut parts = settings.split_whitespace();
+        let reflector = match parts.next() {
+            Some("B") | Some("b") => Reflector::B,
+            Some("C") | Some("c") => Reflector::C,
+            _ => return Err(SettingsError::Reflector),
+        };
+        let rotors: Vec<usize> = parts.next().unwrap_or_default()
+            .split(',')
+            .map(|name| ROTOR_NAMES.iter().position(|n| n.eq_ignore_ascii_case(name)).map(|i| i + 1))
+            .collect::<Option<_>>()
+            .ok_or(SettingsError::Rotors)?;
+        let rotors = rotors.try_into().map_err(|_| SettingsError::Rotors)?;
+        let rings = three_letters(parts.next()).ok_or(SettingsError::Rings)?;
+        let positions = three_letters(parts.next()).ok_or(SettingsError::Positions)?;
+        let plugboard: Vec<(char, char)> = parts
+            .map(|pair| match pair.chars().collect::<Vec<char>>().as_slice() {
+                [a, b] => Ok((*a, *b)),
+                _ => Err(SettingsError::Plugboard),
+            })
+            .collect::<Result<_, _>>()?;
+        Enigma::new(reflector, rotors, rings, positions, &plugboard)
+    }
+
+    /// The letters shown in the windows of the rotors, from left to right.
+    pub fn positions(&self) -> String {
+        self.rotors.iter().map(|r| (b'A' + r.position) as char).collect()
+    }
+
+    /// Encrypts or decrypts the input, which is the same for the Enigma.
+    /// Rotors only move with letters, the rest of the characters are kept
+    /// as they are. The case of the letters is kept too.
+    pub fn exec(&mut self, input: &str) -> String {
+        input.chars()
+            .map(|c| match letter_index(c) {
+                Some(letter) => {
+                    let base = if c.is_ascii_uppercase() { b'A' } else { b'a' };
+                    (base + self.press(letter)) as char
+                }
+                None => c,
+            })
+            .collect()
+    }
+
+    fn press(&mut self, letter: u8) -> u8 {
+        let [left, middle, right] = &mut self.rotors;
+        if middle.at_notch() {
+            middle.step();
+            left.step();
+        } else if right.at_notch() {
+            middle.step();
+        }
+        right.step();
+
+        let mut letter = self.plugboard[letter as usize];
+        for rotor in self.rotors.iter().rev() {
+            letter = rotor.encode(letter, &rotor.forward);
+        }
+        letter = self.reflector[letter as usize];
+        for rotor in self.rotors.iter() {
+            letter = rotor.encode(letter, &rotor.backward);
+        }
+        self.plugboard[letter as usize]
+    }
+}
+
+fn wiring_table(wiring: &str) -> [u8; ALPHABET_LEN] {
+    let mut table = [0; ALPHABET_LEN];
+    for (i, b) in wiring.bytes().enumerate() {
+        table[i] = b - b'A';
+    }
+    table
+}
+
+fn letter_index(c: char) -> Option<u8> {
+    match c.is_ascii_alphabetic() {
+        true => Some(c.to_ascii_uppercase() as u8 - b'A'),
+        false => None,
+    }
+}
+
+fn letter_indexes(letters: [char; 3]) -> Option<[u8; 3]> {
+    Some([letter_index(letters[0])?, letter_index(letters[1])?, letter_index(letters[2])?])
+}
+
+fn three_letters(word: Option<&str>) -> Option<[char; 3]> {
+    word?.chars().collect::<Vec<char>>().try_into().ok()
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SettingsError {
+    Reflector,
+    Rotors,
+    Rings,
+    Positions,
+    Plugboard,
+}
+
+impl Display for SettingsError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingsError::Reflector => write!(f, "the reflector must be B or C."),
+            SettingsError::Rotors => write!(f, "there must be three different rotors from I to VIII, separated by commas."),
+            SettingsError::Rings => write!(f, "the ring settings must be three letters."),
+            SettingsError::Positions => write!(f, "the starting positions must be three letters."),
+            SettingsError::Plugboard => write!(f, "the plugboard must be pairs of letters, with every letter used once at most."),
+        }
+    }
+}
+
+impl Error for SettingsError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_encrypts_with_the_default_settings() {
+        let mut enigma = Enigma::from_settings("B I,II,III AAA AAA").unwrap();
+        assert_eq!("BDZGO", enigma.exec("AAAAA"));
+    }
+
+    #[test]
+    fn it_applies_the_ring_settings() {
+        let mut enigma = Enigma::from_settings("B I,II,III BBB AAA").unwrap();
+        assert_eq!("EWTYX", enigma.exec("AAAAA"));
+    }
+
+    #[test]
+    fn it_is_reciprocal() {
+        let mut enigma = Enigma::from_settings("C VI,VII,VIII QRS XYZ AB CD EF").unwrap();
+        let encrypted = enigma.exec("Learning Rust, by example!");
+        let mut enigma = Enigma::from_settings("C VI,VII,VIII QRS XYZ AB CD EF").unwrap();
+        assert_eq!("Learning Rust, by example!", enigma.exec(&encrypted));
+    }
+
+    #[test]
+    fn it_double_steps_the_middle_rotor() {
+        let mut enigma = Enigma::from_settings("B I,II,III AAA ADU").unwrap();
+        let positions: Vec<String> = (0..3).map(|_| {
+            enigma.exec("A");
+            enigma.positions()
+        }).collect();
+        assert_eq!(vec!["ADV", "AEW", "BFX"], positions);
+    }
+
+    #[test]
+    fn it_decrypts_operation_barbarossa_message() {
+        // First part of a message sent on 1941-07-07, with an Enigma I.
+        let mut enigma = Enigma::from_settings("B II,IV,V BUL BLA AV BS CG DL FU HZ IN KM OW RX").unwrap();
+        let ciphertext = "EDPUD NRGYS ZRCXN UYTPO MRMBO FKTBZ REZKM LXLVE FGUEY SIOZV EQMIK UBPMM YLKLT TDEIS MDICA \
+        GYKUA CTCDO MOHWX MUUIA UBSTS LRNBZ SZWNR FXWFY SSXJZ VIJHI DISHP RKLKA YUPAD TXQSP INQMA";
+        let plaintext = "AUFKL XABTE ILUNG XVONX KURTI NOWAX KURTI NOWAX NORDW ESTLX SEBEZ XSEBE ZXUAF FLIEG ERSTR \
+        ASZER IQTUN GXDUB ROWKI XDUBR OWKIX OPOTS CHKAX OPOTS CHKAX UMXEI NSAQT DREIN ULLXU HRANG";
+        assert_eq!(plaintext, enigma.exec(ciphertext));
+    }
+
+    #[test]
+    fn it_returns_error_on_invalid_settings() {
+        assert_eq!(SettingsError::Reflector, Enigma::from_settings("A I,II,III AAA AAA").unwrap_err());
+        assert_eq!(SettingsError::Rotors, Enigma::from_settings("B I,II,IX AAA AAA").unwrap_err());
+        assert_eq!(SettingsError::Rotors, Enigma::from_settings("B I,II AAA AAA").unwrap_err());
+        assert_eq!(SettingsError::Rotors, Enigma::from_settings("B I,I,II AAA AAA").unwrap_err());
+        assert_eq!(SettingsError::Rings, Enigma::from_settings("B I,II,III AA AAA").unwrap_err());
+        assert_eq!(SettingsError::Positions, Enigma::from_settings("B I,II,III AAA A1A").unwrap_err());
+        assert_eq!(SettingsError::Plugboard, Enigma::from_settings("B I,II,III AAA AAA AB BC").unwrap_err());
+        assert_eq!(SettingsError::Plugboard, Enigma::from_settings("B I,II,III AAA AAA ABC").unwrap_err());
+    }
+}
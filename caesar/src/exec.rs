@@ -1,44 +1,339 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
 use std::error::Error;
 use std::fs;
-use std::io::{BufRead, Write};
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::rc::Rc;
+use std::str;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
-use crate::{args, caesar};
-use crate::caesar::Caesar;
+use crate::{args, caesar, completions, crack, select, selftest, substitution, vigenere};
+use crate::args::{Args, Cipher, Command, Selection};
+use crate::caesar::{Alphabet, Caesar};
+use crate::enigma::Enigma;
+use crate::hill::Hill;
+use crate::lang::Model;
+use crate::output::{AtomicFile, OutputError, same_file};
+use crate::playfair::Playfair;
+use crate::progress::{Progress, Stats};
+use crate::select::Selector;
+use crate::substitution::Substitution;
+use crate::transposition::{Columnar, RailFence};
+use crate::vigenere::Vigenere;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+// Rendered by the build script from the definitions in the options module.
+const MAN_PAGE: &str = include_str!(concat!(env!("OUT_DIR"), "/caesar.1"));
+const CHUNK_SIZE: usize = 64 * 1024;
+const SUBSTITUTION_RESTARTS: usize = 100;
+const VIGENERE_MAX_KEY_LENGTH: usize = 20;
 
-pub fn with<R, W>(args: &[String], mut reader: R, mut writer: W) -> Result<(), Box<dyn Error>>
+// Transforms a chunk of the input with the selected cipher. The flag tells
+// whether it is the last one, as transpositions need the whole text at once.
+type Transform = Box<dyn FnMut(&str, bool) -> Result<String, Box<dyn Error>>>;
+
+// The input to read, along with its size when it is known.
+type Input<'a> = (Box<dyn Read + 'a>, Option<u64>);
+
+pub fn with<R, W>(args: &[String], reader: R, writer: W) -> Result<(), Box<dyn Error>>
     where R: BufRead, W: Write {
+    with_stderr(args, reader, writer, io::stderr())
+}
+
+/// Like [with], but the progress bar and the stats are written to the
+/// provided writer instead of stderr.
+pub fn with_stderr<R, W, E>(args: &[String], reader: R, mut writer: W, mut stderr: E) -> Result<(), Box<dyn Error>>
+    where R: BufRead, W: Write, E: Write {
     let args = args::parse(args)?;
     if args.version {
         writer.write_all(format!("{}{}\n", "v", VERSION).as_bytes())?;
         return Ok(());
     }
-    let mut input = String::new();
+    match args.command {
+        Command::Completions => {
+            let shell = args.shell.ok_or(args::ArgsError)?;
+            writer.write_all(completions::script(shell).as_bytes())?;
+            Ok(())
+        }
+        Command::Man => {
+            writer.write_all(MAN_PAGE.as_bytes())?;
+            Ok(())
+        }
+        Command::Selftest => selftest::run(writer),
+        Command::Cipher => {
+            let (mut input, input_size) = open_input(&args, reader)?;
+            cipher(&args, &mut input, input_size, writer, &mut stderr)
+        }
+        Command::Crack => analyze(&args, reader, writer, crack_key),
+        Command::Train => analyze(&args, reader, writer, |_, text| Ok(Model::train(text)?.to_string())),
+        Command::Crib => analyze(&args, reader, writer, |args, text| {
+            Ok(crack::with_crib(text, &args.crib)
+                .iter()
+                .map(|m| format!("{} {}\n", m.offset, m.key))
+                .collect())
+        }),
+    }
+}
+
+// Opens the input file, or takes the reader when there is none.
+fn open_input<'a, R: Read + 'a>(args: &Args, reader: R) -> Result<Input<'a>, Box<dyn Error>> {
+    if !args.input.is_empty() && !args.output.is_empty() && !args.in_place && same_file(&args.input, &args.output)? {
+        return Err(Box::new(OutputError::SameFile));
+    }
     if args.input.is_empty() {
-        reader.read_to_string(&mut input)?;
+        return Ok((Box::new(reader), None));
+    }
+    let input_file = File::open(&args.input)?;
+    let input_size = input_file.metadata()?.len();
+    Ok((Box::new(input_file), Some(input_size)))
+}
+
+// Reads the whole input, and writes what the analysis gives for it.
+fn analyze<R, W, F>(args: &Args, reader: R, mut writer: W, analysis: F) -> Result<(), Box<dyn Error>>
+    where R: Read, W: Write, F: FnOnce(&Args, &str) -> Result<String, Box<dyn Error>> {
+    let (mut input, _) = open_input(args, reader)?;
+    let mut input_text = String::new();
+    input.read_to_string(&mut input_text)?;
+    let result = analysis(args, input_text.as_str())?;
+    if !args.output.is_empty() {
+        let mut output_file = AtomicFile::create(&args.output, args.no_clobber)?;
+        output_file.write_all(result.as_bytes())?;
+        output_file.commit()?;
     } else {
-        input = fs::read_to_string(args.input)?;
+        writer.write_all(result.as_bytes())?;
     }
+    Ok(())
+}
+
+// Finds the key with the cracker of the cipher, and outputs the decryption
+// with it, or the candidate keys for vigenere.
+fn crack_key(args: &Args, text: &str) -> Result<String, Box<dyn Error>> {
+    let model = load_model(args)?;
+    Ok(match args.cipher {
+        Cipher::Substitution => {
+            let seed = SystemTime::now().duration_since(UNIX_EPOCH)?.as_nanos() as u64;
+            substitution::solve(text, &model, SUBSTITUTION_RESTARTS, seed)?.text
+        }
+        Cipher::Vigenere => vigenere::crack(text, &model, VIGENERE_MAX_KEY_LENGTH)
+            .iter()
+            .map(|c| format!("{} {:.3}\n", c.key, c.confidence))
+            .collect(),
+        // Caesar, as parsing rejects the ciphers without a cracker.
+        _ => {
+            let candidates = crack::crack(text, &model);
+            candidates.into_iter().next().map(|c| c.text).unwrap_or_default()
+        }
+    })
+}
+
+// Processes the input in chunks, so the progress can be reported while working
+// on big inputs. Chunks are cut at char boundaries, as the cipher works on chars.
+// In line buffered mode, the chunks are the lines, and they are flushed as soon
+// as they are written.
+fn cipher<W, E>(args: &Args, input: &mut dyn Read, input_size: Option<u64>, mut writer: W, stderr: &mut E) -> Result<(), Box<dyn Error>>
+    where W: Write, E: Write {
     let mode: caesar::Mode = if args.decrypt {
         caesar::Mode::Decrypt
     } else {
         caesar::Mode::Encrypt
     };
-    let result = Caesar::exec(input.as_str(), args.key, mode)?;
-    if !args.output.is_empty() {
-        fs::write(args.output, result)?;
+    // Keys are validated here, so invalid ones fail before touching the output.
+    let key = args.key;
+    let mut transform: Transform = match args.cipher {
+        Cipher::Caesar => {
+            Caesar::exec("", key, mode)?;
+            let alphabet = args.alphabet.clone();
+            Box::new(move |text, _| Ok(Caesar::exec_with(text, key, mode, &alphabet)?))
+        }
+        Cipher::Substitution => {
+            let substitution = Substitution::new(&args.keyword)?;
+            Box::new(move |text, _| Ok(substitution.exec(text, mode)))
+        }
+        Cipher::Vigenere => {
+            let vigenere = Vigenere::new(&args.keyword)?;
+            let mut position = 0;
+            Box::new(move |text, _| Ok(vigenere.exec_from(text, mode, &mut position)))
+        }
+        Cipher::Enigma => {
+            // Encryption and decryption are the same for the Enigma.
+            let mut enigma = Enigma::from_settings(&args.keyword)?;
+            Box::new(move |text, _| Ok(enigma.exec(text)))
+        }
+        Cipher::RailFence => {
+            let rail_fence = RailFence::new(key)?;
+            whole_text(move |text| rail_fence.exec(text, mode))
+        }
+        Cipher::Columnar => {
+            let columnar = Columnar::new(&args.keyword, args.padding)?;
+            whole_text(move |text| columnar.exec(text, mode))
+        }
+        Cipher::Playfair => {
+            let playfair = Playfair::new(&args.keyword)?;
+            whole_text(move |text| playfair.exec(text, mode))
+        }
+        Cipher::Hill => {
+            let hill = Hill::from_keyword(&args.keyword)?;
+            whole_text(move |text| hill.exec(text, mode))
+        }
+    };
+    // The other ciphers work on the letters only.
+    let alphabet = match args.cipher {
+        Cipher::Caesar => &args.alphabet,
+        _ => &Alphabet::Letters,
+    };
+    // Shared with the selective transform, which is the one knowing what is
+    // transformed.
+    let stats = Rc::new(RefCell::new(Stats::default()));
+    if let Some(selection) = &args.selection {
+        let selector = match selection {
+            Selection::Regex(pattern) => Selector::pattern(pattern)?,
+            Selection::Json(path) => Selector::json(path)?,
+            Selection::Csv(column) => Selector::csv(column),
+        };
+        if args.stats {
+            transform = counted(transform, Rc::clone(&stats), alphabet.clone());
+        }
+        transform = selective(selector, transform);
+    }
+
+    let mut output_file = None;
+    let output: &mut dyn Write = if args.output.is_empty() {
+        &mut writer
     } else {
-        writer.write_all(result.as_bytes())?;
+        output_file.insert(AtomicFile::create(&args.output, args.no_clobber)?)
+    };
+
+    let started = Instant::now();
+    let mut progress = Progress::new(input_size);
+    // Texts are counted before transforming them, as selected regions are
+    // counted while transforming.
+    let count = |text: &str| match args.selection {
+        Some(_) => stats.borrow_mut().pass(text),
+        None => stats.borrow_mut().count(text, alphabet),
+    };
+    if args.line_buffered {
+        // Every line is a whole text on its own, and the line break is kept
+        // out of it, so even transpositions leave the lines in place.
+        let mut input = BufReader::new(input);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = input.read_until(b'\n', &mut line)?;
+            if read == 0 {
+                break;
+            }
+            let text = str::from_utf8(&line).map_err(|_| invalid_utf8())?;
+            let content = text.trim_end_matches(['\n', '\r']);
+            if args.stats {
+                count(text);
+            }
+            output.write_all(transform(content, true)?.as_bytes())?;
+            output.write_all(&line[content.len()..])?;
+            output.flush()?;
+            if args.progress {
+                progress.advance(read, stderr)?;
+            }
+        }
+    } else {
+        let mut buf = vec![0; CHUNK_SIZE];
+        let mut pending = 0;
+        loop {
+            let read = input.read(&mut buf[pending..])?;
+            let filled = pending + read;
+            let valid = match str::from_utf8(&buf[..filled]) {
+                Ok(text) => text.len(),
+                // Only an incomplete char at the end of the chunk is allowed.
+                Err(err) if err.error_len().is_none() && read > 0 => err.valid_up_to(),
+                Err(_) => return Err(Box::new(invalid_utf8())),
+            };
+            let text = str::from_utf8(&buf[..valid])?;
+            if args.stats {
+                count(text);
+            }
+            output.write_all(transform(text, read == 0)?.as_bytes())?;
+            if args.progress {
+                progress.advance(read, stderr)?;
+            }
+            buf.copy_within(valid..filled, 0);
+            pending = filled - valid;
+            if read == 0 {
+                break;
+            }
+        }
+    }
+    output.flush()?;
+    if let Some(output_file) = output_file {
+        output_file.commit()?;
+    }
+
+    if args.progress {
+        progress.finish(stderr)?;
+    }
+    if args.stats {
+        let mut stats = stats.borrow_mut();
+        stats.elapsed = started.elapsed();
+        write!(stderr, "{}", stats)?;
     }
     Ok(())
 }
 
+// Keeps all the chunks until the last one, to transform the whole text at once.
+fn whole_text<F>(exec: F) -> Transform
+    where F: Fn(&str) -> String + 'static {
+    let mut text = String::new();
+    Box::new(move |chunk, last| {
+        text.push_str(chunk);
+        match last {
+            true => Ok(exec(&std::mem::take(&mut text))),
+            false => Ok(String::new()),
+        }
+    })
+}
+
+// Keeps all the chunks until the last one, to find the selected regions of the
+// whole text. Every region is transformed as a whole text on its own.
+fn selective(mut selector: Selector, mut transform: Transform) -> Transform {
+    let mut text = String::new();
+    Box::new(move |chunk, last| {
+        text.push_str(chunk);
+        if !last {
+            return Ok(String::new());
+        }
+        let text = std::mem::take(&mut text);
+        let regions = selector.regions(&text)?;
+        select::apply(&text, &regions, |region| transform(region, true))
+    })
+}
+
+// Counts the regions given to the transform as the selected ones.
+fn counted(mut transform: Transform, stats: Rc<RefCell<Stats>>, alphabet: Alphabet) -> Transform {
+    Box::new(move |region, last| {
+        stats.borrow_mut().select(region, &alphabet);
+        transform(region, last)
+    })
+}
+
+fn invalid_utf8() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "stream did not contain valid UTF-8")
+}
+
+fn load_model(args: &Args) -> Result<Cow<'static, Model>, Box<dyn Error>> {
+    if args.model.is_empty() {
+        return Ok(Cow::Borrowed(Model::builtin(args.language)));
+    }
+    Ok(Cow::Owned(Model::parse(fs::read_to_string(&args.model)?.as_str())?))
+}
+
 
 #[cfg(test)]
 mod test {
     use uuid::Uuid;
 
+    use crate::enigma::SettingsError;
+    use crate::hill;
+
     use super::*;
 
     #[test]
@@ -86,6 +381,663 @@ mod test {
         fs::remove_file(output_file_path).unwrap()
     }
 
+    #[test]
+    fn it_cracks_from_stdin() {
+        let args = vec![
+            "crack".to_string(),
+            "-l".to_string(),
+            "es".to_string(),
+        ];
+        let input = Caesar::exec("El zorro rojo intentaba cazar al conejo blanco", 5, caesar::Mode::Encrypt).unwrap();
+        let mut output = Vec::new();
+
+        with(args.as_slice(), input.as_bytes(), &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!("El zorro rojo intentaba cazar al conejo blanco", output)
+    }
+
+    #[test]
+    fn it_trains_a_model_and_cracks_with_it() {
+        let model_file_path = tmp_path();
+
+        let args = vec![
+            "train".to_string(),
+            "-o".to_string(),
+            model_file_path.to_owned(),
+        ];
+        let corpus: &[u8] = b"zzz yyy zyzy";
+        with(args.as_slice(), corpus, Vec::new()).unwrap();
+
+        let args = vec![
+            "crack".to_string(),
+            "-m".to_string(),
+            model_file_path.to_owned(),
+        ];
+        let input: &[u8] = b"cbbc";
+        let mut output = Vec::new();
+        with(args.as_slice(), input, &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!("zyyz", output);
+
+        fs::remove_file(model_file_path).unwrap()
+    }
+
+    #[test]
+    fn it_encrypts_and_decrypts_with_substitution() {
+        let args = vec![
+            "-c".to_string(),
+            "substitution".to_string(),
+            "-w".to_string(),
+            "zebras".to_string(),
+        ];
+        let input: &[u8] = b"Learning Rust";
+        let mut output = Vec::new();
+
+        with(args.as_slice(), input, &mut output).unwrap();
+
+        let ciphertext = String::from_utf8(output).unwrap();
+        assert_eq!("Iazokfkc Otpq", ciphertext);
+
+        let mut args = args;
+        args.push("-d".to_string());
+        let mut output = Vec::new();
+
+        with(args.as_slice(), ciphertext.as_bytes(), &mut output).unwrap();
+
+        assert_eq!("Learning Rust", String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn it_returns_error_on_invalid_substitution_keyword() {
+        let args = vec![
+            "-c".to_string(),
+            "substitution".to_string(),
+            "-w".to_string(),
+            "zebra 2".to_string(),
+        ];
+        let input: &[u8] = b"Learning Rust";
+
+        let result = with(args.as_slice(), input, Vec::new());
+
+        assert!(result.is_err())
+    }
+
+    #[test]
+    fn it_encrypts_with_vigenere_across_chunks() {
+        let args = vec![
+            "-c".to_string(),
+            "vigenere".to_string(),
+            "-w".to_string(),
+            "lemon".to_string(),
+        ];
+        // The keyword must carry on from one chunk to the next.
+        let text = "attackatdawn".repeat(CHUNK_SIZE / 12 + 1);
+        let mut output = Vec::new();
+
+        with(args.as_slice(), text.as_bytes(), &mut output).unwrap();
+
+        let expected = Vigenere::new("lemon").unwrap().exec(&text, caesar::Mode::Encrypt);
+        assert_eq!(expected, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn it_encrypts_and_decrypts_with_enigma() {
+        let args = vec![
+            "-c".to_string(),
+            "enigma".to_string(),
+            "-w".to_string(),
+            "B II,IV,V BUL BLA AV BS CG DL FU HZ IN KM OW RX".to_string(),
+        ];
+        let input: &[u8] = b"Learning Rust";
+        let mut output = Vec::new();
+
+        with(args.as_slice(), input, &mut output).unwrap();
+
+        let mut args = args;
+        args.push("-d".to_string());
+        let mut decrypted = Vec::new();
+        with(args.as_slice(), output.as_slice(), &mut decrypted).unwrap();
+
+        assert_ne!(input, output.as_slice());
+        assert_eq!(input, decrypted.as_slice())
+    }
+
+    #[test]
+    fn it_returns_error_on_invalid_enigma_settings() {
+        let args = vec![
+            "-c".to_string(),
+            "enigma".to_string(),
+            "-w".to_string(),
+            "B I,II AAA AAA".to_string(),
+        ];
+        let input: &[u8] = b"Learning Rust";
+
+        let err = with(args.as_slice(), input, Vec::new()).unwrap_err();
+
+        assert_eq!(Some(&SettingsError::Rotors), err.downcast_ref::<SettingsError>());
+    }
+
+    #[test]
+    fn it_encrypts_with_playfair() {
+        let args = vec![
+            "-c".to_string(),
+            "playfair".to_string(),
+            "-w".to_string(),
+            "playfair example".to_string(),
+        ];
+        let input: &[u8] = b"Hide the gold in the tree stump";
+        let mut output = Vec::new();
+
+        with(args.as_slice(), input, &mut output).unwrap();
+
+        assert_eq!(b"BMODZBXDNABEKUDMUIXMMOUVIF", output.as_slice())
+    }
+
+    #[test]
+    fn it_decrypts_with_hill() {
+        let args = vec![
+            "-c".to_string(),
+            "hill".to_string(),
+            "-w".to_string(),
+            "GYBNQKURP".to_string(),
+            "-d".to_string(),
+        ];
+        let input: &[u8] = b"POHFIN";
+        let mut output = Vec::new();
+
+        with(args.as_slice(), input, &mut output).unwrap();
+
+        assert_eq!(b"ACTCAT", output.as_slice())
+    }
+
+    #[test]
+    fn it_returns_error_when_hill_key_is_not_invertible() {
+        let args = vec![
+            "-c".to_string(),
+            "hill".to_string(),
+            "-w".to_string(),
+            "abcd".to_string(),
+        ];
+        let input: &[u8] = b"Learning Rust";
+
+        let err = with(args.as_slice(), input, Vec::new()).unwrap_err();
+
+        assert_eq!(Some(&hill::KeyError::NotInvertible { determinant: 24 }), err.downcast_ref::<hill::KeyError>());
+    }
+
+    // Keeps a copy of everything written so far on every flush.
+    #[derive(Default)]
+    struct FlushRecorder {
+        written: Vec<u8>,
+        flushed: Vec<String>,
+    }
+
+    impl Write for FlushRecorder {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushed.push(String::from_utf8(self.written.clone()).unwrap());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn it_flushes_every_line_in_line_buffered_mode() {
+        let args = vec![
+            "-k".to_string(),
+            "3".to_string(),
+            "--line-buffered".to_string(),
+        ];
+        let input: &[u8] = b"Hello\nWorld\r\n!";
+        let mut output = FlushRecorder::default();
+
+        with(args.as_slice(), input, &mut output).unwrap();
+
+        assert_eq!(vec!["Khoor\n", "Khoor\nZruog\r\n", "Khoor\nZruog\r\n!", "Khoor\nZruog\r\n!"], output.flushed);
+    }
+
+    #[test]
+    fn it_transposes_every_line_on_its_own_in_line_buffered_mode() {
+        let args = vec![
+            "-c".to_string(),
+            "railfence".to_string(),
+            "-k".to_string(),
+            "3".to_string(),
+            "--line-buffered".to_string(),
+        ];
+        let input: &[u8] = b"WEAREDISCOVEREDFLEEATONCE\nWEAREDISCOVEREDFLEEATONCE\n";
+        let mut output = Vec::new();
+
+        with(args.as_slice(), input, &mut output).unwrap();
+
+        assert_eq!(b"WECRLTEERDSOEEFEAOCAIVDEN\nWECRLTEERDSOEEFEAOCAIVDEN\n", output.as_slice());
+    }
+
+    #[test]
+    fn it_rotates_digits_and_reports_them_as_transformed() {
+        let args = vec![
+            "-k".to_string(),
+            "5".to_string(),
+            "--charset".to_string(),
+            "alphanumeric".to_string(),
+            "--stats".to_string(),
+        ];
+        let input: &[u8] = b"Call 555-0199";
+        let mut output = Vec::new();
+        let mut stderr = Vec::new();
+
+        with_stderr(args.as_slice(), input, &mut output, &mut stderr).unwrap();
+
+        let stderr = String::from_utf8(stderr).unwrap();
+        assert_eq!(b"Hfqq 000-5644", output.as_slice());
+        assert!(stderr.contains("Characters transformed:     11\n"));
+        assert!(stderr.contains("Characters passed through:  2\n"));
+    }
+
+    #[test]
+    fn it_round_trips_with_a_custom_alphabet() {
+        let args = vec![
+            "-k".to_string(),
+            "7".to_string(),
+            "--alphabet".to_string(),
+            "0123456789-/".to_string(),
+        ];
+        let input: &[u8] = b"Date: 2024/10/19, phone: 600-123-456";
+        let mut encrypted = Vec::new();
+
+        with(args.as_slice(), input, &mut encrypted).unwrap();
+
+        let mut args = args;
+        args.push("-d".to_string());
+        let mut decrypted = Vec::new();
+        with(args.as_slice(), encrypted.as_slice(), &mut decrypted).unwrap();
+
+        assert_eq!(b"Date: 979/687684, phone: 177589-5/01", encrypted.as_slice());
+        assert_eq!(input, decrypted.as_slice());
+    }
+
+    #[test]
+    fn it_only_transforms_the_regex_matches() {
+        let args = vec![
+            "-k".to_string(),
+            "3".to_string(),
+            "--regex".to_string(),
+            "<s>(.*?)</s>".to_string(),
+        ];
+        let input: &[u8] = b"Keep <s>secret data</s> here <s>x</s>";
+        let mut output = Vec::new();
+
+        with(args.as_slice(), input, &mut output).unwrap();
+
+        assert_eq!(b"Keep <s>vhfuhw gdwd</s> here <s>a</s>", output.as_slice());
+    }
+
+    #[test]
+    fn it_reports_stats_of_the_selected_regions_only() {
+        let args = vec![
+            "-k".to_string(),
+            "3".to_string(),
+            "--json".to_string(),
+            ".name".to_string(),
+            "--stats".to_string(),
+        ];
+        let input: &[u8] = b"{\"name\": \"Bob\", \"role\": \"admin\"}\n";
+        let mut output = Vec::new();
+        let mut stderr = Vec::new();
+
+        with_stderr(args.as_slice(), input, &mut output, &mut stderr).unwrap();
+
+        let stderr = String::from_utf8(stderr).unwrap();
+        assert_eq!(b"{\"name\": \"Ere\", \"role\": \"admin\"}\n", output.as_slice());
+        assert!(stderr.contains("Bytes processed:            33\n"));
+        assert!(stderr.contains("Characters transformed:     3\n"));
+        assert!(stderr.contains("Characters passed through:  30\n"));
+    }
+
+    #[test]
+    fn it_only_transforms_the_json_values_across_chunks() {
+        let args = vec![
+            "-c".to_string(),
+            "vigenere".to_string(),
+            "-w".to_string(),
+            "lemon".to_string(),
+            "--json".to_string(),
+            "[].name".to_string(),
+        ];
+        let record = r#"{"name": "Attack at dawn", "id": "ab"}, "#;
+        let text = format!("[{}{{}}]", record.repeat(CHUNK_SIZE / record.len() + 1));
+        let mut output = Vec::new();
+
+        with(args.as_slice(), text.as_bytes(), &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(text.len(), output.len());
+        assert!(output.starts_with(r#"[{"name": "Lxfopv ef rnhr", "id": "ab"}, {"name": "Mhglgw og oeib"#));
+        assert!(output.ends_with(r#", "id": "ab"}, {}]"#));
+    }
+
+    #[test]
+    fn it_only_transforms_the_csv_column_in_line_buffered_mode() {
+        let args = vec![
+            "-c".to_string(),
+            "railfence".to_string(),
+            "-k".to_string(),
+            "2".to_string(),
+            "--csv".to_string(),
+            "name".to_string(),
+            "--line-buffered".to_string(),
+        ];
+        let input: &[u8] = b"id,name\n1,Ann Lee\n2,\"Bob, Jr\"\n";
+        let mut output = Vec::new();
+
+        with(args.as_slice(), input, &mut output).unwrap();
+
+        assert_eq!(b"id,name\n1,AnLen e\n2,\"Bb ro,J\"\n", output.as_slice());
+    }
+
+    #[test]
+    fn it_returns_error_on_invalid_selections() {
+        let args = vec![
+            "-k".to_string(),
+            "3".to_string(),
+            "--json".to_string(),
+            "users".to_string(),
+        ];
+        let input: &[u8] = b"{\"users\": [}";
+
+        let err = with(args.as_slice(), input, Vec::new()).unwrap_err();
+
+        assert_eq!(Some(&select::SelectError::Json(11)), err.downcast_ref::<select::SelectError>());
+    }
+
+    #[test]
+    fn it_transposes_the_whole_text_across_chunks() {
+        let args = vec![
+            "-c".to_string(),
+            "columnar".to_string(),
+            "-w".to_string(),
+            "zebras,lemon".to_string(),
+        ];
+        let text = "We are discovered, flee at once. ".repeat(CHUNK_SIZE / 32 + 1);
+        let mut output = Vec::new();
+
+        with(args.as_slice(), text.as_bytes(), &mut output).unwrap();
+
+        let expected = Columnar::new("zebras,lemon", None).unwrap().exec(&text, caesar::Mode::Encrypt);
+        assert_eq!(expected, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn it_chains_substitution_with_transposition() {
+        let substitution = vec!["-c".to_string(), "vigenere".to_string(), "-w".to_string(), "lemon".to_string()];
+        let transposition = vec!["-c".to_string(), "railfence".to_string(), "-k".to_string(), "3".to_string()];
+        let input: &[u8] = b"We are discovered, flee at once";
+        let mut substituted = Vec::new();
+        let mut transposed = Vec::new();
+
+        with(substitution.as_slice(), input, &mut substituted).unwrap();
+        with(transposition.as_slice(), substituted.as_slice(), &mut transposed).unwrap();
+
+        let mut transposition = transposition;
+        transposition.push("-d".to_string());
+        let mut substitution = substitution;
+        substitution.push("-d".to_string());
+        let mut restored = Vec::new();
+        let mut output = Vec::new();
+        with(transposition.as_slice(), transposed.as_slice(), &mut restored).unwrap();
+        with(substitution.as_slice(), restored.as_slice(), &mut output).unwrap();
+
+        assert_ne!(substituted, transposed);
+        assert_eq!(input, output.as_slice())
+    }
+
+    #[test]
+    fn it_lists_vigenere_key_candidates() {
+        let args = vec![
+            "crack".to_string(),
+            "-c".to_string(),
+            "vigenere".to_string(),
+        ];
+        let plaintext = "The quick growth of the town surprised everyone who had lived there for many years. New \
+        houses appeared along the river, and the old market square was filled every morning with farmers selling \
+        bread, cheese and fresh vegetables to the families that had recently arrived.";
+        let input = Vigenere::new("lemon").unwrap().exec(plaintext, caesar::Mode::Encrypt);
+        let mut output = Vec::new();
+
+        with(args.as_slice(), input.as_bytes(), &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("lemon 1.000\n"), "unexpected output {}", output)
+    }
+
+    #[test]
+    fn it_lists_crib_matches() {
+        let args = vec![
+            "crib".to_string(),
+            "-p".to_string(),
+            "the".to_string(),
+        ];
+        let input = Caesar::exec("the rabbit saw the fox", 4, caesar::Mode::Encrypt).unwrap();
+        let mut output = Vec::new();
+
+        with(args.as_slice(), input.as_bytes(), &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!("0 4\n15 4\n", output)
+    }
+
+    #[test]
+    fn it_reports_progress_and_stats_to_stderr() {
+        let input_file_path = tmp_path();
+        fs::write(&input_file_path, "Learning Rust 2023").unwrap();
+
+        let args = vec![
+            "-k".to_string(),
+            "1".to_string(),
+            "-i".to_string(),
+            input_file_path.to_owned(),
+            "--progress".to_string(),
+            "--stats".to_string(),
+        ];
+        let input: &[u8] = b"";
+        let mut output = Vec::new();
+        let mut stderr = Vec::new();
+
+        with_stderr(args.as_slice(), input, &mut output, &mut stderr).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!("Mfbsojoh Svtu 2023", output);
+        let stderr = String::from_utf8(stderr).unwrap();
+        assert!(stderr.contains("100% 0.0/0.0 MB"));
+        assert!(stderr.contains("Characters transformed:     12\n"));
+        assert!(stderr.contains("Characters passed through:  6\n"));
+
+        fs::remove_file(input_file_path).unwrap()
+    }
+
+    #[test]
+    fn it_does_not_write_to_stderr_by_default() {
+        let args = vec![
+            "-k".to_string(),
+            "1".to_string(),
+        ];
+        let input: &[u8] = b"Learning Rust";
+        let mut stderr = Vec::new();
+
+        with_stderr(args.as_slice(), input, Vec::new(), &mut stderr).unwrap();
+
+        assert!(stderr.is_empty())
+    }
+
+    #[test]
+    fn it_processes_chars_split_between_chunks() {
+        let args = vec![
+            "-k".to_string(),
+            "1".to_string(),
+        ];
+        // The two bytes of "З" end up in different chunks.
+        let mut text = "a".repeat(CHUNK_SIZE - 1);
+        text.push_str("ЗaЗ");
+        let mut output = Vec::new();
+
+        with(args.as_slice(), text.as_bytes(), &mut output).unwrap();
+
+        let mut expected = "b".repeat(CHUNK_SIZE - 1);
+        expected.push_str("ЗbЗ");
+        assert_eq!(expected, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn it_returns_error_on_invalid_utf8() {
+        let args = vec![
+            "-k".to_string(),
+            "1".to_string(),
+        ];
+        let input: &[u8] = b"abc\xff";
+
+        let result = with(args.as_slice(), input, Vec::new());
+
+        assert!(result.is_err())
+    }
+
+    #[test]
+    fn it_refuses_to_overwrite_the_input_file() {
+        let file_path = tmp_path();
+        fs::write(&file_path, "Learning Rust").unwrap();
+
+        let args = vec![
+            "-k".to_string(),
+            "1".to_string(),
+            "-i".to_string(),
+            file_path.to_owned(),
+            "-o".to_string(),
+            file_path.to_owned(),
+        ];
+        let input: &[u8] = b"";
+
+        let err = with(args.as_slice(), input, Vec::new()).unwrap_err();
+
+        assert_eq!(Some(&OutputError::SameFile), err.downcast_ref::<OutputError>());
+        assert_eq!("Learning Rust", fs::read_to_string(&file_path).unwrap());
+
+        fs::remove_file(file_path).unwrap()
+    }
+
+    #[test]
+    fn it_overwrites_the_input_file_in_place() {
+        let file_path = tmp_path();
+        fs::write(&file_path, "Learning Rust").unwrap();
+
+        let args = vec![
+            "-k".to_string(),
+            "1".to_string(),
+            "-i".to_string(),
+            file_path.to_owned(),
+            "--in-place".to_string(),
+        ];
+        let input: &[u8] = b"";
+
+        with(args.as_slice(), input, Vec::new()).unwrap();
+
+        assert_eq!("Mfbsojoh Svtu", fs::read_to_string(&file_path).unwrap());
+
+        fs::remove_file(file_path).unwrap()
+    }
+
+    #[test]
+    fn it_does_not_clobber_the_output_file() {
+        let output_file_path = tmp_path();
+        fs::write(&output_file_path, "Important stuff").unwrap();
+
+        let args = vec![
+            "-k".to_string(),
+            "1".to_string(),
+            "-o".to_string(),
+            output_file_path.to_owned(),
+            "--no-clobber".to_string(),
+        ];
+        let input: &[u8] = b"Learning Rust";
+
+        let err = with(args.as_slice(), input, Vec::new()).unwrap_err();
+
+        assert_eq!(Some(&OutputError::Exists), err.downcast_ref::<OutputError>());
+        assert_eq!("Important stuff", fs::read_to_string(&output_file_path).unwrap());
+
+        fs::remove_file(output_file_path).unwrap()
+    }
+
+    #[test]
+    fn it_keeps_the_output_file_untouched_on_errors() {
+        let output_file_path = tmp_path();
+        fs::write(&output_file_path, "Important stuff").unwrap();
+
+        let args = vec![
+            "-k".to_string(),
+            "1".to_string(),
+            "-o".to_string(),
+            output_file_path.to_owned(),
+        ];
+        let input: &[u8] = b"Learning Rust \xff";
+
+        with(args.as_slice(), input, Vec::new()).unwrap_err();
+
+        assert_eq!("Important stuff", fs::read_to_string(&output_file_path).unwrap());
+
+        fs::remove_file(output_file_path).unwrap()
+    }
+
+    #[test]
+    fn it_prints_completions() {
+        let args = vec![
+            "completions".to_string(),
+            "bash".to_string(),
+        ];
+        let input: &[u8] = b"";
+        let mut output = Vec::new();
+
+        with(args.as_slice(), input, &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("_caesar() {\n"))
+    }
+
+    #[test]
+    fn it_prints_the_man_page() {
+        let args = vec![
+            "man".to_string(),
+        ];
+        let input: &[u8] = b"";
+        let mut output = Vec::new();
+
+        with(args.as_slice(), input, &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with(&format!(".TH CAESAR 1 \"\" \"caesar {}\"", VERSION)));
+        assert!(output.contains(".TP\n\\fB\\-\\-no\\-clobber\\fR\n"));
+        assert!(output.contains(".TP\n\\fB\\-k\\fR \\fIkey\\fR\nThe key,"));
+    }
+
+    #[test]
+    fn it_runs_the_selftest() {
+        let args = vec![
+            "selftest".to_string(),
+        ];
+        let input: &[u8] = b"";
+        let mut output = Vec::new();
+
+        with(args.as_slice(), input, &mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with("ok    -k 1 -e\n"));
+        assert!(output.contains("ok    -c hill -w abcd\n"));
+        assert!(!output.contains("FAIL"));
+    }
+
     fn tmp_path() -> String {
         format!("{}{}{}", "/tmp/rust-test-", Uuid::new_v4(), ".txt")
     }
@@ -0,0 +1,213 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use crate::caesar::Mode;
+
+const MODULUS: i64 = 26;
+// Completes the last block of letters.
+const FILLER: u8 = b'X';
+
+/// Hill cipher. Letters are encrypted in blocks of n, as vectors multiplied
+/// by a n×n key matrix, modulo 26. Decryption multiplies by the inverse of
+/// the matrix, so only matrices that can be inverted modulo 26 are valid keys.
+///
+/// Only letters are encrypted, in uppercase. Any other character is dropped,
+/// and fillers complete the last block. Decryption keeps the fillers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hill {
+    key: Vec<Vec<i64>>,
+    inverse: Vec<Vec<i64>>,
+}
+
+impl Hill {
+    /// The matrix must be square, with rows of the same length as the blocks.
+    pub fn new(key: Vec<Vec<i64>>) -> Result<Hill, KeyError> {
+        if key.is_empty() || key.iter().any(|row| row.len() != key.len()) {
+            return Err(KeyError::NotSquare);
+        }
+        let key: Vec<Vec<i64>> = key.iter().map(|row| row.iter().map(|v| v.rem_euclid(MODULUS)).collect()).collect();
+        let n = key.len();
+        // Gauss-Jordan elimination: the row operations that take the key to
+        // the identity take the identity to the inverse.
+        let mut reduced = key.clone();
+        let mut inverse: Vec<Vec<i64>> = (0..n).map(|i| (0..n).map(|j| (i == j) as i64).collect()).collect();
+        let key_determinant = triangulate(&mut reduced, &mut inverse);
+        if modular_inverse(key_determinant).is_none() {
+            return Err(KeyError::NotInvertible { determinant: key_determinant });
+        }
+        back_substitute(&mut reduced, &mut inverse);
+        Ok(Hill { key, inverse })
+    }
+
+    /// Takes the matrix from a keyword whose length is a square number, filling
+    /// it row by row with the positions of the letters in the alphabet.
+    pub fn from_keyword(keyword: &str) -> Result<Hill, KeyError> {
+        if keyword.is_empty() || !keyword.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(KeyError::NotSquare);
+        }
+        let n = (1..=keyword.len()).find(|n| n * n >= keyword.len()).unwrap();
+        if n * n != keyword.len() {
+            return Err(KeyError::NotSquare);
+        }
+        let values: Vec<i64> = letters(keyword).map(|l| l as i64).collect();
+        Hill::new(values.chunks(n).map(|row| row.to_vec()).collect())
+    }
+
+    pub fn inverse(&self) -> &Vec<Vec<i64>> {
+        &self.inverse
+    }
+
+    pub fn exec(&self, input: &str, mode: Mode) -> String {
+        let matrix = match mode {
+            Mode::Encrypt => &self.key,
+            Mode::Decrypt => &self.inverse,
+        };
+        let n = matrix.len();
+        let mut letters: Vec<i64> = letters(input).map(|l| l as i64).collect();
+        letters.resize(letters.len().div_ceil(n) * n, (FILLER - b'A') as i64);
+        letters.chunks(n)
+            .flat_map(|block| matrix.iter()
+                .map(|row| row.iter().zip(block).map(|(k, l)| k * l).sum::<i64>().rem_euclid(MODULUS))
+                .map(|l| (b'A' + l as u8) as char)
+                .collect::<Vec<char>>())
+            .collect()
+    }
+}
+
+fn letters(text: &str) -> impl Iterator<Item=u8> + '_ {
+    text.bytes()
+        .filter(|b| b.is_ascii_alphabetic())
+        .map(|b| b.to_ascii_uppercase() - b'A')
+}
+
+// Makes the matrix upper triangular with row operations modulo 26, doing the
+// same ones on the companion, and returns the determinant. 26 is not prime, so
+// the pivots can't always be divided by. Instead, two rows are subtracted
+// from each other like in the Euclidean algorithm, until one of them has a 0
+// in the column.
+fn triangulate(matrix: &mut [Vec<i64>], companion: &mut [Vec<i64>]) -> i64 {
+    let mut determinant = 1;
+    for column in 0..matrix.len() {
+        for row in column + 1..matrix.len() {
+            while matrix[row][column] != 0 {
+                let quotient = matrix[column][column] / matrix[row][column];
+                subtract_row(matrix, column, row, quotient);
+                subtract_row(companion, column, row, quotient);
+                matrix.swap(column, row);
+                companion.swap(column, row);
+                determinant = -determinant;
+            }
+        }
+        determinant = (determinant * matrix[column][column]).rem_euclid(MODULUS);
+    }
+    determinant
+}
+
+// Turns the triangular matrix into the identity, doing the same on the
+// companion. The pivots multiply to the determinant, so when it can be
+// inverted, all of them can.
+fn back_substitute(matrix: &mut [Vec<i64>], companion: &mut [Vec<i64>]) {
+    for column in (0..matrix.len()).rev() {
+        let pivot_inverse = modular_inverse(matrix[column][column]).expect("the pivots of an invertible matrix can be inverted");
+        for values in [&mut matrix[column], &mut companion[column]] {
+            values.iter_mut().for_each(|v| *v = (*v * pivot_inverse).rem_euclid(MODULUS));
+        }
+        for row in 0..column {
+            let factor = matrix[row][column];
+            subtract_row(matrix, row, column, factor);
+            subtract_row(companion, row, column, factor);
+        }
+    }
+}
+
+// Subtracts a multiple of the source row from the target one.
+fn subtract_row(matrix: &mut [Vec<i64>], target: usize, source: usize, factor: i64) {
+    for j in 0..matrix[target].len() {
+        matrix[target][j] = (matrix[target][j] - factor * matrix[source][j]).rem_euclid(MODULUS);
+    }
+}
+
+fn modular_inverse(value: i64) -> Option<i64> {
+    (1..MODULUS).find(|candidate| (value * candidate).rem_euclid(MODULUS) == 1)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum KeyError {
+    NotSquare,
+    /// The determinant shares a factor with 26, so the matrix cannot be inverted.
+    NotInvertible { determinant: i64 },
+}
+
+impl Display for KeyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyError::NotSquare => write!(f, "the key must be a square matrix, or a keyword of 4, 9, 16... letters."),
+            KeyError::NotInvertible { determinant } => write!(f, "the key matrix is not invertible modulo 26, \
+            as its determinant {} shares a factor with 26. Decryption would not be possible.", determinant),
+        }
+    }
+}
+
+impl Error for KeyError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_encrypts() {
+        let hill = Hill::from_keyword("GYBNQKURP").unwrap();
+        assert_eq!("POH", hill.exec("act", Mode::Encrypt));
+        assert_eq!("FIN", hill.exec("cat", Mode::Encrypt));
+    }
+
+    #[test]
+    fn it_inverts_the_key_matrix() {
+        let hill = Hill::new(vec![vec![6, 24, 1], vec![13, 16, 10], vec![20, 17, 15]]).unwrap();
+        assert_eq!(&vec![vec![8, 5, 10], vec![21, 8, 21], vec![21, 12, 8]], hill.inverse());
+    }
+
+    #[test]
+    fn it_inverts_big_key_matrices() {
+        // Upper triangular with odd values other than 13 in the diagonal, so
+        // it can be inverted, and mixed up by adding the rows to each other.
+        let n = 16;
+        let mut key: Vec<Vec<i64>> = (0..n).map(|i| (0..n).map(|j| if j < i { 0 } else if i == j { 3 } else { (i * j) as i64 }).collect()).collect();
+        for i in 1..n {
+            let previous = key[i - 1].clone();
+            key[i].iter_mut().zip(previous).for_each(|(v, p)| *v += 5 * p);
+        }
+        let keyword: String = key.iter().flatten().map(|v| (b'a' + v.rem_euclid(MODULUS) as u8) as char).collect();
+
+        let hill = Hill::from_keyword(&keyword).unwrap();
+
+        let text = "THEQUICKBROWNFOXJUMPSOVERTHELAZYDOG";
+        let encrypted = hill.exec(text, Mode::Encrypt);
+        assert_eq!(format!("{}XXXXXXXXXXXXX", text), hill.exec(&encrypted, Mode::Decrypt));
+        assert_eq!(KeyError::NotInvertible { determinant: 0 }, Hill::from_keyword(&"a".repeat(n * n)).unwrap_err());
+    }
+
+    #[test]
+    fn it_decrypts_keeping_the_fillers() {
+        let hill = Hill::new(vec![vec![3, 3], vec![2, 5]]).unwrap();
+        assert_eq!("HIAT", hill.exec("Help", Mode::Encrypt));
+        let encrypted = hill.exec("Help me now!", Mode::Encrypt);
+        assert_eq!(10, encrypted.len());
+        assert_eq!("HELPMENOWX", hill.exec(&encrypted, Mode::Decrypt));
+    }
+
+    #[test]
+    fn it_returns_error_when_the_key_is_not_invertible() {
+        let err = Hill::from_keyword("abcd").unwrap_err();
+        assert_eq!(KeyError::NotInvertible { determinant: 24 }, err);
+        assert!(err.to_string().contains("not invertible"));
+    }
+
+    #[test]
+    fn it_returns_error_when_the_key_is_not_square() {
+        assert_eq!(KeyError::NotSquare, Hill::from_keyword("abc").unwrap_err());
+        assert_eq!(KeyError::NotSquare, Hill::from_keyword("").unwrap_err());
+        assert_eq!(KeyError::NotSquare, Hill::new(vec![vec![1, 2], vec![3]]).unwrap_err());
+    }
+}
@@ -0,0 +1,377 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::sync::OnceLock;
+
+const ALPHABET_LEN: usize = 26;
+const QUADGRAMS_LEN: usize = ALPHABET_LEN * ALPHABET_LEN * ALPHABET_LEN * ALPHABET_LEN;
+
+// Probability assigned to grams that never appeared in the training corpus,
+// so a single unseen pair does not send a candidate score to minus infinity.
+const FLOOR_PROBABILITY: f64 = 1e-6;
+
+/// The languages for which a model is shipped with the crate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    English,
+    Spanish,
+    French,
+    German,
+}
+
+impl Language {
+    /// Accepts the ISO 639-1 code of the language (en, es, fr, de).
+    pub fn from_code(code: &str) -> Option<Language> {
+        match code.to_ascii_lowercase().as_str() {
+            "en" => Some(Language::English),
+            "es" => Some(Language::Spanish),
+            "fr" => Some(Language::French),
+            "de" => Some(Language::German),
+            _ => None,
+        }
+    }
+}
+
+/// Unigram, bigram and quadgram letter frequencies of a language, used for
+/// scoring how much a piece of text looks like that language.
+///
+/// Only ascii letters are taken into account, case insensitive. Bigrams
+/// are only counted between letters that are adjacent in the text, so
+/// word boundaries are respected. Quadgrams are counted over the letters
+/// alone, skipping anything else, as short words would not have any.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Model {
+    unigrams: [f64; ALPHABET_LEN],
+    bigrams: [f64; ALPHABET_LEN * ALPHABET_LEN],
+    /// Empty when the model has no quadgrams, as they take a few megabytes.
+    quadgrams: Vec<f64>,
+}
+
+impl Model {
+    /// Returns one of the models embedded in the binary. They are parsed the
+    /// first time they are needed only.
+    pub fn builtin(language: Language) -> &'static Model {
+        static MODELS: [OnceLock<Model>; 4] = [OnceLock::new(), OnceLock::new(), OnceLock::new(), OnceLock::new()];
+        let (index, definition, quadgrams) = match language {
+            Language::English => (0, include_str!("../models/en.txt"), include_str!("../models/en.quadgrams.txt")),
+            Language::Spanish => (1, include_str!("../models/es.txt"), include_str!("../models/es.quadgrams.txt")),
+            Language::French => (2, include_str!("../models/fr.txt"), include_str!("../models/fr.quadgrams.txt")),
+            Language::German => (3, include_str!("../models/de.txt"), include_str!("../models/de.quadgrams.txt")),
+        };
+        MODELS[index].get_or_init(|| {
+            let mut model = Model::parse(definition).expect("built-in models must be valid");
+            model.read(quadgrams).expect("built-in models must be valid");
+            model
+        })
+    }
+
+    /// Builds a model by counting the letters of the provided corpus.
+    pub fn train(corpus: &str) -> Result<Model, ModelError> {
+        let mut unigram_counts = [0_u64; ALPHABET_LEN];
+        let mut bigram_counts = [0_u64; ALPHABET_LEN * ALPHABET_LEN];
+        let mut quadgram_counts = vec![0_u64; QUADGRAMS_LEN];
+        let mut previous: Option<usize> = None;
+        let mut letters = 0;
+        let mut quadgram = 0;
+
+        for c in corpus.chars() {
+            match letter_index(c) {
+                Some(current) => {
+                    unigram_counts[current] += 1;
+                    if let Some(previous) = previous {
+                        bigram_counts[previous * ALPHABET_LEN + current] += 1;
+                    }
+                    previous = Some(current);
+                    letters += 1;
+                    quadgram = (quadgram * ALPHABET_LEN + current) % QUADGRAMS_LEN;
+                    if letters >= 4 {
+                        quadgram_counts[quadgram] += 1;
+                    }
+                }
+                None => previous = None,
+            }
+        }
+
+        let unigram_total: u64 = unigram_counts.iter().sum();
+        let bigram_total: u64 = bigram_counts.iter().sum();
+        let quadgram_total: u64 = quadgram_counts.iter().sum();
+        if unigram_total == 0 || bigram_total == 0 {
+            return Err(ModelError);
+        }
+
+        let mut model = Model::empty();
+        for (i, count) in unigram_counts.iter().enumerate() {
+            model.unigrams[i] = *count as f64 / unigram_total as f64;
+        }
+        for (i, count) in bigram_counts.iter().enumerate() {
+            model.bigrams[i] = *count as f64 / bigram_total as f64;
+        }
+        if quadgram_total > 0 {
+            model.quadgrams = quadgram_counts.iter().map(|count| *count as f64 / quadgram_total as f64).collect();
+        }
+        Ok(model)
+    }
+
+    /// Parses a model in the format produced by its `Display` implementation.
+    ///
+    /// Every line holds a gram (one, two or four letters) followed by its frequency.
+    /// Empty lines and lines starting with `#` are ignored.
+    pub fn parse(definition: &str) -> Result<Model, ModelError> {
+        let mut model = Model::empty();
+        model.read(definition)?;
+        if model.unigrams.iter().all(|f| *f == 0.0) {
+            return Err(ModelError);
+        }
+        Ok(model)
+    }
+
+    // Sets the frequencies of the grams in the definition, so a model can be
+    // read from several of them.
+    fn read(&mut self, definition: &str) -> Result<(), ModelError> {
+        for line in definition.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split_whitespace();
+            let (gram, frequency) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(gram), Some(frequency), None) => (gram, frequency),
+                _ => return Err(ModelError),
+            };
+            let frequency: f64 = frequency.parse().map_err(|_| ModelError)?;
+            if !(0.0..=1.0).contains(&frequency) {
+                return Err(ModelError);
+            }
+            let letters: Vec<usize> = gram.chars().map(letter_index).collect::<Option<_>>().ok_or(ModelError)?;
+            match letters.as_slice() {
+                [a] => self.unigrams[*a] = frequency,
+                [a, b] => self.bigrams[a * ALPHABET_LEN + b] = frequency,
+                [a, b, c, d] => {
+                    self.quadgrams.resize(QUADGRAMS_LEN, 0.0);
+                    self.quadgrams[quadgram_index(*a, *b, *c, *d)] = frequency
+                }
+                _ => return Err(ModelError),
+            }
+        }
+        Ok(())
+    }
+
+    fn empty() -> Model {
+        Model {
+            unigrams: [0.0; ALPHABET_LEN],
+            bigrams: [0.0; ALPHABET_LEN * ALPHABET_LEN],
+            quadgrams: Vec::new(),
+        }
+    }
+
+    /// Whether the model can be used for quadgram scoring. Models trained
+    /// with very short corpora might lack them.
+    pub fn has_quadgrams(&self) -> bool {
+        self.quadgrams.iter().any(|f| *f > 0.0)
+    }
+
+    pub(crate) fn unigram_frequencies(&self) -> &[f64; ALPHABET_LEN] {
+        &self.unigrams
+    }
+
+    /// Log probabilities of all the quadgrams, indexed by the position of
+    /// their letters in the alphabet, as in `((a * 26 + b) * 26 + c) * 26 + d`.
+    pub(crate) fn quadgram_log_probabilities(&self) -> Vec<f64> {
+        // Unseen quadgrams score below the rarest seen one, less so the more
+        // likely their bigrams are, so partly right decryptions still score
+        // better than the wrong ones.
+        let floor = self.quadgrams.iter().filter(|f| **f > 0.0).fold(f64::MAX, |a, b| a.min(*b)) / 10.0;
+        let top = self.bigrams.iter().fold(FLOOR_PROBABILITY, |a, b| a.max(*b));
+        let bigram = |a: usize, b: usize| self.bigrams[a * ALPHABET_LEN + b].max(FLOOR_PROBABILITY) / top;
+        self.quadgrams.iter().enumerate()
+            .map(|(i, f)| match *f > 0.0 {
+                true => f.ln(),
+                false => {
+                    let (a, b, c, d) = (i / ALPHABET_LEN.pow(3), i / ALPHABET_LEN.pow(2) % ALPHABET_LEN, i / ALPHABET_LEN % ALPHABET_LEN, i % ALPHABET_LEN);
+                    (floor * bigram(a, b) * bigram(b, c) * bigram(c, d)).ln()
+                }
+            })
+            .collect()
+    }
+
+    /// Scores how likely the text belongs to the language of the model.
+    /// Higher is better. Scores are only comparable between texts of similar length.
+    pub fn score(&self, text: &str) -> f64 {
+        let mut unigram_score = 0.0;
+        let mut unigram_count = 0;
+        let mut bigram_score = 0.0;
+        let mut bigram_count = 0;
+        let mut previous: Option<usize> = None;
+
+        for c in text.chars() {
+            match letter_index(c) {
+                Some(current) => {
+                    unigram_score += log_probability(self.unigrams[current]);
+                    unigram_count += 1;
+                    if let Some(previous) = previous {
+                        bigram_score += log_probability(self.bigrams[previous * ALPHABET_LEN + current]);
+                        bigram_count += 1;
+                    }
+                    previous = Some(current)
+                }
+                None => previous = None,
+            }
+        }
+        if unigram_count == 0 {
+            return f64::NEG_INFINITY;
+        }
+        let mut score = unigram_score / unigram_count as f64;
+        if bigram_count > 0 {
+            score += bigram_score / bigram_count as f64;
+        }
+        score
+    }
+}
+
+impl Display for Model {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for (i, frequency) in self.unigrams.iter().enumerate() {
+            writeln!(f, "{} {:.8}", letter(i), frequency)?;
+        }
+        for (i, frequency) in self.bigrams.iter().enumerate() {
+            if *frequency > 0.0 {
+                writeln!(f, "{}{} {:.8}", letter(i / ALPHABET_LEN), letter(i % ALPHABET_LEN), frequency)?;
+            }
+        }
+        for (i, frequency) in self.quadgrams.iter().enumerate() {
+            if *frequency > 0.0 {
+                writeln!(f, "{}{}{}{} {:.8}", letter(i / ALPHABET_LEN.pow(3)), letter(i / ALPHABET_LEN.pow(2) % ALPHABET_LEN),
+                         letter(i / ALPHABET_LEN % ALPHABET_LEN), letter(i % ALPHABET_LEN), frequency)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn letter_index(c: char) -> Option<usize> {
+    match c.is_ascii_alphabetic() {
+        true => Some((c.to_ascii_lowercase() as u8 - b'a') as usize),
+        false => None,
+    }
+}
+
+fn quadgram_index(a: usize, b: usize, c: usize, d: usize) -> usize {
+    ((a * ALPHABET_LEN + b) * ALPHABET_LEN + c) * ALPHABET_LEN + d
+}
+
+fn letter(index: usize) -> char {
+    (b'a' + index as u8) as char
+}
+
+fn log_probability(frequency: f64) -> f64 {
+    frequency.max(FLOOR_PROBABILITY).ln()
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ModelError;
+
+const MODEL_ERROR_MSG: &str = "the language model is not valid. It needs lines with a gram of 1, 2 or 4 letters and its frequency.";
+
+impl Display for ModelError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", MODEL_ERROR_MSG)
+    }
+}
+
+impl Error for ModelError {
+    fn description(&self) -> &str {
+        MODEL_ERROR_MSG
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_trains_unigrams_and_bigrams() {
+        let model = Model::train("Aab b").unwrap();
+        assert_eq!(0.5, model.unigrams[0]);
+        assert_eq!(0.5, model.unigrams[1]);
+        // Only "aa" and "ab", the space breaks the "bb" pair.
+        assert_eq!(0.5, model.bigrams[0]);
+        assert_eq!(0.5, model.bigrams[1]);
+        assert_eq!(0.0, model.bigrams[ALPHABET_LEN + 1]);
+        // Quadgrams skip the space, so "aabb" is the only one.
+        assert_eq!(1.0, model.quadgrams[quadgram_index(0, 0, 1, 1)]);
+        assert!(model.has_quadgrams());
+    }
+
+    #[test]
+    fn it_returns_error_when_training_without_letters() {
+        let result = Model::train("1 2 3 !").unwrap_err();
+        assert_eq!(ModelError, result);
+    }
+
+    #[test]
+    fn it_round_trips_through_its_text_format() {
+        let model = Model::train("the quick brown fox jumps over the lazy dog").unwrap();
+        let parsed = Model::parse(&model.to_string()).unwrap();
+        assert_eq!(model.to_string(), parsed.to_string());
+    }
+
+    #[test]
+    fn it_returns_error_on_malformed_model() {
+        assert_eq!(ModelError, Model::parse("").unwrap_err());
+        assert_eq!(ModelError, Model::parse("a").unwrap_err());
+        assert_eq!(ModelError, Model::parse("a x").unwrap_err());
+        assert_eq!(ModelError, Model::parse("abc 0.1").unwrap_err());
+        assert_eq!(ModelError, Model::parse("abcde 0.1").unwrap_err());
+        assert_eq!(ModelError, Model::parse("1 0.1").unwrap_err());
+        assert_eq!(ModelError, Model::parse("a 2.0").unwrap_err());
+    }
+
+    #[test]
+    fn it_parses_quadgrams() {
+        let model = Model::parse("a 1.0\ntion 0.5").unwrap();
+        assert_eq!(0.5, model.quadgrams[quadgram_index(19, 8, 14, 13)]);
+        assert!(model.to_string().contains("\ntion 0.50000000\n"));
+    }
+
+    #[test]
+    fn it_scores_unseen_quadgrams_by_their_bigrams() {
+        let model = Model::parse("a 0.5\nb 0.5\nab 0.9\nba 0.1\nabab 1.0").unwrap();
+        let log_probabilities = model.quadgram_log_probabilities();
+
+        let abab = log_probabilities[quadgram_index(0, 1, 0, 1)];
+        let baba = log_probabilities[quadgram_index(1, 0, 1, 0)];
+        let bbbb = log_probabilities[quadgram_index(1, 1, 1, 1)];
+        assert!(abab > baba);
+        assert!(baba > bbbb);
+    }
+
+    #[test]
+    fn it_loads_all_builtin_models() {
+        for language in [Language::English, Language::Spanish, Language::French, Language::German] {
+            assert!(Model::builtin(language).has_quadgrams());
+            assert!(std::ptr::eq(Model::builtin(language), Model::builtin(language)));
+        }
+    }
+
+    #[test]
+    fn it_leaves_the_quadgrams_out_of_models_without_them() {
+        let model = Model::parse("a 0.5\nb 0.5\nab 1.0").unwrap();
+        assert!(model.quadgrams.is_empty());
+        assert!(!model.has_quadgrams());
+        assert!(Model::train("ab b").unwrap().quadgrams.is_empty());
+    }
+
+    #[test]
+    fn it_scores_plain_text_higher_than_random_letters() {
+        let model = Model::builtin(Language::Spanish);
+        let plain = model.score("En un lugar de la Mancha, de cuyo nombre no quiero acordarme");
+        let noise = model.score("Qz xw kjvqp fz yx Wkxqzv, zj qwyk vjxkzq xz hjqzkv kzxqjwkvz");
+        assert!(plain > noise)
+    }
+
+    #[test]
+    fn it_parses_language_codes() {
+        assert_eq!(Some(Language::Spanish), Language::from_code("ES"));
+        assert_eq!(None, Language::from_code("xx"));
+    }
+}
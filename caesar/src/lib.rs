@@ -1,3 +1,18 @@
 mod args;
 pub mod caesar;
+mod completions;
+pub mod crack;
+pub mod enigma;
 pub mod exec;
+pub mod hill;
+pub mod lang;
+mod options;
+mod output;
+pub mod pattern;
+pub mod playfair;
+mod progress;
+pub mod select;
+mod selftest;
+pub mod substitution;
+pub mod transposition;
+pub mod vigenere;
@@ -0,0 +1,152 @@
+//! Definition of the commands and arguments of the CLI.
+//!
+//! This is the single source for the help menu, the man page and the shell
+//! completions, so they can never drift from each other. The build script
+//! includes this file for rendering the help menu and the man page, so it
+//! must only depend on the standard library.
+
+pub struct Cmd {
+    pub name: &'static str,
+    pub value: Option<Value>,
+    pub help: &'static str,
+}
+
+pub struct Opt {
+    pub flag: &'static str,
+    pub value: Option<Value>,
+    pub help: &'static str,
+}
+
+/// The kind of value an argument expects, used to offer the right completions.
+pub enum Value {
+    Number(&'static str),
+    Text(&'static str),
+    File,
+    Choice(&'static str, &'static [&'static str]),
+}
+
+impl Value {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Value::Number(name) | Value::Text(name) | Value::Choice(name, _) => name,
+            Value::File => "file",
+        }
+    }
+}
+
+pub const LANGUAGES: &[&str] = &["en", "es", "fr", "de"];
+pub const CIPHERS: &[&str] = &["caesar", "substitution", "vigenere", "railfence", "columnar", "enigma", "playfair",
+    "hill"];
+pub const CHARSETS: &[&str] = &["letters", "alphanumeric", "printable"];
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish"];
+
+pub const COMMANDS: &[Cmd] = &[
+    Cmd {
+        name: "crack",
+        value: None,
+        help: "Finds the key of a ciphertext by scoring all the decryptions against a language model.\nWith -c substitution, the key is searched by hill climbing over quadgram scores.\nWith -c vigenere, outputs a line with every candidate key and its confidence, best first.\nOnly caesar, substitution and vigenere can be cracked.",
+    },
+    Cmd {
+        name: "train",
+        value: None,
+        help: "Builds a language model from a training corpus, to be used later with crack -m.",
+    },
+    Cmd {
+        name: "crib",
+        value: None,
+        help: "Finds the keys that turn a known plaintext (-p) into some part of the ciphertext.\nOutputs a line with the char offset and the key for every match.",
+    },
+    Cmd {
+        name: "completions",
+        value: Some(Value::Choice("shell", SHELLS)),
+        help: "Prints the completion script for the given shell. One of bash, zsh, fish.",
+    },
+    Cmd {
+        name: "man",
+        value: None,
+        help: "Prints the man page.",
+    },
+    Cmd {
+        name: "selftest",
+        value: None,
+        help: "Checks the ciphers against embedded known-answer vectors, printing a line for each one.\nExits with an error if any of them does not match.",
+    },
+];
+
+pub const OPTIONS: &[Opt] = &[
+    Opt { flag: "-h", value: None, help: "Shows this menu." },
+    Opt { flag: "-v", value: None, help: "Shows the version." },
+    Opt {
+        flag: "-k",
+        value: Some(Value::Number("key")),
+        help: "The key, or positive shift number of the cipher (mandatory). Max is a 6 digit number.\nFor railfence, the number of rails.",
+    },
+    Opt {
+        flag: "-c",
+        value: Some(Value::Choice("cipher", CIPHERS)),
+        help: "The cipher to use. One of caesar (default), substitution, vigenere, railfence, columnar,\nenigma, playfair, hill.",
+    },
+    Opt {
+        flag: "--charset",
+        value: Some(Value::Choice("charset", CHARSETS)),
+        help: "Chars rotated by the caesar cipher. One of letters (default), alphanumeric, which also\nrotates the digits within 0-9 like ROT5, or printable, which rotates all the printable\nascii chars but the space together, like ROT47 with -k 47.",
+    },
+    Opt {
+        flag: "--alphabet",
+        value: Some(Value::Text("chars")),
+        help: "Rotates the given chars with the caesar cipher, in their order, instead of the letters.",
+    },
+    Opt {
+        flag: "-w",
+        value: Some(Value::Text("keyword")),
+        help: "Keyword of the substitution, vigenere, columnar, enigma, playfair and hill ciphers\n(mandatory for them).\nFor substitution, a permutation of the 26 letters of the alphabet, or a keyword completed\nwith the rest of it. For columnar, two keywords separated by a comma apply a double\ntransposition. For enigma, the machine settings, as in \"B I,II,III AAA AAA AB CD\". For hill,\n4, 9, 16... letters that fill the key matrix row by row.",
+    },
+    Opt {
+        flag: "--pad",
+        value: Some(Value::Text("char")),
+        help: "Fills the last row of the columnar cipher with the given char, instead of leaving it short.",
+    },
+    Opt { flag: "-o", value: Some(Value::File), help: "Write results to specified file." },
+    Opt { flag: "-i", value: Some(Value::File), help: "Specify path to input file." },
+    Opt { flag: "-e", value: None, help: "Encryption mode. (default)." },
+    Opt { flag: "-d", value: None, help: "Decryption mode." },
+    Opt {
+        flag: "-l",
+        value: Some(Value::Choice("language", LANGUAGES)),
+        help: "Language of the built-in model used by crack. One of en (default), es, fr, de.",
+    },
+    Opt {
+        flag: "-m",
+        value: Some(Value::File),
+        help: "Path to a model file created with train, used by crack instead of -l.",
+    },
+    Opt {
+        flag: "-p",
+        value: Some(Value::Text("crib")),
+        help: "Known plaintext (crib) to look for in the ciphertext. Mandatory for crib.",
+    },
+    Opt { flag: "--progress", value: None, help: "Shows a progress bar in stderr while encrypting or decrypting." },
+    Opt { flag: "--stats", value: None, help: "Prints a summary of the processed characters to stderr at the end." },
+    Opt { flag: "--in-place", value: None, help: "Overwrites the input file (-i) with the results. Cannot be used with -o." },
+    Opt { flag: "--no-clobber", value: None, help: "Fails instead of overwriting an already existing output file." },
+    Opt {
+        flag: "--line-buffered",
+        value: None,
+        help: "Encrypts or decrypts every line as soon as it is read, flushing the output after it. Useful\nin pipelines that never end, like tail -f. The transposition, playfair and hill ciphers\nwork on every line on its own.",
+    },
+    Opt {
+        flag: "--regex",
+        value: Some(Value::Text("pattern")),
+        help: "Only encrypts or decrypts the matches of the pattern, or their first group if it has any.\nSupports . [] \\d \\w \\s ^ $ () (?:) | * + ? {n,m} and lazy quantifiers.",
+    },
+    Opt {
+        flag: "--json",
+        value: Some(Value::Text("path")),
+        help: "Only encrypts or decrypts the JSON string values under the path, as in .users[].name.\nA * stands for any key, and . selects every string value. Keys and structure are kept.\nCannot be used with --line-buffered.",
+    },
+    Opt {
+        flag: "--csv",
+        value: Some(Value::Text("column")),
+        help: "Only encrypts or decrypts the values of the CSV column with the given name in the header.",
+    },
+];
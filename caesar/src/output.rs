@@ -0,0 +1,238 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A file that is only visible at its final path once all the contents
+/// are written.
+///
+/// Contents go to a temporary file in the same directory, which is renamed
+/// over the destination on [AtomicFile::commit]. If the file is dropped
+/// without committing, the temporary file is removed and the destination
+/// is left untouched.
+pub(crate) struct AtomicFile {
+    path: PathBuf,
+    tmp_path: PathBuf,
+    file: Option<BufWriter<File>>,
+    no_clobber: bool,
+}
+
+impl AtomicFile {
+    pub(crate) fn create(path: &str, no_clobber: bool) -> Result<AtomicFile, Box<dyn Error>> {
+        let path = PathBuf::from(path);
+        if no_clobber && path.exists() {
+            return Err(Box::new(OutputError::Exists));
+        }
+        let (tmp_path, file) = create_tmp_file(&path)?;
+        Ok(AtomicFile {
+            path,
+            tmp_path,
+            file: Some(BufWriter::new(file)),
+            no_clobber,
+        })
+    }
+
+    pub(crate) fn commit(mut self) -> Result<(), Box<dyn Error>> {
+        let file = self.file.take().expect("file is only taken on commit");
+        let file = file.into_inner().map_err(|err| err.into_error())?;
+        file.sync_all()?;
+        drop(file);
+
+        match fs::metadata(&self.path) {
+            Ok(metadata) => fs::set_permissions(&self.tmp_path, metadata.permissions())?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(Box::new(err)),
+        }
+        if self.no_clobber {
+            // Unlike renaming, linking fails if the destination was created in the meantime.
+            match fs::hard_link(&self.tmp_path, &self.path) {
+                Ok(()) => fs::remove_file(&self.tmp_path)?,
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => return Err(Box::new(OutputError::Exists)),
+                Err(err) => return Err(Box::new(err)),
+            }
+        } else {
+            fs::rename(&self.tmp_path, &self.path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.as_mut().expect("file is only taken on commit").write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.as_mut().expect("file is only taken on commit").flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        // Nothing to report at this point. Leftovers are hidden files at worst.
+        self.file.take();
+        let _ = fs::remove_file(&self.tmp_path);
+    }
+}
+
+fn create_tmp_file(path: &Path) -> io::Result<(PathBuf, File)> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().subsec_nanos();
+    let mut attempt: u32 = 0;
+    loop {
+        let tmp_path = dir.join(format!(".{}.{}.{}.{}.tmp", name, process::id(), nanos, attempt));
+        match OpenOptions::new().write(true).create_new(true).open(&tmp_path) {
+            Ok(file) => return Ok((tmp_path, file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => attempt += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Whether both paths point to the same existing file, also through links.
+pub(crate) fn same_file(a: &str, b: &str) -> io::Result<bool> {
+    let (metadata_a, metadata_b) = match (fs::metadata(a), fs::metadata(b)) {
+        (Ok(metadata_a), Ok(metadata_b)) => (metadata_a, metadata_b),
+        (Err(err), _) | (_, Err(err)) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+        (Err(err), _) | (_, Err(err)) => return Err(err),
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Ok(metadata_a.dev() == metadata_b.dev() && metadata_a.ino() == metadata_b.ino())
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (metadata_a, metadata_b);
+        Ok(fs::canonicalize(a)? == fs::canonicalize(b)?)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum OutputError {
+    SameFile,
+    Exists,
+}
+
+impl Display for OutputError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputError::SameFile => write!(f, "input and output are the same file. Use --in-place to overwrite it."),
+            OutputError::Exists => write!(f, "the output file already exists and --no-clobber was specified."),
+        }
+    }
+}
+
+impl Error for OutputError {}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    #[test]
+    fn it_only_writes_the_destination_on_commit() {
+        let path = tmp_path();
+        fs::write(&path, "old").unwrap();
+
+        let mut file = AtomicFile::create(&path, false).unwrap();
+        file.write_all(b"new").unwrap();
+        assert_eq!("old", fs::read_to_string(&path).unwrap());
+
+        file.commit().unwrap();
+        assert_eq!("new", fs::read_to_string(&path).unwrap());
+
+        fs::remove_file(path).unwrap()
+    }
+
+    #[test]
+    fn it_leaves_no_trace_when_not_committed() {
+        let path = tmp_path();
+
+        let mut file = AtomicFile::create(&path, false).unwrap();
+        file.write_all(b"new").unwrap();
+        let tmp_path = file.tmp_path.clone();
+        drop(file);
+
+        assert!(!Path::new(&path).exists());
+        assert!(!tmp_path.exists());
+    }
+
+    #[test]
+    fn it_does_not_clobber_existing_files() {
+        let path = tmp_path();
+        fs::write(&path, "old").unwrap();
+
+        let err = AtomicFile::create(&path, true).err().unwrap();
+        assert_eq!(Some(&OutputError::Exists), err.downcast_ref::<OutputError>());
+
+        fs::remove_file(path).unwrap()
+    }
+
+    #[test]
+    fn it_does_not_clobber_files_created_while_writing() {
+        let path = tmp_path();
+
+        let file = AtomicFile::create(&path, true).unwrap();
+        fs::write(&path, "old").unwrap();
+
+        let err = file.commit().unwrap_err();
+        assert_eq!(Some(&OutputError::Exists), err.downcast_ref::<OutputError>());
+        assert_eq!("old", fs::read_to_string(&path).unwrap());
+
+        fs::remove_file(path).unwrap()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn it_preserves_permissions_of_the_destination() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = tmp_path();
+        fs::write(&path, "old").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+
+        let mut file = AtomicFile::create(&path, false).unwrap();
+        file.write_all(b"new").unwrap();
+        file.commit().unwrap();
+
+        assert_eq!(0o640, fs::metadata(&path).unwrap().permissions().mode() & 0o777);
+
+        fs::remove_file(path).unwrap()
+    }
+
+    #[test]
+    fn it_detects_the_same_file() {
+        let path = tmp_path();
+        fs::write(&path, "").unwrap();
+        let other_path = tmp_path();
+        fs::write(&other_path, "").unwrap();
+
+        let dir = Path::new(&path).parent().unwrap().to_str().unwrap().to_string();
+        let name = Path::new(&path).file_name().unwrap().to_str().unwrap().to_string();
+        let indirect_path = format!("{}/../{}/{}", dir, Path::new(&dir).file_name().unwrap().to_str().unwrap(), name);
+
+        assert!(same_file(&path, &path).unwrap());
+        assert!(same_file(&path, &indirect_path).unwrap());
+        assert!(!same_file(&path, &other_path).unwrap());
+        assert!(!same_file(&path, &tmp_path()).unwrap());
+
+        fs::remove_file(path).unwrap();
+        fs::remove_file(other_path).unwrap()
+    }
+
+    fn tmp_path() -> String {
+        format!("{}{}{}", "/tmp/rust-test-", Uuid::new_v4(), ".txt")
+    }
+}
@@ -0,0 +1,603 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::iter;
+use std::ops::Range;
+
+/// A small regular expression engine, as new code sticks to the standard
+/// library. The pattern is compiled to a program that runs all its
+/// alternatives at once over the text (a Pike VM), so the time grows
+/// linearly with the text and long inputs don't exhaust the stack. Matches
+/// are leftmost first, like with backtracking.
+///
+/// Supports literals, `.`, classes like `[a-z]` and `[^"]`, the `\d`, `\w`
+/// and `\s` classes and their negations, the `^` and `$` anchors, capturing
+/// `(...)` and non capturing `(?:...)` groups, alternation with `|`, and the
+/// `*`, `+`, `?`, `{n}`, `{n,}` and `{n,m}` quantifiers, which are lazy when
+/// followed by `?`. The `.` does not match line breaks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    program: Vec<Inst>,
+    groups: usize,
+}
+
+// Repetitions are compiled to copies of what they repeat, so they are
+// limited to keep the program small.
+const MAX_INSTRUCTIONS: usize = 100_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Empty,
+    Char(char),
+    Any,
+    Class(Class),
+    Start,
+    End,
+    // The index is None for non capturing groups.
+    Group(Box<Node>, Option<usize>),
+    Concat(Vec<Node>),
+    Alternation(Vec<Node>),
+    Repeat(Repeat),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Class {
+    ranges: Vec<(char, char)>,
+    negated: bool,
+}
+
+impl Class {
+    fn matches(&self, c: char) -> bool {
+        self.ranges.iter().any(|(from, to)| (*from..=*to).contains(&c)) != self.negated
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Repeat {
+    node: Box<Node>,
+    min: usize,
+    max: Option<usize>,
+    greedy: bool,
+}
+
+impl Node {
+    // The number of instructions it compiles to.
+    fn size(&self) -> usize {
+        match self {
+            Node::Empty => 0,
+            Node::Char(_) | Node::Any | Node::Class(_) | Node::Start | Node::End => 1,
+            Node::Group(inner, Some(_)) => inner.size().saturating_add(2),
+            Node::Group(inner, None) => inner.size(),
+            Node::Concat(nodes) => nodes.iter().fold(0, |size, node| size.saturating_add(node.size())),
+            Node::Alternation(branches) => branches.iter().fold(0, |size, node| size.saturating_add(node.size() + 2)),
+            Node::Repeat(repeat) => {
+                let size = repeat.node.size();
+                let optional = match repeat.max {
+                    None => size.saturating_add(2),
+                    Some(max) => (max - repeat.min).saturating_mul(size.saturating_add(1)),
+                };
+                repeat.min.saturating_mul(size).saturating_add(optional)
+            }
+        }
+    }
+
+    fn compile(&self, program: &mut Vec<Inst>) {
+        match self {
+            Node::Empty => {}
+            Node::Char(c) => program.push(Inst::Char(*c)),
+            Node::Any => program.push(Inst::Any),
+            Node::Class(class) => program.push(Inst::Class(class.clone())),
+            Node::Start => program.push(Inst::Start),
+            Node::End => program.push(Inst::End),
+            Node::Group(inner, None) => inner.compile(program),
+            Node::Group(inner, Some(index)) => {
+                program.push(Inst::Save(2 * index));
+                inner.compile(program);
+                program.push(Inst::Save(2 * index + 1));
+            }
+            Node::Concat(nodes) => nodes.iter().for_each(|node| node.compile(program)),
+            Node::Alternation(branches) => {
+                let mut jumps = Vec::new();
+                for (n, branch) in branches.iter().enumerate() {
+                    if n + 1 == branches.len() {
+                        branch.compile(program);
+                        break;
+                    }
+                    let split = program.len();
+                    program.push(Inst::Split(split + 1, 0));
+                    branch.compile(program);
+                    jumps.push(program.len());
+                    program.push(Inst::Jump(0));
+                    program[split] = Inst::Split(split + 1, program.len());
+                }
+                let end = program.len();
+                for jump in jumps {
+                    program[jump] = Inst::Jump(end);
+                }
+            }
+            Node::Repeat(repeat) => {
+                for _ in 0..repeat.min {
+                    repeat.node.compile(program);
+                }
+                let split = |body, out| match repeat.greedy {
+                    true => Inst::Split(body, out),
+                    false => Inst::Split(out, body),
+                };
+                match repeat.max {
+                    None => {
+                        let start = program.len();
+                        program.push(Inst::Jump(0));
+                        repeat.node.compile(program);
+                        program.push(Inst::Jump(start));
+                        program[start] = split(start + 1, program.len());
+                    }
+                    Some(max) => {
+                        let mut splits = Vec::new();
+                        for _ in repeat.min..max {
+                            splits.push(program.len());
+                            program.push(Inst::Jump(0));
+                            repeat.node.compile(program);
+                        }
+                        let end = program.len();
+                        for start in splits {
+                            program[start] = split(start + 1, end);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Inst {
+    Char(char),
+    Any,
+    Class(Class),
+    Start,
+    End,
+    // Goes on at both, giving priority to the first.
+    Split(usize, usize),
+    Jump(usize),
+    // Records the position in a slot, two for each group.
+    Save(usize),
+    Match,
+}
+
+/// The byte ranges of a match, and of every group of the pattern in it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Captures(Vec<Option<Range<usize>>>);
+
+impl Captures {
+    /// The group 0 is the whole match.
+    pub fn get(&self, group: usize) -> Option<Range<usize>> {
+        self.0.get(group).cloned().flatten()
+    }
+}
+
+impl Pattern {
+    pub fn new(pattern: &str) -> Result<Pattern, PatternError> {
+        let mut parser = Parser { chars: pattern.chars().collect(), position: 0, groups: 0 };
+        let node = parser.alternation()?;
+        if parser.position < parser.chars.len() {
+            return Err(parser.error("unmatched )"));
+        }
+        let mut program = Vec::new();
+        node.compile(&mut program);
+        program.push(Inst::Match);
+        Ok(Pattern { program, groups: parser.groups })
+    }
+
+    /// Number of capturing groups, not counting the whole match.
+    pub fn groups(&self) -> usize {
+        self.groups
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        !self.captures_iter(text).is_empty()
+    }
+
+    /// Finds all the non overlapping matches, from left to right.
+    pub fn captures_iter(&self, text: &str) -> Vec<Captures> {
+        let chars: Vec<char> = text.chars().collect();
+        let offsets: Vec<usize> = text.char_indices().map(|(i, _)| i).chain(iter::once(text.len())).collect();
+        let mut result = Vec::new();
+        let mut start = 0;
+        while start <= chars.len() {
+            let Some(slots) = self.search(&chars, start) else {
+                break;
+            };
+            let groups = slots.chunks(2).map(|slot| match slot {
+                [Some(from), Some(to)] => Some(offsets[*from]..offsets[*to]),
+                _ => None,
+            });
+            result.push(Captures(groups.collect()));
+            let (from, to) = (slots[0].unwrap(), slots[1].unwrap());
+            start = if to > from { to } else { to + 1 };
+        }
+        result
+    }
+
+    // Runs the threads of the program in lockstep over the chars, ordered by
+    // priority. A new thread starts at every position until the first match,
+    // and a match drops the threads of lower priority, so the result is the
+    // leftmost match that backtracking would find first.
+    fn search(&self, chars: &[char], from: usize) -> Option<Slots> {
+        let mut current = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+        let mut matched = None;
+        for pos in from..=chars.len() {
+            if matched.is_none() {
+                let mut slots = vec![None; 2 * (self.groups + 1)];
+                slots[0] = Some(pos);
+                self.add(&mut current, 0, pos, slots, chars);
+            } else if current.list.is_empty() {
+                break;
+            }
+            for (pc, mut slots) in current.list.drain(..) {
+                let matches = match (&self.program[pc], chars.get(pos)) {
+                    (Inst::Match, _) => {
+                        slots[1] = Some(pos);
+                        matched = Some(slots);
+                        break;
+                    }
+                    (Inst::Char(expected), Some(c)) => c == expected,
+                    (Inst::Any, Some(c)) => *c != '\n',
+                    (Inst::Class(class), Some(c)) => class.matches(*c),
+                    _ => false,
+                };
+                if matches {
+                    self.add(&mut next, pc + 1, pos + 1, slots, chars);
+                }
+            }
+            std::mem::swap(&mut current, &mut next);
+        }
+        matched
+    }
+
+    // Follows the instructions that don't take a char, in priority order, and
+    // adds the threads that end at one. A thread is not added twice for an
+    // instruction and position, which also ends the repetitions of nothing.
+    fn add(&self, threads: &mut Threads, pc: usize, pos: usize, slots: Slots, chars: &[char]) {
+        let mut pending = vec![(pc, slots)];
+        while let Some((pc, mut slots)) = pending.pop() {
+            if threads.seen[pc] == Some(pos) {
+                continue;
+            }
+            threads.seen[pc] = Some(pos);
+            match &self.program[pc] {
+                Inst::Jump(to) => pending.push((*to, slots)),
+                Inst::Split(first, second) => {
+                    pending.push((*second, slots.clone()));
+                    pending.push((*first, slots));
+                }
+                Inst::Save(slot) => {
+                    slots[*slot] = Some(pos);
+                    pending.push((pc + 1, slots));
+                }
+                Inst::Start if pos == 0 => pending.push((pc + 1, slots)),
+                Inst::End if pos == chars.len() => pending.push((pc + 1, slots)),
+                Inst::Start | Inst::End => {}
+                _ => threads.list.push((pc, slots)),
+            }
+        }
+    }
+}
+
+// Start and end positions of the groups, as char indexes.
+type Slots = Vec<Option<usize>>;
+
+struct Threads {
+    list: Vec<(usize, Slots)>,
+    // The last position every instruction was added at.
+    seen: Vec<Option<usize>>,
+}
+
+impl Threads {
+    fn new(instructions: usize) -> Threads {
+        Threads { list: Vec::new(), seen: vec![None; instructions] }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    groups: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        if c.is_some() {
+            self.position += 1;
+        }
+        c
+    }
+
+    fn error(&self, reason: &'static str) -> PatternError {
+        PatternError { position: self.position, reason }
+    }
+
+    fn alternation(&mut self) -> Result<Node, PatternError> {
+        let mut branches = vec![self.concat()?];
+        while self.peek() == Some('|') {
+            self.position += 1;
+            branches.push(self.concat()?);
+        }
+        Ok(match branches.len() {
+            1 => branches.pop().unwrap(),
+            _ => Node::Alternation(branches),
+        })
+    }
+
+    fn concat(&mut self) -> Result<Node, PatternError> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.quantifier(atom)?);
+        }
+        Ok(match nodes.len() {
+            0 => Node::Empty,
+            1 => nodes.pop().unwrap(),
+            _ => Node::Concat(nodes),
+        })
+    }
+
+    fn atom(&mut self) -> Result<Node, PatternError> {
+        match self.next() {
+            Some('(') => {
+                let index = if self.chars[self.position..].starts_with(&['?', ':']) {
+                    self.position += 2;
+                    None
+                } else {
+                    self.groups += 1;
+                    Some(self.groups)
+                };
+                let node = self.alternation()?;
+                if self.next() != Some(')') {
+                    return Err(self.error("unclosed group"));
+                }
+                Ok(Node::Group(Box::new(node), index))
+            }
+            Some('[') => self.class(),
+            Some('.') => Ok(Node::Any),
+            Some('^') => Ok(Node::Start),
+            Some('$') => Ok(Node::End),
+            Some('\\') => self.escape(),
+            Some('*' | '+' | '?' | '{') => {
+                self.position -= 1;
+                Err(self.error("nothing to repeat"))
+            }
+            Some(c) => Ok(Node::Char(c)),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    fn escape(&mut self) -> Result<Node, PatternError> {
+        match self.next() {
+            Some('n') => Ok(Node::Char('\n')),
+            Some('t') => Ok(Node::Char('\t')),
+            Some('r') => Ok(Node::Char('\r')),
+            Some(c) => match shorthand(c) {
+                Some(class) => Ok(Node::Class(class)),
+                None if c.is_ascii_alphanumeric() => Err(self.error("unknown escape")),
+                None => Ok(Node::Char(c)),
+            },
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    fn class(&mut self) -> Result<Node, PatternError> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.position += 1;
+        }
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let from = match self.next() {
+                Some(']') if !first => break,
+                Some('\\') => match self.next() {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some(c) => match shorthand(c) {
+                        Some(class) if !class.negated => {
+                            ranges.extend(class.ranges);
+                            first = false;
+                            continue;
+                        }
+                        Some(_) => return Err(self.error("negated class inside a class")),
+                        None => c,
+                    },
+                    None => return Err(self.error("unclosed class")),
+                },
+                Some(c) => c,
+                None => return Err(self.error("unclosed class")),
+            };
+            first = false;
+            let to = match (self.peek(), self.chars.get(self.position + 1)) {
+                (Some('-'), Some(to)) if *to != ']' => {
+                    self.position += 2;
+                    *to
+                }
+                _ => from,
+            };
+            if to < from {
+                return Err(self.error("invalid class range"));
+            }
+            ranges.push((from, to));
+        }
+        Ok(Node::Class(Class { ranges, negated }))
+    }
+
+    fn quantifier(&mut self, node: Node) -> Result<Node, PatternError> {
+        let at = self.position;
+        let (min, max) = match self.peek() {
+            Some('*') => (0, None),
+            Some('+') => (1, None),
+            Some('?') => (0, Some(1)),
+            Some('{') => {
+                let start = self.position;
+                let close = self.chars[start..].iter().position(|c| *c == '}')
+                    .ok_or(self.error("unclosed repetition"))?;
+                let bounds: String = self.chars[start + 1..start + close].iter().collect();
+                self.position = start + close;
+                let parse = |bound: &str| bound.trim().parse::<usize>().map_err(|_| self.error("invalid repetition"));
+                match bounds.split_once(',') {
+                    None => (parse(&bounds)?, Some(parse(&bounds)?)),
+                    Some((min, "")) => (parse(min)?, None),
+                    Some((min, max)) => (parse(min)?, Some(parse(max)?)),
+                }
+            }
+            _ => return Ok(node),
+        };
+        if max.is_some_and(|max| max < min) {
+            return Err(self.error("invalid repetition"));
+        }
+        let repeat = Node::Repeat(Repeat { node: Box::new(node), min, max, greedy: true });
+        if repeat.size() > MAX_INSTRUCTIONS {
+            return Err(PatternError { position: at, reason: "repetition too big" });
+        }
+        self.position += 1;
+        let greedy = self.peek() != Some('?');
+        if !greedy {
+            self.position += 1;
+        }
+        match repeat {
+            Node::Repeat(repeat) => Ok(Node::Repeat(Repeat { greedy, ..repeat })),
+            _ => unreachable!(),
+        }
+    }
+}
+
+fn shorthand(c: char) -> Option<Class> {
+    let ranges = match c.to_ascii_lowercase() {
+        'd' => vec![('0', '9')],
+        'w' => vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')],
+        's' => vec![(' ', ' '), ('\t', '\r')],
+        _ => return None,
+    };
+    Some(Class { ranges, negated: c.is_ascii_uppercase() })
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct PatternError {
+    position: usize,
+    reason: &'static str,
+}
+
+impl Display for PatternError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid pattern, {} at char {}.", self.reason, self.position)
+    }
+}
+
+impl Error for PatternError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> Vec<String> {
+        Pattern::new(pattern).unwrap().captures_iter(text).iter()
+            .map(|captures| text[captures.get(0).unwrap()].to_string())
+            .collect()
+    }
+
+    #[test]
+    fn it_finds_literals() {
+        assert_eq!(vec!["ab", "ab"], matches("ab", "xabyab"));
+        assert_eq!(Vec::<String>::new(), matches("ab", "ba"));
+    }
+
+    #[test]
+    fn it_matches_classes() {
+        assert_eq!(vec!["2024", "10"], matches("\\d+", "On 2024-10"));
+        assert_eq!(vec!["abc", "Z"], matches("[a-cZ]+", "abcdZ"));
+        assert_eq!(vec!["x y"], matches("[^\"]+", "\"x y\""));
+        assert_eq!(vec!["user_1", "host"], matches("[\\w]+", "user_1@host"));
+        assert_eq!(vec!["-", "]"], matches("[]-]", "a-]"));
+    }
+
+    #[test]
+    fn it_does_not_match_line_breaks_with_dot() {
+        assert_eq!(vec!["ab", "cd"], matches(".+", "ab\ncd"));
+    }
+
+    #[test]
+    fn it_anchors() {
+        assert_eq!(vec!["ab"], matches("^ab", "abab"));
+        assert_eq!(vec!["ab"], matches("ab$", "abab"));
+    }
+
+    #[test]
+    fn it_alternates() {
+        assert_eq!(vec!["cat", "dog"], matches("cat|dog", "cat bird dog"));
+        assert_eq!(vec!["grey", "gray"], matches("gr(?:e|a)y", "grey gray groy"));
+    }
+
+    #[test]
+    fn it_repeats_greedy_and_lazy() {
+        assert_eq!(vec!["<a><b>"], matches("<.+>", "<a><b>"));
+        assert_eq!(vec!["<a>", "<b>"], matches("<.+?>", "<a><b>"));
+        assert_eq!(vec!["aaa", "aa"], matches("a{2,3}", "aaaaa"));
+        assert_eq!(vec!["aa", "aa"], matches("a{2}", "aaaaa"));
+        assert_eq!(vec!["colour", "color"], matches("colou?r", "colour color"));
+    }
+
+    #[test]
+    fn it_backtracks() {
+        assert_eq!(vec!["abcbc"], matches("a(bc)*bc", "abcbc"));
+        assert_eq!(vec!["aab"], matches("(a|aa)+b", "aab"));
+    }
+
+    #[test]
+    fn it_captures_groups() {
+        let pattern = Pattern::new("(\\w+)@(\\w+)(x)?").unwrap();
+        let text = "mail user@host now";
+        let captures = &pattern.captures_iter(text)[0];
+
+        assert_eq!(3, pattern.groups());
+        assert_eq!(Some(5..14), captures.get(0));
+        assert_eq!("user", &text[captures.get(1).unwrap()]);
+        assert_eq!("host", &text[captures.get(2).unwrap()]);
+        assert_eq!(None, captures.get(3));
+    }
+
+    #[test]
+    fn it_works_with_multibyte_chars() {
+        assert_eq!(vec!["ñandú"], matches("ñ.+", "el ñandú"));
+    }
+
+    #[test]
+    fn it_does_not_loop_on_empty_repetitions() {
+        assert_eq!(vec!["", "", ""], matches("(a*)*", "bb"));
+    }
+
+    #[test]
+    fn it_matches_long_texts() {
+        let text = "a".repeat(200_000);
+        assert_eq!(vec![text.clone()], matches("\\w+", &text));
+        assert_eq!(vec![text.clone()], matches(".+", &text));
+        assert_eq!(vec![format!("{}b", text)], matches("(a|aa)*b", &format!("{}b", text)));
+    }
+
+    #[test]
+    fn it_returns_error_on_invalid_patterns() {
+        assert_eq!(PatternError { position: 0, reason: "nothing to repeat" }, Pattern::new("*a").unwrap_err());
+        assert_eq!(PatternError { position: 3, reason: "unclosed group" }, Pattern::new("(ab").unwrap_err());
+        assert_eq!(PatternError { position: 2, reason: "unmatched )" }, Pattern::new("ab)").unwrap_err());
+        assert_eq!(PatternError { position: 4, reason: "unclosed class" }, Pattern::new("[abc").unwrap_err());
+        assert_eq!(PatternError { position: 2, reason: "unknown escape" }, Pattern::new("\\q").unwrap_err());
+        assert_eq!(PatternError { position: 9, reason: "repetition too big" }, Pattern::new("(a{1000}){1000}").unwrap_err());
+        assert_eq!("invalid pattern, unclosed group at char 3.", Pattern::new("(ab").unwrap_err().to_string());
+    }
+}
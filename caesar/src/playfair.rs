@@ -0,0 +1,177 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use crate::caesar::Mode;
+
+const SIDE: usize = 5;
+// Inserted between repeated letters of a pair, and after the last letter when it has no pair.
+const FILLER: u8 = b'X';
+// Filler for the cases in which the letter to separate is the filler itself.
+const ALTERNATIVE_FILLER: u8 = b'Q';
+
+/// Playfair cipher. Letters are encrypted in pairs with a 5x5 square, which
+/// holds the letters of a keyword followed by the rest of the alphabet.
+/// The "J" shares the square with the "I".
+///
+/// Both letters of a pair are replaced by the ones on their right when they
+/// are in the same row, by the ones below when they are in the same column,
+/// and by the ones in the other corners of their rectangle otherwise.
+///
+/// Only letters are encrypted, in uppercase. Any other character is dropped,
+/// and fillers are added for separating pairs of the same letter and for
+/// completing the last pair. Decryption keeps the fillers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Playfair {
+    square: [u8; SIDE * SIDE],
+    // Position in the square of every letter of the alphabet.
+    positions: [usize; 26],
+}
+
+impl Playfair {
+    pub fn new(keyword: &str) -> Result<Playfair, KeyError> {
+        if keyword.is_empty() || !keyword.chars().all(|c| c.is_ascii_alphabetic() || c == ' ') {
+            return Err(KeyError);
+        }
+        let mut square = Vec::with_capacity(SIDE * SIDE);
+        for letter in letters(keyword).chain(b'A'..=b'Z') {
+            if letter != b'J' && !square.contains(&letter) {
+                square.push(letter)
+            }
+        }
+        let square: [u8; SIDE * SIDE] = square.try_into().unwrap();
+        let mut positions = [0; 26];
+        for (position, letter) in square.iter().enumerate() {
+            positions[(letter - b'A') as usize] = position;
+        }
+        positions[(b'J' - b'A') as usize] = positions[(b'I' - b'A') as usize];
+        Ok(Playfair { square, positions })
+    }
+
+    pub fn exec(&self, input: &str, mode: Mode) -> String {
+        let pairs = match mode {
+            Mode::Encrypt => pairs(letters(input)),
+            Mode::Decrypt => letters(input).collect::<Vec<u8>>().chunks(2).map(|p| (p[0], *p.get(1).unwrap_or(&FILLER))).collect(),
+        };
+        // Moving forward 4 positions in a row or column of 5 is moving backwards 1.
+        let step = match mode {
+            Mode::Encrypt => 1,
+            Mode::Decrypt => SIDE - 1,
+        };
+        let mut result = String::with_capacity(pairs.len() * 2);
+        for (a, b) in pairs {
+            let (row_a, column_a) = self.position(a);
+            let (row_b, column_b) = self.position(b);
+            let (a, b) = if row_a == row_b {
+                (self.at(row_a, (column_a + step) % SIDE), self.at(row_b, (column_b + step) % SIDE))
+            } else if column_a == column_b {
+                (self.at((row_a + step) % SIDE, column_a), self.at((row_b + step) % SIDE, column_b))
+            } else {
+                (self.at(row_a, column_b), self.at(row_b, column_a))
+            };
+            result.push(a as char);
+            result.push(b as char);
+        }
+        result
+    }
+
+    fn position(&self, letter: u8) -> (usize, usize) {
+        let position = self.positions[(letter - b'A') as usize];
+        (position / SIDE, position % SIDE)
+    }
+
+    fn at(&self, row: usize, column: usize) -> u8 {
+        self.square[row * SIDE + column]
+    }
+}
+
+fn letters(text: &str) -> impl Iterator<Item=u8> + '_ {
+    text.bytes()
+        .filter(|b| b.is_ascii_alphabetic())
+        .map(|b| match b.to_ascii_uppercase() {
+            b'J' => b'I',
+            b => b,
+        })
+}
+
+// Splits the letters in pairs of different letters, adding fillers where needed.
+fn pairs(letters: impl Iterator<Item=u8>) -> Vec<(u8, u8)> {
+    let filler_for = |letter: u8| if letter == FILLER { ALTERNATIVE_FILLER } else { FILLER };
+    let mut pairs = Vec::new();
+    let mut first = None;
+    for letter in letters {
+        match first.take() {
+            None => first = Some(letter),
+            Some(a) if a == letter => {
+                pairs.push((a, filler_for(a)));
+                first = Some(letter);
+            }
+            Some(a) => pairs.push((a, letter)),
+        }
+    }
+    if let Some(a) = first {
+        pairs.push((a, filler_for(a)));
+    }
+    pairs
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct KeyError;
+
+const KEY_ERROR_MSG: &str = "the key must be a keyword made of letters.";
+
+impl Display for KeyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", KEY_ERROR_MSG)
+    }
+}
+
+impl Error for KeyError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_builds_the_square_from_the_keyword() {
+        let playfair = Playfair::new("playfair example").unwrap();
+        assert_eq!(b"PLAYFIREXMBCDGHKNOQSTUVWZ", &playfair.square);
+    }
+
+    #[test]
+    fn it_encrypts() {
+        let playfair = Playfair::new("playfair example").unwrap();
+        let result = playfair.exec("Hide the gold in the tree stump", Mode::Encrypt);
+        assert_eq!("BMODZBXDNABEKUDMUIXMMOUVIF", result);
+    }
+
+    #[test]
+    fn it_decrypts_keeping_the_fillers() {
+        let playfair = Playfair::new("playfair example").unwrap();
+        let result = playfair.exec("BMODZBXDNABEKUDMUIXMMOUVIF", Mode::Decrypt);
+        assert_eq!("HIDETHEGOLDINTHETREXESTUMP", result);
+    }
+
+    #[test]
+    fn it_separates_repeated_letters_and_completes_the_last_pair() {
+        assert_eq!(vec![(b'B', b'X'), (b'B', b'O'), (b'X', b'Q'), (b'X', b'A'), (b'X', b'Q')],
+                   pairs("BBOXXAX".bytes()));
+    }
+
+    #[test]
+    fn it_merges_j_with_i() {
+        let playfair = Playfair::new("jump").unwrap();
+        assert_eq!(playfair.exec("ijij", Mode::Encrypt), playfair.exec("iiii", Mode::Encrypt));
+    }
+
+    #[test]
+    fn it_returns_error_on_invalid_keys() {
+        assert_eq!(KeyError, Playfair::new("").unwrap_err());
+        assert_eq!(KeyError, Playfair::new("key 2").unwrap_err());
+    }
+
+    #[test]
+    fn errors_key_has_display() {
+        assert_eq!(KEY_ERROR_MSG, format!("{}", KeyError));
+    }
+}
@@ -0,0 +1,184 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::io::Write;
+use std::time::{Duration, Instant};
+
+use crate::caesar::Alphabet;
+
+const BAR_WIDTH: usize = 30;
+const MEGABYTE: f64 = 1_000_000.0;
+// Redrawing the bar on every chunk would make the terminal the bottleneck.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A progress bar, drawn in a single line that is overwritten on every update.
+///
+/// When the total size of the input is unknown, only the processed bytes
+/// and the throughput are shown.
+pub(crate) struct Progress {
+    total: Option<u64>,
+    processed: u64,
+    started: Instant,
+    last_draw: Option<Instant>,
+}
+
+impl Progress {
+    pub(crate) fn new(total: Option<u64>) -> Self {
+        Progress {
+            total,
+            processed: 0,
+            started: Instant::now(),
+            last_draw: None,
+        }
+    }
+
+    pub(crate) fn advance<W: Write>(&mut self, bytes: usize, out: &mut W) -> io::Result<()> {
+        self.processed += bytes as u64;
+        match self.last_draw {
+            Some(last_draw) if last_draw.elapsed() < REDRAW_INTERVAL => Ok(()),
+            _ => self.draw(out),
+        }
+    }
+
+    pub(crate) fn finish<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        self.draw(out)?;
+        writeln!(out)
+    }
+
+    fn draw<W: Write>(&mut self, out: &mut W) -> io::Result<()> {
+        self.last_draw = Some(Instant::now());
+        let processed = self.processed as f64 / MEGABYTE;
+        let throughput = throughput(self.processed, self.started.elapsed());
+        match self.total {
+            Some(total) => {
+                let ratio = match total {
+                    0 => 1.0,
+                    total => (self.processed as f64 / total as f64).min(1.0),
+                };
+                let filled = (ratio * BAR_WIDTH as f64) as usize;
+                write!(out, "\r[{}{}] {:>3.0}% {:.1}/{:.1} MB {:.1} MB/s",
+                       "#".repeat(filled), " ".repeat(BAR_WIDTH - filled), ratio * 100.0,
+                       processed, total as f64 / MEGABYTE, throughput)?
+            }
+            None => write!(out, "\r{:.1} MB {:.1} MB/s", processed, throughput)?,
+        }
+        out.flush()
+    }
+}
+
+/// Summary of a cipher execution.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct Stats {
+    pub(crate) bytes: u64,
+    pub(crate) transformed: u64,
+    pub(crate) passed_through: u64,
+    pub(crate) elapsed: Duration,
+}
+
+impl Stats {
+    pub(crate) fn count(&mut self, text: &str, alphabet: &Alphabet) {
+        self.bytes += text.len() as u64;
+        for c in text.chars() {
+            match alphabet.contains(c) {
+                true => self.transformed += 1,
+                false => self.passed_through += 1,
+            }
+        }
+    }
+
+    /// Counts a text with a selection in it, as passed through until the
+    /// selected regions are known.
+    pub(crate) fn pass(&mut self, text: &str) {
+        self.bytes += text.len() as u64;
+        self.passed_through += text.chars().count() as u64;
+    }
+
+    /// Counts the chars of the alphabet in a selected region as transformed.
+    /// The text it is in must have been passed before.
+    pub(crate) fn select(&mut self, region: &str, alphabet: &Alphabet) {
+        let transformed = region.chars().filter(|c| alphabet.contains(*c)).count() as u64;
+        self.transformed += transformed;
+        self.passed_through -= transformed;
+    }
+}
+
+impl Display for Stats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Bytes processed:            {}", self.bytes)?;
+        writeln!(f, "Characters transformed:     {}", self.transformed)?;
+        writeln!(f, "Characters passed through:  {}", self.passed_through)?;
+        writeln!(f, "Elapsed time:               {:.3}s", self.elapsed.as_secs_f64())?;
+        writeln!(f, "Throughput:                 {:.1} MB/s", throughput(self.bytes, self.elapsed))
+    }
+}
+
+fn throughput(bytes: u64, elapsed: Duration) -> f64 {
+    match elapsed.as_secs_f64() {
+        secs if secs > 0.0 => bytes as f64 / MEGABYTE / secs,
+        _ => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_draws_percentage_when_total_is_known() {
+        let mut out = Vec::new();
+        let mut progress = Progress::new(Some(2_000_000));
+
+        progress.advance(1_000_000, &mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with(&format!("\r[{}{}]  50% 1.0/2.0 MB", "#".repeat(15), " ".repeat(15))));
+    }
+
+    #[test]
+    fn it_draws_only_bytes_when_total_is_unknown() {
+        let mut out = Vec::new();
+        let mut progress = Progress::new(None);
+
+        progress.advance(1_500_000, &mut out).unwrap();
+        progress.finish(&mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("\r1.5 MB "));
+        assert!(out.ends_with(" MB/s\n"));
+    }
+
+    #[test]
+    fn it_counts_transformed_and_passed_through_characters() {
+        let mut stats = Stats::default();
+
+        stats.count("Ab 1", &Alphabet::Letters);
+        stats.count("ЗaЗ", &Alphabet::Letters);
+
+        assert_eq!(3, stats.transformed);
+        assert_eq!(4, stats.passed_through);
+        assert_eq!(9, stats.bytes);
+    }
+
+    #[test]
+    fn it_counts_the_chars_of_the_alphabet_as_transformed() {
+        let mut stats = Stats::default();
+
+        stats.count("Ab 1, Ç", &Alphabet::Alphanumeric);
+        stats.count("Ab 1, Ç", &Alphabet::Printable);
+
+        assert_eq!(7, stats.transformed);
+        assert_eq!(7, stats.passed_through);
+    }
+
+    #[test]
+    fn it_counts_only_the_selected_regions_as_transformed() {
+        let mut stats = Stats::default();
+
+        stats.pass("Keep <s>secret 1</s>");
+        stats.select("secret 1", &Alphabet::Letters);
+
+        assert_eq!(6, stats.transformed);
+        assert_eq!(14, stats.passed_through);
+        assert_eq!(20, stats.bytes);
+    }
+}
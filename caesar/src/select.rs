@@ -0,0 +1,484 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+
+use crate::pattern::{Pattern, PatternError};
+
+/// Selects the regions of a text to transform, so the rest of it can be kept
+/// byte by byte, like the structure of a JSON or CSV document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selector {
+    /// The matches of the pattern, or the first group of them if it has any.
+    Pattern(Pattern),
+    /// The string values under a path, like `.users[].name`.
+    Json(Vec<Segment>),
+    /// The values of a column, found by its name in the header.
+    Csv { column: String, index: Option<usize> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Segment {
+    Key(String),
+    AnyKey,
+    Elements,
+}
+
+impl Selector {
+    pub fn pattern(pattern: &str) -> Result<Selector, PatternError> {
+        Ok(Selector::Pattern(Pattern::new(pattern)?))
+    }
+
+    /// Keys are separated by dots, `*` stands for any key and `[]` for every
+    /// element of an array. An empty path, or just a dot, selects every string
+    /// value of the document.
+    pub fn json(path: &str) -> Result<Selector, SelectError> {
+        let path = path.strip_prefix('.').unwrap_or(path);
+        let mut segments = Vec::new();
+        if path.is_empty() {
+            return Ok(Selector::Json(segments));
+        }
+        for part in path.split('.') {
+            let mut key = part;
+            let mut elements = 0;
+            while let Some(rest) = key.strip_suffix("[]") {
+                key = rest;
+                elements += 1;
+            }
+            match key {
+                "" if elements == 0 => return Err(SelectError::Path),
+                "" => {}
+                "*" => segments.push(Segment::AnyKey),
+                key => segments.push(Segment::Key(key.to_string())),
+            }
+            segments.extend((0..elements).map(|_| Segment::Elements));
+        }
+        Ok(Selector::Json(segments))
+    }
+
+    pub fn csv(column: &str) -> Selector {
+        Selector::Csv { column: column.to_string(), index: None }
+    }
+
+    /// Finds the byte ranges of the text to transform, in order. The CSV
+    /// selector takes the header from the first text, so following texts can
+    /// hold just more rows.
+    pub fn regions(&mut self, text: &str) -> Result<Vec<Range<usize>>, SelectError> {
+        match self {
+            Selector::Pattern(pattern) => {
+                let group = pattern.groups().min(1);
+                Ok(pattern.captures_iter(text).iter().filter_map(|captures| captures.get(group)).collect())
+            }
+            Selector::Json(path) => {
+                let mut scanner = JsonScanner { bytes: text.as_bytes(), position: 0, path, regions: Vec::new() };
+                scanner.values()?;
+                Ok(scanner.regions)
+            }
+            Selector::Csv { column, index } => {
+                let mut regions = Vec::new();
+                for record in csv_records(text)? {
+                    match *index {
+                        Some(index) => {
+                            if let Some(field) = record.get(index) {
+                                regions.extend(field.iter().cloned())
+                            }
+                        }
+                        None => {
+                            let names: Vec<String> = record.iter()
+                                .map(|field| field.iter().map(|run| &text[run.clone()]).collect::<Vec<&str>>().join("\""))
+                                .collect();
+                            let found = names.iter().position(|name| name == column);
+                            *index = Some(found.ok_or_else(|| SelectError::Column(column.clone()))?);
+                        }
+                    }
+                }
+                Ok(regions)
+            }
+        }
+    }
+}
+
+/// Transforms the regions of the text, which must be in order and must not
+/// overlap, keeping the rest as it is.
+pub fn apply<F, E>(text: &str, regions: &[Range<usize>], mut exec: F) -> Result<String, E>
+    where F: FnMut(&str) -> Result<String, E> {
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+    for region in regions.iter().filter(|region| !region.is_empty()) {
+        result.push_str(&text[last..region.start]);
+        result.push_str(&exec(&text[region.clone()])?);
+        last = region.end;
+    }
+    result.push_str(&text[last..]);
+    Ok(result)
+}
+
+// The objects and arrays the scanner is in, with their depth in the path.
+enum Container {
+    Object(Option<usize>),
+    Array(Option<usize>),
+}
+
+// Walks the document, following the path in order to find the selected
+// strings. Escape sequences are left out of the regions, so the strings stay
+// valid after transforming them.
+struct JsonScanner<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    path: &'a [Segment],
+    regions: Vec<Range<usize>>,
+}
+
+impl JsonScanner<'_> {
+    // Several documents can follow each other, as in JSON lines.
+    fn values(&mut self) -> Result<(), SelectError> {
+        loop {
+            self.whitespace();
+            if self.position == self.bytes.len() {
+                return Ok(());
+            }
+            self.value(Some(0))?;
+        }
+    }
+
+    // The depth is the number of segments of the path matched so far, or None
+    // when the value is out of the path. The objects and arrays the value is
+    // in are kept in a stack, so deep documents don't exhaust the call stack.
+    fn value(&mut self, depth: Option<usize>) -> Result<(), SelectError> {
+        let mut containers: Vec<Container> = Vec::new();
+        let mut depth = depth;
+        loop {
+            self.whitespace();
+            match self.bytes.get(self.position) {
+                Some(b'{') => {
+                    self.position += 1;
+                    self.whitespace();
+                    if self.bytes.get(self.position) == Some(&b'}') {
+                        self.position += 1;
+                    } else {
+                        containers.push(Container::Object(depth));
+                        depth = self.key(depth)?;
+                        continue;
+                    }
+                }
+                Some(b'[') => {
+                    self.position += 1;
+                    self.whitespace();
+                    if self.bytes.get(self.position) == Some(&b']') {
+                        self.position += 1;
+                    } else {
+                        containers.push(Container::Array(depth));
+                        depth = self.child(depth, |segment| *segment == Segment::Elements);
+                        continue;
+                    }
+                }
+                Some(b'"') => {
+                    self.string(depth == Some(self.path.len()))?;
+                }
+                _ => {
+                    let start = self.position;
+                    while self.bytes.get(self.position).is_some_and(|b| !b",]}\" \t\r\n".contains(b)) {
+                        self.position += 1;
+                    }
+                    if self.position == start {
+                        return Err(SelectError::Json(self.position));
+                    }
+                }
+            }
+            // The value is done, so the next one is found after the commas and
+            // the ends of the containers.
+            loop {
+                let Some(container) = containers.last() else {
+                    return Ok(());
+                };
+                self.whitespace();
+                match (container, self.bytes.get(self.position)) {
+                    (Container::Object(parent), Some(b',')) => {
+                        let parent = *parent;
+                        self.position += 1;
+                        depth = self.key(parent)?;
+                        break;
+                    }
+                    (Container::Array(parent), Some(b',')) => {
+                        self.position += 1;
+                        depth = self.child(*parent, |segment| *segment == Segment::Elements);
+                        break;
+                    }
+                    (Container::Object(_), Some(b'}')) | (Container::Array(_), Some(b']')) => {
+                        self.position += 1;
+                        containers.pop();
+                    }
+                    _ => return Err(SelectError::Json(self.position)),
+                }
+            }
+        }
+    }
+
+    // Reads a key of an object and its colon, and returns the depth of its
+    // value.
+    fn key(&mut self, depth: Option<usize>) -> Result<Option<usize>, SelectError> {
+        self.whitespace();
+        if self.bytes.get(self.position) != Some(&b'"') {
+            return Err(SelectError::Json(self.position));
+        }
+        let bytes = self.bytes;
+        let key = &bytes[self.string(false)?];
+        let child = self.child(depth, |segment| match segment {
+            Segment::Key(name) => name.as_bytes() == key,
+            Segment::AnyKey => true,
+            Segment::Elements => false,
+        });
+        self.expect(b':')?;
+        Ok(child)
+    }
+
+    // Everything inside a selected value is selected too.
+    fn child<F>(&self, depth: Option<usize>, matches: F) -> Option<usize>
+        where F: Fn(&Segment) -> bool {
+        match depth {
+            Some(depth) if depth == self.path.len() => Some(depth),
+            Some(depth) if matches(&self.path[depth]) => Some(depth + 1),
+            _ => None,
+        }
+    }
+
+    // Returns the range of the raw content between the quotes.
+    fn string(&mut self, selected: bool) -> Result<Range<usize>, SelectError> {
+        self.position += 1;
+        let start = self.position;
+        let mut run = start;
+        loop {
+            match self.bytes.get(self.position) {
+                Some(b'"') => {
+                    if selected {
+                        self.regions.push(run..self.position);
+                    }
+                    self.position += 1;
+                    return Ok(start..self.position - 1);
+                }
+                Some(b'\\') => {
+                    if selected {
+                        self.regions.push(run..self.position);
+                    }
+                    self.position += match self.bytes.get(self.position + 1) {
+                        Some(b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't') => 2,
+                        Some(b'u') if self.bytes.get(self.position + 2..self.position + 6).is_some_and(|digits| digits.iter().all(u8::is_ascii_hexdigit)) => 6,
+                        _ => return Err(SelectError::Json(self.position)),
+                    };
+                    run = self.position;
+                }
+                Some(_) => self.position += 1,
+                None => return Err(SelectError::Json(start - 1)),
+            }
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), SelectError> {
+        self.whitespace();
+        match self.bytes.get(self.position) == Some(&byte) {
+            true => {
+                self.position += 1;
+                Ok(())
+            }
+            false => Err(SelectError::Json(self.position)),
+        }
+    }
+
+    fn whitespace(&mut self) {
+        while self.bytes.get(self.position).is_some_and(|b| b.is_ascii_whitespace()) {
+            self.position += 1;
+        }
+    }
+}
+
+// Splits the text in records of fields. Every field is a list of runs of
+// content, as the quotes of quoted fields and the doubled quotes inside them
+// are left out.
+fn csv_records(text: &str) -> Result<Vec<Vec<Vec<Range<usize>>>>, SelectError> {
+    let bytes = text.as_bytes();
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut position = 0;
+    while position < bytes.len() {
+        let mut runs = Vec::new();
+        if bytes[position] == b'"' {
+            let start = position;
+            position += 1;
+            let mut run = position;
+            loop {
+                match (bytes.get(position), bytes.get(position + 1)) {
+                    (Some(b'"'), Some(b'"')) => {
+                        runs.push(run..position);
+                        position += 2;
+                        run = position;
+                    }
+                    (Some(b'"'), _) => {
+                        runs.push(run..position);
+                        position += 1;
+                        break;
+                    }
+                    (Some(_), _) => position += 1,
+                    (None, _) => return Err(SelectError::Csv(start)),
+                }
+            }
+        } else {
+            let start = position;
+            while position < bytes.len() && !b",\r\n".contains(&bytes[position]) {
+                position += 1;
+            }
+            runs.push(start..position);
+        }
+        record.push(runs);
+        match bytes.get(position) {
+            Some(b',') => {
+                position += 1;
+                if position == bytes.len() {
+                    record.push(Vec::new());
+                }
+            }
+            Some(b'\r') | Some(b'\n') => {
+                position += if bytes[position..].starts_with(b"\r\n") { 2 } else { 1 };
+                records.push(std::mem::take(&mut record));
+            }
+            None => {}
+            Some(_) => return Err(SelectError::Csv(position)),
+        }
+    }
+    if !record.is_empty() {
+        records.push(record);
+    }
+    Ok(records)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SelectError {
+    Path,
+    Column(String),
+    Json(usize),
+    Csv(usize),
+}
+
+impl Display for SelectError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SelectError::Path => write!(f, "the JSON path must be made of keys separated by dots, like .users[].name."),
+            SelectError::Column(column) => write!(f, "the column {} is not in the CSV header.", column),
+            SelectError::Json(offset) => write!(f, "invalid JSON at byte {}.", offset),
+            SelectError::Csv(offset) => write!(f, "invalid CSV at byte {}.", offset),
+        }
+    }
+}
+
+impl Error for SelectError {}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use crate::caesar::{Caesar, Mode};
+
+    use super::*;
+
+    fn encrypt(selector: &mut Selector, text: &str) -> String {
+        let regions = selector.regions(text).unwrap();
+        apply(text, &regions, |region| Caesar::exec(region, 1, Mode::Encrypt)).unwrap()
+    }
+
+    #[test]
+    fn it_transforms_the_pattern_matches() {
+        let mut selector = Selector::pattern("\\d+|secret").unwrap();
+        assert_eq!("abc 234 tfdsfu.", encrypt(&mut selector, "abc 234 secret."));
+    }
+
+    #[test]
+    fn it_transforms_only_the_first_group_of_the_pattern() {
+        let mut selector = Selector::pattern("<b>(.*?)</b>").unwrap();
+        assert_eq!("Keep <b>bcd</b> and <b>fgh</b>.", encrypt(&mut selector, "Keep <b>abc</b> and <b>efg</b>."));
+    }
+
+    #[test]
+    fn it_transforms_the_string_values_of_the_path() {
+        let mut selector = Selector::json(".users[].name").unwrap();
+        let text = r#"{"users": [{"name": "Ann", "role": "admin"}, {"name": "Bob"}], "name": "team"}"#;
+        let expected = r#"{"users": [{"name": "Boo", "role": "admin"}, {"name": "Cpc"}], "name": "team"}"#;
+        assert_eq!(expected, encrypt(&mut selector, text));
+    }
+
+    #[test]
+    fn it_transforms_every_string_value_with_an_empty_path() {
+        let mut selector = Selector::json(".").unwrap();
+        let text = "{\"a\": \"xy\", \"b\": [1, true, null, \"z\", {\"c\": \"d\"}]}\n{\"e\": \"f\"}\n";
+        let expected = "{\"a\": \"yz\", \"b\": [1, true, null, \"a\", {\"c\": \"e\"}]}\n{\"e\": \"g\"}\n";
+        assert_eq!(expected, encrypt(&mut selector, text));
+    }
+
+    #[test]
+    fn it_selects_whole_values_and_any_key() {
+        let mut selector = Selector::json("*.tags").unwrap();
+        let text = r#"{"a": {"tags": ["x", "y"]}, "b": {"tags": "z", "other": "w"}}"#;
+        let expected = r#"{"a": {"tags": ["y", "z"]}, "b": {"tags": "a", "other": "w"}}"#;
+        assert_eq!(expected, encrypt(&mut selector, text));
+    }
+
+    #[test]
+    fn it_keeps_json_escape_sequences() {
+        let mut selector = Selector::json("").unwrap();
+        let text = r#"["a\nb\"céd"]"#;
+        assert_eq!(r#"["b\nc\"dée"]"#, encrypt(&mut selector, text));
+    }
+
+    #[test]
+    fn it_returns_error_on_invalid_json() {
+        let mut selector = Selector::json("").unwrap();
+        assert_eq!(SelectError::Json(5), selector.regions(r#"{"a" 1}"#).unwrap_err());
+        assert_eq!(SelectError::Json(1), selector.regions(r#"["a"#).unwrap_err());
+        assert_eq!(SelectError::Json(5), selector.regions("[1, 2").unwrap_err());
+        assert_eq!(SelectError::Path, Selector::json("a..b").unwrap_err());
+    }
+
+    #[test]
+    fn it_returns_error_on_invalid_json_escape_sequences() {
+        let mut selector = Selector::json("").unwrap();
+        assert_eq!(SelectError::Json(2), selector.regions("[\"\\uaññ x\"]").unwrap_err());
+        assert_eq!(SelectError::Json(2), selector.regions("[\"\\ñabc\"]").unwrap_err());
+        assert_eq!(SelectError::Json(2), selector.regions("[\"\\u12\"]").unwrap_err());
+        assert_eq!(SelectError::Json(2), selector.regions("[\"\\x\"]").unwrap_err());
+        assert_eq!(vec![2..2, 8..8, 10..11], selector.regions(r#"["\u00e9\/a"]"#).unwrap());
+    }
+
+    #[test]
+    fn it_scans_deeply_nested_json() {
+        let mut selector = Selector::json("").unwrap();
+        let text = format!("{}\"a\"{}", "[{\"k\": ".repeat(100_000), "}]".repeat(100_000));
+        assert_eq!(vec![700_001..700_002], selector.regions(&text).unwrap());
+        assert_eq!(SelectError::Json(300_000), selector.regions(&"[[[".repeat(100_000)).unwrap_err());
+    }
+
+    #[test]
+    fn it_transforms_the_csv_column() {
+        let mut selector = Selector::csv("name");
+        let text = "id,name,role\n1,Ann,admin\r\n2,\"Bob, \"\"Jr\"\"\",user\n3,,user";
+        let expected = "id,name,role\n1,Boo,admin\r\n2,\"Cpc, \"\"Ks\"\"\",user\n3,,user";
+        assert_eq!(expected, encrypt(&mut selector, text));
+    }
+
+    #[test]
+    fn it_keeps_the_csv_header_between_texts() {
+        let mut selector = Selector::csv("name");
+        assert_eq!("id,name\n", encrypt(&mut selector, "id,name\n"));
+        assert_eq!("1,Boo\n", encrypt(&mut selector, "1,Ann\n"));
+    }
+
+    #[test]
+    fn it_returns_error_on_unknown_csv_column() {
+        let mut selector = Selector::csv("email");
+        assert_eq!(SelectError::Column("email".to_string()), selector.regions("id,name\n1,Ann").unwrap_err());
+        assert_eq!(SelectError::Csv(2), Selector::csv("id").regions("1,\"a").unwrap_err());
+    }
+
+    #[test]
+    fn it_applies_the_regions_in_order() {
+        let result: Result<String, Infallible> = apply("abcdef", &[0..1, 2..2, 3..5], |region| Ok(region.to_uppercase()));
+        assert_eq!("AbcDEf", result.unwrap());
+    }
+}
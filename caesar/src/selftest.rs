@@ -0,0 +1,190 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::io::Write;
+
+use std::any::type_name;
+
+use crate::{caesar, exec, hill};
+use crate::caesar::{Alphabet, Mode};
+use crate::pattern::PatternError;
+use crate::select::SelectError;
+
+/// A known answer: running the CLI with the args over the input must give
+/// the expected result.
+struct Vector {
+    args: Vec<String>,
+    input: &'static str,
+    expected: Expected,
+}
+
+enum Expected {
+    Text(&'static str),
+    /// An error of the type with the name.
+    Error(&'static str, fn(&(dyn Error + 'static)) -> bool),
+}
+
+fn vector(args: &[&str], input: &'static str, expected: Expected) -> Vector {
+    Vector { args: args.iter().map(|arg| arg.to_string()).collect(), input, expected }
+}
+
+fn error<E: Error + 'static>() -> Expected {
+    let name = type_name::<E>().rsplit("::").next().unwrap_or_default();
+    Expected::Error(name, |error| error.is::<E>())
+}
+
+// The Caesar ones are the known answers of the caesar module, followed by the
+// other ciphers, the commands and the selections.
+fn vectors() -> Vec<Vector> {
+    let mut vectors: Vec<Vector> = caesar::known_answers().into_iter()
+        .map(|answer| {
+            let key = answer.key.to_string();
+            let mode = match answer.mode {
+                Mode::Encrypt => "-e",
+                Mode::Decrypt => "-d",
+            };
+            let chars: String;
+            let mut args = vec!["-k", key.as_str(), mode];
+            match &answer.alphabet {
+                Alphabet::Letters => {}
+                Alphabet::Alphanumeric => args.extend(["--charset", "alphanumeric"]),
+                Alphabet::Printable => args.extend(["--charset", "printable"]),
+                Alphabet::Custom(custom) => {
+                    chars = custom.iter().collect();
+                    args.extend(["--alphabet", chars.as_str()]);
+                }
+            }
+            let expected = match answer.expected {
+                Ok(text) => Expected::Text(text),
+                Err(_) => error::<caesar::KeyError>(),
+            };
+            vector(&args, answer.input, expected)
+        })
+        .collect();
+    vectors.extend([
+        vector(&["-c", "substitution", "-w", "QWERTYUIOPASDFGHJKLZXCVBNM", "-e"], "Hello, World!", Expected::Text("Itssg, Vgksr!")),
+        vector(&["-c", "substitution", "-w", "QWERTYUIOPASDFGHJKLZXCVBNM", "-d"], "Itssg, Vgksr!", Expected::Text("Hello, World!")),
+        vector(&["-c", "substitution", "-w", "zebras"], "Hello", Expected::Text("Daiil")),
+        vector(&["-c", "vigenere", "-w", "lemon", "-e"], "Attack at dawn!", Expected::Text("Lxfopv ef rnhr!")),
+        vector(&["-c", "vigenere", "-w", "lemon", "-d"], "Lxfopv ef rnhr!", Expected::Text("Attack at dawn!")),
+        vector(&["-c", "railfence", "-k", "3", "-e"], "WEAREDISCOVEREDFLEEATONCE", Expected::Text("WECRLTEERDSOEEFEAOCAIVDEN")),
+        vector(&["-c", "railfence", "-k", "3", "-d"], "WECRLTEERDSOEEFEAOCAIVDEN", Expected::Text("WEAREDISCOVEREDFLEEATONCE")),
+        vector(&["-c", "columnar", "-w", "zebras", "-e"], "WEAREDISCOVEREDFLEEATONCE", Expected::Text("EVLNACDTESEAROFODEECWIREE")),
+        vector(&["-c", "columnar", "-w", "zebras", "-d"], "EVLNACDTESEAROFODEECWIREE", Expected::Text("WEAREDISCOVEREDFLEEATONCE")),
+        vector(&["-c", "columnar", "-w", "zebras", "--pad", "Q"], "WEAREDISCOVEREDFLEEATONCE", Expected::Text("EVLNQACDTQESEAQROFOQDEECQWIREE")),
+        vector(&["-c", "enigma", "-w", "B I,II,III AAA AAA", "-e"], "AAAAA", Expected::Text("BDZGO")),
+        vector(&["-c", "enigma", "-w", "B I,II,III AAA AAA", "-d"], "BDZGO", Expected::Text("AAAAA")),
+        vector(&["-c", "enigma", "-w", "B I,II,III BBB AAA"], "AAAAA", Expected::Text("EWTYX")),
+        vector(&["-c", "playfair", "-w", "playfair example", "-e"], "Hide the gold in the tree stump", Expected::Text("BMODZBXDNABEKUDMUIXMMOUVIF")),
+        vector(&["-c", "playfair", "-w", "playfair example", "-d"], "BMODZBXDNABEKUDMUIXMMOUVIF", Expected::Text("HIDETHEGOLDINTHETREXESTUMP")),
+        vector(&["-c", "hill", "-w", "GYBNQKURP", "-e"], "ACTCAT", Expected::Text("POHFIN")),
+        vector(&["-c", "hill", "-w", "GYBNQKURP", "-d"], "POHFIN", Expected::Text("ACTCAT")),
+        vector(&["-c", "hill", "-w", "abcd"], "ACT", error::<hill::KeyError>()),
+        vector(&["crack"], "Wkh txlfn eurzq ira mxpsv ryhu wkh odcb grj", Expected::Text("The quick brown fox jumps over the lazy dog")),
+        vector(&["crib", "-p", "the"], "Ghdu Ere, wkh udeelw lv lq wkh kroh.", Expected::Text("10 3\n27 3\n")),
+        // Every line is transposed on its own.
+        vector(&["-c", "columnar", "-w", "zebras", "--line-buffered"], "WEAREDISCOVERED\nFLEEATONCE\n", Expected::Text("EVACDESERODEWIR\nAECLNEETFO\n")),
+        vector(&["-k", "3", "--regex", "<s>(.*?)</s>"], "Keep <s>secret data</s> here", Expected::Text("Keep <s>vhfuhw gdwd</s> here")),
+        vector(&["-k", "3", "--regex", "(a"], "a", error::<PatternError>()),
+        vector(&["-k", "3", "--json", ".name"], r#"{"name": "Bob", "role": "admin"}"#, Expected::Text(r#"{"name": "Ere", "role": "admin"}"#)),
+        vector(&["-k", "3", "--json", ".a"], r#"{"a" 1}"#, error::<SelectError>()),
+        vector(&["-k", "3", "--csv", "name"], "id,name\n1,Bob\n", Expected::Text("id,name\n1,Ere\n")),
+        vector(&["-k", "3", "--csv", "name"], "id\n1\n", error::<SelectError>()),
+    ]);
+    vectors
+}
+
+/// Runs every known-answer vector through the same path as the command line,
+/// writing a line with the outcome of each one. Fails if any of them does
+/// not match.
+pub(crate) fn run<W: Write>(writer: W) -> Result<(), Box<dyn Error>> {
+    check(&vectors(), writer)
+}
+
+fn check<W: Write>(vectors: &[Vector], mut writer: W) -> Result<(), Box<dyn Error>> {
+    let mut failed = 0;
+    for vector in vectors {
+        let mut output = Vec::new();
+        let result = exec::with_stderr(&vector.args, vector.input.as_bytes(), &mut output, io::sink())
+            .map(|_| String::from_utf8_lossy(&output).to_string());
+        let mismatch = match (&vector.expected, result) {
+            (Expected::Text(expected), Ok(text)) if *expected == text => None,
+            (Expected::Text(expected), Ok(text)) => Some(format!("expected {:?}, got {:?}", expected, text)),
+            (Expected::Text(expected), Err(error)) => Some(format!("expected {:?}, got error: {}", expected, error)),
+            (Expected::Error(_, is), Err(error)) if is(error.as_ref()) => None,
+            (Expected::Error(name, _), Err(error)) => Some(format!("expected {}, got error: {}", name, error)),
+            (Expected::Error(name, _), Ok(text)) => Some(format!("expected {}, got {:?}", name, text)),
+        };
+        match mismatch {
+            None => writeln!(writer, "ok    {}", vector.args.join(" "))?,
+            Some(mismatch) => {
+                failed += 1;
+                writeln!(writer, "FAIL  {}: {}", vector.args.join(" "), mismatch)?
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(Box::new(SelftestError { failed, total: vectors.len() }));
+    }
+    writeln!(writer, "{} known-answer vectors passed.", vectors.len())?;
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct SelftestError {
+    failed: usize,
+    total: usize,
+}
+
+impl Display for SelftestError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of {} known-answer vectors failed.", self.failed, self.total)
+    }
+}
+
+impl Error for SelftestError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_passes_all_the_vectors() {
+        let mut output = Vec::new();
+
+        run(&mut output).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let total = vectors().len();
+        assert_eq!(total, output.lines().filter(|line| line.starts_with("ok ")).count());
+        assert!(output.ends_with(&format!("{} known-answer vectors passed.\n", total)));
+    }
+
+    #[test]
+    fn it_reports_the_mismatches() {
+        let vectors = &[
+            vector(&["-k", "1"], "ABC", Expected::Text("BCD")),
+            vector(&["-k", "2"], "ABC", Expected::Text("BCD")),
+            vector(&["-k", "1"], "ABC", error::<caesar::KeyError>()),
+            vector(&["-k", "-1"], "ABC", error::<caesar::KeyError>()),
+            vector(&["-k", "-1"], "ABC", error::<SelectError>()),
+        ];
+        let mut output = Vec::new();
+
+        let err = check(vectors, &mut output).unwrap_err();
+
+        assert_eq!(Some(&SelftestError { failed: 3, total: 5 }), err.downcast_ref::<SelftestError>());
+        assert_eq!("ok    -k 1\n\
+        FAIL  -k 2: expected \"BCD\", got \"CDE\"\n\
+        FAIL  -k 1: expected KeyError, got \"BCD\"\n\
+        ok    -k -1\n\
+        FAIL  -k -1: expected SelectError, got error: the key parameter must be a positive number between 0 - 999999.\n", String::from_utf8(output).unwrap());
+    }
+
+    #[test]
+    fn errors_selftest_has_display() {
+        let error = SelftestError { failed: 2, total: 35 };
+        assert_eq!("2 of 35 known-answer vectors failed.", format!("{}", error));
+    }
+}
@@ -0,0 +1,289 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use crate::caesar::Mode;
+use crate::lang::{Model, ModelError};
+
+const ALPHABET_LEN: usize = 26;
+// Consecutive swaps without improvement before a climb is considered stuck.
+const MAX_FAILED_SWAPS: usize = 1000;
+
+/// Monoalphabetic substitution cipher, in which every letter of the alphabet
+/// is replaced by another one, following a permutation of the alphabet.
+///
+/// Caesar is the particular case in which the permutation is a rotation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Substitution {
+    encryption: [u8; ALPHABET_LEN],
+    decryption: [u8; ALPHABET_LEN],
+}
+
+impl Substitution {
+    /// Accepts a permutation of the 26 letters of the alphabet, in which the
+    /// first letter replaces the "a", the second one the "b" and so on.
+    ///
+    /// Any other word is taken as a keyword. Its letters go first, without
+    /// repetitions, followed by the rest of the alphabet in order.
+    pub fn new(key: &str) -> Result<Substitution, KeyError> {
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(KeyError);
+        }
+        let mut encryption = Vec::with_capacity(ALPHABET_LEN);
+        let keyword = key.bytes().map(|b| b.to_ascii_lowercase() - b'a');
+        for letter in keyword.chain(0..ALPHABET_LEN as u8) {
+            if !encryption.contains(&letter) {
+                encryption.push(letter)
+            }
+        }
+        Ok(Substitution::from_encryption(encryption.try_into().unwrap()))
+    }
+
+    fn from_encryption(encryption: [u8; ALPHABET_LEN]) -> Substitution {
+        let mut decryption = [0; ALPHABET_LEN];
+        for (plain, cipher) in encryption.iter().enumerate() {
+            decryption[*cipher as usize] = plain as u8;
+        }
+        Substitution { encryption, decryption }
+    }
+
+    fn from_decryption(decryption: [u8; ALPHABET_LEN]) -> Substitution {
+        let mut encryption = [0; ALPHABET_LEN];
+        for (cipher, plain) in decryption.iter().enumerate() {
+            encryption[*plain as usize] = cipher as u8;
+        }
+        Substitution { encryption, decryption }
+    }
+
+    /// The key as a permutation of the alphabet, in lowercase.
+    pub fn key(&self) -> String {
+        self.encryption.iter().map(|i| (b'a' + i) as char).collect()
+    }
+
+    pub fn exec(&self, input: &str, mode: Mode) -> String {
+        let table = match mode {
+            Mode::Encrypt => &self.encryption,
+            Mode::Decrypt => &self.decryption,
+        };
+        input.chars()
+            .map(|c| match c.is_ascii_alphabetic() {
+                true => {
+                    let base = if c.is_ascii_uppercase() { b'A' } else { b'a' };
+                    (base + table[(c as u8 - base) as usize]) as char
+                }
+                false => c,
+            })
+            .collect()
+    }
+}
+
+/// The best decryption found by [solve].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Solution {
+    pub key: Substitution,
+    pub score: f64,
+    pub text: String,
+}
+
+/// Recovers the key of a ciphertext by hill climbing with random restarts.
+///
+/// Every climb starts with a key and keeps swapping random pairs of letters
+/// in it, keeping the swaps that improve the quadgram score of the decryption,
+/// until none of the last [MAX_FAILED_SWAPS] did. Climbs get
+/// stuck at local maximums, so it is restarted from random keys, keeping
+/// the best one. The first climb starts from the key that matches the
+/// letter frequencies of the ciphertext with the ones of the model.
+///
+/// Short ciphertexts might not have enough quadgrams to lead the climb
+/// to the right key. A few hundred letters are usually enough, although
+/// very repetitive texts can still get stuck and need more restarts.
+pub fn solve(ciphertext: &str, model: &Model, restarts: usize, seed: u64) -> Result<Solution, ModelError> {
+    if !model.has_quadgrams() {
+        return Err(ModelError);
+    }
+    let quadgrams = model.quadgram_log_probabilities();
+    let letters: Vec<u8> = ciphertext.bytes()
+        .filter(|b| b.is_ascii_alphabetic())
+        .map(|b| b.to_ascii_lowercase() - b'a')
+        .collect();
+    let mut rng = Rng::new(seed);
+    let mut best: Option<([u8; ALPHABET_LEN], f64)> = None;
+
+    for restart in 0..restarts.max(1) {
+        let mut key = match restart {
+            0 => frequency_key(&letters, model),
+            _ => rng.permutation(),
+        };
+        let mut score = quadgram_score(&letters, &key, &quadgrams);
+        let mut failed_swaps = 0;
+        while failed_swaps < MAX_FAILED_SWAPS {
+            let (a, b) = (rng.below(ALPHABET_LEN), rng.below(ALPHABET_LEN));
+            key.swap(a, b);
+            let candidate_score = quadgram_score(&letters, &key, &quadgrams);
+            if candidate_score > score {
+                score = candidate_score;
+                failed_swaps = 0;
+            } else {
+                key.swap(a, b);
+                failed_swaps += 1;
+            }
+        }
+        match best {
+            Some((_, best_score)) if best_score >= score => {}
+            _ => best = Some((key, score)),
+        }
+    }
+
+    let (decryption, score) = best.unwrap();
+    let key = Substitution::from_decryption(decryption);
+    let text = key.exec(ciphertext, Mode::Decrypt);
+    Ok(Solution { key, score, text })
+}
+
+// Pairs the most frequent letters of the ciphertext with the most frequent ones of the model.
+fn frequency_key(letters: &[u8], model: &Model) -> [u8; ALPHABET_LEN] {
+    let mut counts = [0_usize; ALPHABET_LEN];
+    for letter in letters {
+        counts[*letter as usize] += 1;
+    }
+    let mut cipher_order: Vec<usize> = (0..ALPHABET_LEN).collect();
+    cipher_order.sort_by(|a, b| counts[*b].cmp(&counts[*a]));
+    let unigrams = model.unigram_frequencies();
+    let mut plain_order: Vec<usize> = (0..ALPHABET_LEN).collect();
+    plain_order.sort_by(|a, b| unigrams[*b].total_cmp(&unigrams[*a]));
+
+    let mut key = [0; ALPHABET_LEN];
+    for (cipher, plain) in cipher_order.iter().zip(plain_order) {
+        key[*cipher] = plain as u8;
+    }
+    key
+}
+
+fn quadgram_score(letters: &[u8], decryption: &[u8; ALPHABET_LEN], quadgrams: &[f64]) -> f64 {
+    letters.windows(4)
+        .map(|w| {
+            let (a, b, c, d) = (decryption[w[0] as usize], decryption[w[1] as usize], decryption[w[2] as usize], decryption[w[3] as usize]);
+            quadgrams[((a as usize * ALPHABET_LEN + b as usize) * ALPHABET_LEN + c as usize) * ALPHABET_LEN + d as usize]
+        })
+        .sum()
+}
+
+// A xorshift pseudo random number generator. Good enough for picking
+// swaps, without adding a dependency for them.
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Rng {
+        // Splitmix64 spreads the bits of small seeds, which xorshift needs
+        // to produce good numbers from the start. Zero is the only state it cannot get out of.
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        Rng((z ^ (z >> 31)) | 1)
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    fn permutation(&mut self) -> [u8; ALPHABET_LEN] {
+        let mut permutation = [0; ALPHABET_LEN];
+        for (i, p) in permutation.iter_mut().enumerate() {
+            *p = i as u8;
+        }
+        for i in (1..ALPHABET_LEN).rev() {
+            permutation.swap(i, self.below(i + 1));
+        }
+        permutation
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct KeyError;
+
+const KEY_ERROR_MSG: &str = "the key must be a permutation of the 26 letters of the alphabet, or a keyword made of letters.";
+
+impl Display for KeyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", KEY_ERROR_MSG)
+    }
+}
+
+impl Error for KeyError {
+    fn description(&self) -> &str {
+        KEY_ERROR_MSG
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lang::Language;
+
+    use super::*;
+
+    const PLAINTEXT: &str = "The quick growth of the town surprised everyone who had lived there for many years. \
+    New houses appeared along the river, and the old market square was filled every morning with farmers selling \
+    bread, cheese and fresh vegetables to the families that had recently arrived.";
+
+    #[test]
+    fn it_encrypts_with_a_permutation() {
+        let substitution = Substitution::new("QWERTYUIOPASDFGHJKLZXCVBNM").unwrap();
+        let result = substitution.exec("Hello, World!", Mode::Encrypt);
+        assert_eq!("Itssg, Vgksr!", result);
+    }
+
+    #[test]
+    fn it_decrypts_with_a_permutation() {
+        let substitution = Substitution::new("qwertyuiopasdfghjklzxcvbnm").unwrap();
+        let result = substitution.exec("Itssg, Vgksr!", Mode::Decrypt);
+        assert_eq!("Hello, World!", result);
+    }
+
+    #[test]
+    fn it_derives_the_permutation_from_a_keyword() {
+        let substitution = Substitution::new("Zebras").unwrap();
+        assert_eq!("zebrascdfghijklmnopqtuvwxy", substitution.key());
+        assert_eq!("Daiil", substitution.exec("Hello", Mode::Encrypt));
+    }
+
+    #[test]
+    fn it_ignores_but_keeps_non_alphabet_characters() {
+        let substitution = Substitution::new("zebras").unwrap();
+        let result = substitution.exec("ЗaЗ 1, b.", Mode::Encrypt);
+        assert_eq!("ЗzЗ 1, e.", result);
+    }
+
+    #[test]
+    fn it_returns_error_on_invalid_keys() {
+        assert_eq!(KeyError, Substitution::new("").unwrap_err());
+        assert_eq!(KeyError, Substitution::new("zebra 2").unwrap_err());
+    }
+
+    #[test]
+    fn errors_key_has_display() {
+        assert_eq!(KEY_ERROR_MSG, format!("{}", KeyError));
+    }
+
+    #[test]
+    fn it_solves_the_key() {
+        let substitution = Substitution::new("LetsHopeItFindsThisKeyword").unwrap();
+        let ciphertext = substitution.exec(PLAINTEXT, Mode::Encrypt);
+
+        let solution = solve(&ciphertext, Model::builtin(Language::English), 20, 42).unwrap();
+
+        assert_eq!(PLAINTEXT, solution.text);
+    }
+
+    #[test]
+    fn it_returns_error_when_solving_without_quadgrams() {
+        let model = Model::parse("a 1.0\nab 1.0").unwrap();
+        assert_eq!(ModelError, solve("abc", &model, 1, 1).unwrap_err());
+    }
+}
@@ -0,0 +1,191 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use crate::caesar::Mode;
+
+/// Rail fence cipher. The text is written in a zigzag over a number of rails,
+/// going down and up again, and then read rail by rail.
+///
+/// Letters are not changed, only moved, so every character of the text takes
+/// part in the transposition, including spaces and line breaks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RailFence {
+    rails: usize,
+}
+
+impl RailFence {
+    pub fn new(rails: i32) -> Result<RailFence, KeyError> {
+        if rails < 1 {
+            return Err(KeyError::Rails);
+        }
+        Ok(RailFence { rails: rails as usize })
+    }
+
+    pub fn exec(&self, input: &str, mode: Mode) -> String {
+        let chars: Vec<char> = input.chars().collect();
+        let cycle = (2 * (self.rails - 1)).max(1);
+        let rail = |i: usize| match i % cycle {
+            r if r < self.rails => r,
+            r => cycle - r,
+        };
+        let mut order: Vec<usize> = (0..chars.len()).collect();
+        order.sort_by_key(|i| rail(*i));
+        transpose(&chars, &order, mode)
+    }
+}
+
+/// Keyed columnar transposition. The text is written in rows as long as the
+/// keyword, and then read column by column, following the alphabetical order
+/// of the letters of the keyword. Repeated letters are taken from left to right.
+///
+/// Several keywords separated by commas apply the transposition once with
+/// each of them, like in the double transposition cipher.
+///
+/// The last row is left incomplete unless a padding char is provided, which
+/// fills it during the first transposition. Decryption keeps the padding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Columnar {
+    keys: Vec<Vec<usize>>,
+    padding: Option<char>,
+}
+
+impl Columnar {
+    pub fn new(keywords: &str, padding: Option<char>) -> Result<Columnar, KeyError> {
+        let keys = keywords.split(',')
+            .map(|keyword| match !keyword.is_empty() && keyword.chars().all(|c| c.is_ascii_alphabetic()) {
+                true => {
+                    let letters = keyword.to_ascii_lowercase().into_bytes();
+                    let mut columns: Vec<usize> = (0..letters.len()).collect();
+                    columns.sort_by_key(|column| letters[*column]);
+                    Ok(columns)
+                }
+                false => Err(KeyError::Keyword),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Columnar { keys, padding })
+    }
+
+    pub fn exec(&self, input: &str, mode: Mode) -> String {
+        let mut chars: Vec<char> = input.chars().collect();
+        if let (Some(padding), Mode::Encrypt) = (self.padding, mode) {
+            let columns = self.keys[0].len();
+            chars.resize(chars.len().div_ceil(columns) * columns, padding);
+        }
+        let keys: Vec<&Vec<usize>> = match mode {
+            Mode::Encrypt => self.keys.iter().collect(),
+            Mode::Decrypt => self.keys.iter().rev().collect(),
+        };
+        for columns in keys {
+            let order: Vec<usize> = columns.iter()
+                .flat_map(|column| (*column..chars.len()).step_by(columns.len()))
+                .collect();
+            chars = transpose(&chars, &order, mode).chars().collect();
+        }
+        chars.into_iter().collect()
+    }
+}
+
+// The order holds the positions of the plaintext chars, in the order they
+// appear in the ciphertext.
+fn transpose(chars: &[char], order: &[usize], mode: Mode) -> String {
+    match mode {
+        Mode::Encrypt => order.iter().map(|i| chars[*i]).collect(),
+        Mode::Decrypt => {
+            let mut result = vec!['\0'; chars.len()];
+            for (c, i) in chars.iter().zip(order) {
+                result[*i] = *c;
+            }
+            result.into_iter().collect()
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum KeyError {
+    Rails,
+    Keyword,
+}
+
+impl Display for KeyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyError::Rails => write!(f, "the key must be a positive number of rails."),
+            KeyError::Keyword => write!(f, "the key must be one or more keywords made of letters, separated by commas."),
+        }
+    }
+}
+
+impl Error for KeyError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_encrypts_with_rail_fence() {
+        let rail_fence = RailFence::new(3).unwrap();
+        let result = rail_fence.exec("WEAREDISCOVEREDFLEEATONCE", Mode::Encrypt);
+        assert_eq!("WECRLTEERDSOEEFEAOCAIVDEN", result);
+    }
+
+    #[test]
+    fn it_decrypts_with_rail_fence() {
+        let rail_fence = RailFence::new(3).unwrap();
+        let result = rail_fence.exec("WECRLTEERDSOEEFEAOCAIVDEN", Mode::Decrypt);
+        assert_eq!("WEAREDISCOVEREDFLEEATONCE", result);
+    }
+
+    #[test]
+    fn it_round_trips_any_text_with_rail_fence() {
+        let text = "Learning Rust,\nЗ by example! ";
+        for rails in 1..10 {
+            let rail_fence = RailFence::new(rails).unwrap();
+            let encrypted = rail_fence.exec(text, Mode::Encrypt);
+            assert_eq!(text, rail_fence.exec(&encrypted, Mode::Decrypt), "failed with {} rails", rails);
+        }
+    }
+
+    #[test]
+    fn it_encrypts_with_columnar() {
+        let columnar = Columnar::new("ZEBRAS", None).unwrap();
+        let result = columnar.exec("WEAREDISCOVEREDFLEEATONCE", Mode::Encrypt);
+        assert_eq!("EVLNACDTESEAROFODEECWIREE", result);
+    }
+
+    #[test]
+    fn it_decrypts_with_columnar() {
+        let columnar = Columnar::new("zebras", None).unwrap();
+        let result = columnar.exec("EVLNACDTESEAROFODEECWIREE", Mode::Decrypt);
+        assert_eq!("WEAREDISCOVEREDFLEEATONCE", result);
+    }
+
+    #[test]
+    fn it_pads_the_last_row() {
+        let columnar = Columnar::new("zebras", Some('Q')).unwrap();
+        let result = columnar.exec("WEAREDISCOVEREDFLEEATONCE", Mode::Encrypt);
+        assert_eq!("EVLNQACDTQESEAQROFOQDEECQWIREE", result);
+        assert_eq!("WEAREDISCOVEREDFLEEATONCEQQQQQ", columnar.exec(&result, Mode::Decrypt));
+    }
+
+    #[test]
+    fn it_applies_double_transposition() {
+        let single = Columnar::new("zebras", None).unwrap();
+        let double = Columnar::new("zebras,lemon", None).unwrap();
+        let expected = Columnar::new("lemon", None).unwrap()
+            .exec(&single.exec("We are discovered, flee at once", Mode::Encrypt), Mode::Encrypt);
+
+        let encrypted = double.exec("We are discovered, flee at once", Mode::Encrypt);
+
+        assert_eq!(expected, encrypted);
+        assert_eq!("We are discovered, flee at once", double.exec(&encrypted, Mode::Decrypt));
+    }
+
+    #[test]
+    fn it_returns_error_on_invalid_keys() {
+        assert_eq!(KeyError::Rails, RailFence::new(0).unwrap_err());
+        assert_eq!(KeyError::Keyword, Columnar::new("", None).unwrap_err());
+        assert_eq!(KeyError::Keyword, Columnar::new("zebras,", None).unwrap_err());
+        assert_eq!(KeyError::Keyword, Columnar::new("zebras 2", None).unwrap_err());
+    }
+}
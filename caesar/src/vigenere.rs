@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use crate::caesar::Mode;
+use crate::crack;
+use crate::lang::Model;
+
+const ALPHABET_LEN: usize = 26;
+// Length of the repeated sequences looked up by the Kasiski examination.
+const KASISKI_SEQUENCE_LEN: usize = 3;
+// Index of coincidence of uniformly random letters, 1/26.
+const RANDOM_COINCIDENCE: f64 = 1.0 / ALPHABET_LEN as f64;
+// How much the Friedman estimate counts when ranking the key lengths. It is
+// rough with short texts, so it only decides between lengths that look alike
+// otherwise.
+const FRIEDMAN_WEIGHT: f64 = 0.2;
+// Key lengths tried when cracking, from the best ranked ones.
+const CRACKED_KEY_LENGTHS: usize = 5;
+
+/// Vigenère cipher. Every letter is shifted like in Caesar, but the shift
+/// changes from letter to letter, following the letters of a keyword
+/// ("a" shifts by 0, "b" by 1 and so on), which is repeated over the text.
+///
+/// Only letters move the keyword forward, the rest of the characters are
+/// kept as they are.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Vigenere {
+    shifts: Vec<u8>,
+}
+
+impl Vigenere {
+    pub fn new(keyword: &str) -> Result<Vigenere, KeyError> {
+        if keyword.is_empty() || !keyword.chars().all(|c| c.is_ascii_alphabetic()) {
+            return Err(KeyError);
+        }
+        let shifts = keyword.bytes().map(|b| b.to_ascii_lowercase() - b'a').collect();
+        Ok(Vigenere { shifts })
+    }
+
+    /// The keyword, in lowercase.
+    pub fn key(&self) -> String {
+        self.shifts.iter().map(|s| (b'a' + s) as char).collect()
+    }
+
+    pub fn exec(&self, input: &str, mode: Mode) -> String {
+        self.exec_from(input, mode, &mut 0)
+    }
+
+    /// Like [Vigenere::exec], but the keyword starts at the given letter
+    /// position, which is moved forward by the letters of the input. This
+    /// allows processing a text in chunks.
+    pub fn exec_from(&self, input: &str, mode: Mode, position: &mut usize) -> String {
+        input.chars()
+            .map(|c| match c.is_ascii_alphabetic() {
+                true => {
+                    let shift = self.shifts[*position % self.shifts.len()];
+                    *position += 1;
+                    let shift = match mode {
+                        Mode::Encrypt => shift,
+                        Mode::Decrypt => ALPHABET_LEN as u8 - shift,
+                    };
+                    let base = if c.is_ascii_uppercase() { b'A' } else { b'a' };
+                    (base + (c as u8 - base + shift) % ALPHABET_LEN as u8) as char
+                }
+                false => c,
+            })
+            .collect()
+    }
+}
+
+/// How likely a key length is, according to the ciphertext.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyLength {
+    pub length: usize,
+    /// Fraction of the distances between repeated sequences of the ciphertext
+    /// that are a multiple of the length (Kasiski examination).
+    pub kasiski: f64,
+    /// Average index of coincidence of the columns obtained by splitting the
+    /// ciphertext with this length. It gets close to the one of the language
+    /// when every column is encrypted with a single shift.
+    pub coincidence: f64,
+}
+
+/// Ranks all the key lengths up to the max one, from the most to the least
+/// likely, combining the Kasiski examination with the index of coincidence.
+/// The [friedman] estimate breaks the tie between a length and its multiples,
+/// which split the ciphertext in columns of a single shift too.
+pub fn key_lengths(ciphertext: &str, model: &Model, max_length: usize) -> Vec<KeyLength> {
+    let letters = letters(ciphertext);
+    let distances = repeated_sequence_distances(&letters);
+    let language_coincidence: f64 = model.unigram_frequencies().iter().map(|f| f * f).sum();
+    let estimate = friedman(ciphertext, model).max(1.0);
+
+    let mut key_lengths: Vec<KeyLength> = (1..=max_length.min(letters.len() / 2).max(1))
+        .map(|length| {
+            let multiples = distances.iter().filter(|d| d.is_multiple_of(length)).count();
+            let kasiski = match distances.len() {
+                0 => 0.0,
+                total => multiples as f64 / total as f64,
+            };
+            let coincidence = (0..length)
+                .map(|column| coincidence(letters.iter().skip(column).step_by(length)))
+                .sum::<f64>() / length as f64;
+            KeyLength { length, kasiski, coincidence }
+        })
+        .collect();
+    let rank = |key_length: &KeyLength| {
+        // 1 when the columns look like the language, 0 when they look random.
+        let coincidence = (key_length.coincidence - RANDOM_COINCIDENCE) / (language_coincidence - RANDOM_COINCIDENCE);
+        // Random distances are a multiple of the length once every length times.
+        let kasiski = key_length.kasiski - 1.0 / key_length.length as f64;
+        // 1 when the length is the estimated one, less the further it is.
+        let length = key_length.length as f64;
+        let friedman = length.min(estimate) / length.max(estimate);
+        coincidence + kasiski + FRIEDMAN_WEIGHT * friedman
+    };
+    key_lengths.sort_by(|a, b| rank(b).total_cmp(&rank(a)));
+    key_lengths
+}
+
+/// Friedman test. Estimates the key length from the index of coincidence of
+/// the whole ciphertext, which gets closer to the one of random letters the
+/// longer the key is.
+pub fn friedman(ciphertext: &str, model: &Model) -> f64 {
+    let language_coincidence: f64 = model.unigram_frequencies().iter().map(|f| f * f).sum();
+    let coincidence = coincidence(letters(ciphertext).iter());
+    (language_coincidence - RANDOM_COINCIDENCE) / (coincidence - RANDOM_COINCIDENCE).max(f64::EPSILON)
+}
+
+/// A possible key of a ciphertext.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyCandidate {
+    pub key: String,
+    /// Score of the decryption according to the model. Higher is better.
+    pub score: f64,
+    /// Likelihood of this key compared to the rest of the candidates, from 0 to 1.
+    pub confidence: f64,
+    pub text: String,
+}
+
+/// Finds the key of a ciphertext. The most likely key lengths are taken from
+/// [key_lengths], and every column of letters encrypted with the same shift
+/// is cracked as a Caesar ciphertext.
+///
+/// Returns the keys ranked from the most to the least likely. Repeated keys,
+/// like "abab" for "ab", are only returned once, in their shortest form.
+/// Ciphertexts without letters have no candidates.
+pub fn crack(ciphertext: &str, model: &Model, max_length: usize) -> Vec<KeyCandidate> {
+    let letters: Vec<char> = ciphertext.chars().filter(|c| c.is_ascii_alphabetic()).collect();
+    let mut candidates: Vec<KeyCandidate> = Vec::new();
+    if letters.is_empty() {
+        return candidates;
+    }
+
+    for key_length in key_lengths(ciphertext, model, max_length).iter().take(CRACKED_KEY_LENGTHS) {
+        let shifts: Vec<u8> = (0..key_length.length)
+            .map(|column| {
+                // Spaces keep the model from scoring letters that were not together as bigrams.
+                let column: String = letters.iter().skip(column).step_by(key_length.length)
+                    .flat_map(|c| [*c, ' '])
+                    .collect();
+                crack::crack(&column, model)[0].key as u8
+            })
+            .collect();
+        let vigenere = Vigenere { shifts: shortest_period(&shifts).to_vec() };
+        if candidates.iter().any(|c| c.key == vigenere.key()) {
+            continue;
+        }
+        let text = vigenere.exec(ciphertext, Mode::Decrypt);
+        candidates.push(KeyCandidate { key: vigenere.key(), score: model.score(&text), confidence: 0.0, text });
+    }
+
+    // Scores are averages per letter, so they are scaled back to the whole
+    // text for comparing how likely every decryption is against the others.
+    let best_score = candidates.iter().map(|c| c.score).fold(f64::MIN, f64::max);
+    let likelihoods: Vec<f64> = candidates.iter()
+        .map(|c| ((c.score - best_score) * letters.len() as f64).exp())
+        .collect();
+    let total: f64 = likelihoods.iter().sum();
+    for (candidate, likelihood) in candidates.iter_mut().zip(likelihoods) {
+        candidate.confidence = likelihood / total;
+    }
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+    candidates
+}
+
+fn letters(text: &str) -> Vec<u8> {
+    text.bytes()
+        .filter(|b| b.is_ascii_alphabetic())
+        .map(|b| b.to_ascii_lowercase() - b'a')
+        .collect()
+}
+
+// Distances between all the occurrences of every repeated sequence.
+fn repeated_sequence_distances(letters: &[u8]) -> Vec<usize> {
+    let mut last_seen = HashMap::new();
+    let mut distances = Vec::new();
+    for (position, sequence) in letters.windows(KASISKI_SEQUENCE_LEN).enumerate() {
+        if let Some(previous) = last_seen.insert(sequence, position) {
+            distances.push(position - previous);
+        }
+    }
+    distances
+}
+
+// Probability of picking two equal letters from the text.
+fn coincidence<'a>(letters: impl Iterator<Item=&'a u8>) -> f64 {
+    let mut counts = [0_usize; ALPHABET_LEN];
+    let mut total = 0;
+    for letter in letters {
+        counts[*letter as usize] += 1;
+        total += 1;
+    }
+    if total < 2 {
+        return 0.0;
+    }
+    let pairs: usize = counts.iter().map(|c| c * c.saturating_sub(1)).sum();
+    pairs as f64 / (total * (total - 1)) as f64
+}
+
+fn shortest_period(shifts: &[u8]) -> &[u8] {
+    (1..shifts.len())
+        .find(|period| shifts.len().is_multiple_of(*period) && shifts.chunks(*period).all(|chunk| chunk == &shifts[..*period]))
+        .map(|period| &shifts[..period])
+        .unwrap_or(shifts)
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct KeyError;
+
+const KEY_ERROR_MSG: &str = "the key must be a keyword made of letters.";
+
+impl Display for KeyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", KEY_ERROR_MSG)
+    }
+}
+
+impl Error for KeyError {
+    fn description(&self) -> &str {
+        KEY_ERROR_MSG
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lang::Language;
+
+    use super::*;
+
+    const PLAINTEXT: &str = "The quick growth of the town surprised everyone who had lived there for many years. \
+    New houses appeared along the river, and the old market square was filled every morning with farmers selling \
+    bread, cheese and fresh vegetables to the families that had recently arrived. By the end of the summer the \
+    council decided to build a second bridge, because the first one could no longer carry all the carts that \
+    crossed it every day on their way to the market.";
+
+    #[test]
+    fn it_encrypts_with_a_keyword() {
+        let vigenere = Vigenere::new("LEMON").unwrap();
+        let result = vigenere.exec("Attack at dawn!", Mode::Encrypt);
+        assert_eq!("Lxfopv ef rnhr!", result);
+    }
+
+    #[test]
+    fn it_decrypts_with_a_keyword() {
+        let vigenere = Vigenere::new("lemon").unwrap();
+        let result = vigenere.exec("Lxfopv ef rnhr!", Mode::Decrypt);
+        assert_eq!("Attack at dawn!", result);
+    }
+
+    #[test]
+    fn it_continues_the_keyword_between_chunks() {
+        let vigenere = Vigenere::new("lemon").unwrap();
+        let mut position = 0;
+        let mut result = vigenere.exec_from("Attack a", Mode::Encrypt, &mut position);
+        result.push_str(&vigenere.exec_from("t dawn!", Mode::Encrypt, &mut position));
+        assert_eq!("Lxfopv ef rnhr!", result);
+        assert_eq!(12, position);
+    }
+
+    #[test]
+    fn it_returns_error_on_invalid_keys() {
+        assert_eq!(KeyError, Vigenere::new("").unwrap_err());
+        assert_eq!(KeyError, Vigenere::new("lemon 2").unwrap_err());
+    }
+
+    #[test]
+    fn errors_key_has_display() {
+        assert_eq!(KEY_ERROR_MSG, format!("{}", KeyError));
+    }
+
+    #[test]
+    fn it_estimates_the_key_length() {
+        let model = Model::builtin(Language::English);
+        let ciphertext = Vigenere::new("bridge").unwrap().exec(PLAINTEXT, Mode::Encrypt);
+
+        let key_lengths = key_lengths(&ciphertext, model, 20);
+
+        assert_eq!(6, key_lengths[0].length);
+        assert!(key_lengths[0].kasiski > 0.5);
+        let friedman = friedman(&ciphertext, model);
+        assert!(friedman > 3.0 && friedman < 9.0, "friedman estimate was {}", friedman);
+    }
+
+    #[test]
+    fn it_cracks_the_key() {
+        let model = Model::builtin(Language::English);
+        let ciphertext = Vigenere::new("bridge").unwrap().exec(PLAINTEXT, Mode::Encrypt);
+
+        let candidates = crack(&ciphertext, model, 20);
+
+        assert_eq!("bridge", candidates[0].key);
+        assert_eq!(PLAINTEXT, candidates[0].text);
+        assert!(candidates[0].confidence > 0.9);
+        let total: f64 = candidates.iter().map(|c| c.confidence).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn it_ranks_the_key_length_above_its_multiples() {
+        let model = Model::builtin(Language::English);
+        let ciphertext = Vigenere::new("sky").unwrap().exec(PLAINTEXT, Mode::Encrypt);
+
+        let key_lengths = key_lengths(&ciphertext, model, 20);
+
+        assert_eq!(3, key_lengths[0].length);
+        assert_eq!(6, key_lengths[1].length);
+    }
+
+    #[test]
+    fn it_has_no_candidates_without_letters() {
+        let model = Model::builtin(Language::English);
+
+        assert_eq!(Vec::<KeyCandidate>::new(), crack("1234 !!\n", model, 20));
+        assert_eq!(Vec::<KeyCandidate>::new(), crack("", model, 20));
+    }
+
+    #[test]
+    fn it_returns_repeated_keys_in_their_shortest_form() {
+        assert_eq!(&[1, 2], shortest_period(&[1, 2, 1, 2, 1, 2]));
+        assert_eq!(&[1, 2, 1], shortest_period(&[1, 2, 1]));
+    }
+}
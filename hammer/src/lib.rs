@@ -1,30 +1,951 @@
 use std::fmt::Display;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 
-use clap::{Arg, Command};
+use clap::error::ErrorKind as ArgsErrorKind;
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::{Client, Method, Url};
+use tokio::sync::{watch, Semaphore};
+use tokio::task::JoinSet;
 
-pub async fn hammer_with_args(args: Vec<String>) -> Result<Vec<Stats>, Error> {
-    let matches = Command::new("hammer").arg(Arg::new("url").short('u').required(true).long("url"));
+use output::RawLog;
+use scenario::Scenarios;
+use stages::{Stages, Target};
 
-    let matchs = matches.get_matches_from(args);
+pub use output::Output;
+pub use report::{Latency, Report, Schedule, StageSummary, Summary, Totals};
+pub use stages::Stage;
 
-    let url = matchs.get_one::<String>("url").unwrap();
+mod output;
+mod report;
+mod scenario;
+mod stages;
 
-    let response = reqwest::get(url).await.unwrap();
+/// Runs the load test until the requests or the duration are exhausted, or
+/// until Ctrl-C is pressed. An interrupted run still returns the report of
+/// the requests that were sent.
+pub async fn hammer_with_args(args: Vec<String>) -> Result<Report, Error> {
+    hammer_with_shutdown(args, async {
+        let _ = tokio::signal::ctrl_c().await;
+    })
+    .await
+}
+
+/// Like [hammer_with_args], but the run is stopped when the shutdown future
+/// completes. The workers don't start new requests from then on, but the
+/// ones in flight are awaited.
+pub async fn hammer_with_shutdown<F>(args: Vec<String>, shutdown: F) -> Result<Report, Error>
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let matchs = command()
+        .try_get_matches_from(args)
+        .map_err(|err| match err.kind() {
+            ArgsErrorKind::DisplayHelp | ArgsErrorKind::DisplayVersion => {
+                Error::Help(err.render().to_string())
+            }
+            _ => Error::InvalidArgs(err.render().to_string()),
+        })?;
+
+    let workload = match matchs.get_one::<String>("scenario") {
+        Some(path) => Workload::Scenarios(Arc::new(Scenarios::load(path)?)),
+        None => Workload::Request(Arc::new(Request::from(&matchs)?)),
+    };
+    let concurrency = matchs.get_one::<u64>("concurrency").copied();
+    let rate = matchs.get_one::<Rate>("rate").copied();
+    let stages = match matchs.get_many::<(Duration, Target)>("stage") {
+        Some(targets) => {
+            let targets: Vec<_> = targets.copied().collect();
+            let stages = Stages::new(&targets).map_err(Error::InvalidArgs)?;
+            if !stages.rate && concurrency.is_some() {
+                return Err(Error::InvalidArgs(
+                    "the concurrency is given by the users of the stages".to_string(),
+                ));
+            }
+            Some(Arc::new(stages))
+        }
+        None => None,
+    };
+    let duration = match &stages {
+        Some(stages) => Some(stages.duration()),
+        None => matchs.get_one::<Duration>("duration").copied(),
+    };
+    let requests = match (matchs.get_one::<u64>("requests"), duration) {
+        (Some(requests), _) => *requests,
+        (None, Some(_)) => u64::MAX,
+        // A single request on a schedule is not what a rate is meant for.
+        (None, None) if rate.is_some() => {
+            return Err(Error::InvalidArgs(
+                "the rate needs a duration (-d) or a number of requests (-n)".to_string(),
+            ))
+        }
+        (None, None) => 1,
+    };
+
+    let timeout = *matchs.get_one::<Duration>("timeout").unwrap();
+    let client = Client::builder()
+        .timeout(timeout)
+        .build()
+        .map_err(|err| Error::Tls(root_cause(&err)))?;
+    let (stop, stopped) = watch::channel(false);
+    let limits = Arc::new(Limits {
+        budget: AtomicU64::new(requests),
+        // Too far away to be represented, it is as if there was none.
+        deadline: duration.and_then(|duration| Instant::now().checked_add(duration)),
+        stopped,
+    });
+
+    let stopper = tokio::spawn(async move {
+        shutdown.await;
+        let _ = stop.send(true);
+    });
+
+    let (log, log_writer) = match matchs.get_one::<String>("raw-log") {
+        Some(path) => {
+            let (log, writer) = RawLog::create(path).await?;
+            (log, Some(writer))
+        }
+        None => (RawLog::default(), None),
+    };
+    let start = Instant::now();
+    let started = SystemTime::now();
+    let totals = Arc::new(Mutex::new(Totals::new(
+        started,
+        stages
+            .as_ref()
+            .map_or_else(Vec::new, |stages| stages.stages.clone()),
+    )));
+    let requester = Requester {
+        client,
+        workload,
+        log,
+        totals: totals.clone(),
+    };
+
+    let schedule = match (rate, &stages) {
+        (Some(rate), _) => {
+            let offset = |n| Some(rate.offset(n));
+            Some(open_loop(requester, limits, offset, rate.per_second(), concurrency).await?)
+        }
+        (None, Some(stages)) if stages.rate => {
+            let offset = |n| stages.offset(n);
+            Some(open_loop(requester, limits, offset, stages.mean_rate(), concurrency).await?)
+        }
+        (None, Some(stages)) => {
+            let workers = stages.max_users().min(requests);
+            closed_loop(requester, limits, workers, Some(stages.clone())).await?;
+            None
+        }
+        (None, None) => {
+            let concurrency = concurrency.unwrap_or(1);
+            closed_loop(requester, limits, concurrency.min(requests), None).await?;
+            None
+        }
+    };
+    let elapsed = start.elapsed();
+    stopper.abort();
+    if let Some(writer) = log_writer {
+        writer
+            .await
+            .map_err(|err| Error::General(err.to_string()))??;
+    }
+    // All the requests are done, so nothing else holds the totals.
+    let totals = Arc::into_inner(totals)
+        .and_then(|totals| totals.into_inner().ok())
+        .ok_or_else(|| Error::General("the requests could not be recorded".to_string()))?;
+    Ok(Report {
+        totals,
+        elapsed,
+        schedule,
+        output: *matchs.get_one::<Output>("output").unwrap(),
+    })
+}
+
+fn command() -> Command {
+    Command::new("hammer")
+        .arg(
+            Arg::new("url")
+                .short('u')
+                .long("url")
+                .required_unless_present("scenario"),
+        )
+        .arg(
+            Arg::new("scenario")
+                .short('s')
+                .long("scenario")
+                .help("YAML or TOML file with the scenarios to run instead of a single request")
+                .conflicts_with_all(["url", "method", "header", "body", "body-file", "json"]),
+        )
+        .arg(
+            Arg::new("method")
+                .short('X')
+                .long("method")
+                .help("HTTP method of the requests")
+                .default_value("GET")
+                .value_parser(parse_method),
+        )
+        .arg(
+            Arg::new("header")
+                .short('H')
+                .long("header")
+                .help("Header to send, like \"Name: value\". Can be repeated")
+                .action(ArgAction::Append)
+                .value_parser(parse_header),
+        )
+        .arg(
+            Arg::new("body")
+                .long("body")
+                .help("Body of the requests")
+                .conflicts_with_all(["body-file", "json"]),
+        )
+        .arg(
+            Arg::new("body-file")
+                .long("body-file")
+                .help("File with the body of the requests")
+                .conflicts_with("json"),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .help("JSON body of the requests, sent with its content type"),
+        )
+        .arg(
+            Arg::new("concurrency")
+                .short('c')
+                .long("concurrency")
+                .help("Number of workers sending requests at the same time. 1 by default, or the maximum requests in flight with a rate")
+                .value_parser(value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("requests")
+                .short('n')
+                .long("requests")
+                .help("Total number of requests, or scenario runs, shared by all the workers. Unlimited with a duration, 1 otherwise")
+                .value_parser(value_parser!(u64).range(1..)),
+        )
+        .arg(
+            Arg::new("duration")
+                .short('d')
+                .long("duration")
+                .help("Stops sending requests after this time, like 500ms, 30s, 5m or 1h")
+                .value_parser(parse_duration),
+        )
+        .arg(
+            Arg::new("timeout")
+                .short('t')
+                .long("timeout")
+                .help("Gives up on a request after this time, like 500ms or 30s")
+                .default_value("30s")
+                .value_parser(parse_duration),
+        )
+        .arg(
+            Arg::new("output")
+                .short('o')
+                .long("output")
+                .help("Format of the summary")
+                .default_value("text")
+                .value_parser(parse_output),
+        )
+        .arg(
+            Arg::new("raw-log")
+                .long("raw-log")
+                .help("CSV file where a line is written for every request, as they finish"),
+        )
+        .arg(
+            Arg::new("rate")
+                .short('r')
+                .long("rate")
+                .help("Sends requests on a fixed schedule, no matter how long the responses take, like 500/s or 100/m. Needs a duration or a number of requests")
+                .value_parser(parse_rate),
+        )
+        .arg(
+            Arg::new("stage")
+                .long("stage")
+                .help("Ramps the users, or the rate, from the previous stage to a target over a time, like 1m:200 or 30s:500/s. Can be repeated")
+                .action(ArgAction::Append)
+                .value_parser(parse_stage)
+                .conflicts_with_all(["rate", "duration"]),
+        )
+}
+
+// Every worker sends a request after the other, so the load depends on how
+// fast the server answers. With stages, the workers join and leave as the
+// users ramp up and down.
+async fn closed_loop(
+    requester: Requester,
+    limits: Arc<Limits>,
+    workers: u64,
+    stages: Option<Arc<Stages>>,
+) -> Result<(), Error> {
+    let start = Instant::now();
+    let workers: Vec<_> = (0..workers)
+        .map(|n| {
+            let users = stages.clone().map(|stages| Users { stages, start, n });
+            tokio::spawn(worker(requester.clone(), limits.clone(), users))
+        })
+        .collect();
+
+    for worker in workers {
+        worker
+            .await
+            .map_err(|err| Error::General(err.to_string()))?;
+    }
+    Ok(())
+}
+
+// Sends requests until any of the limits is reached.
+async fn worker(requester: Requester, limits: Arc<Limits>, users: Option<Users>) {
+    loop {
+        if let Some(users) = &users {
+            match users.next_turn() {
+                Some(turn) => {
+                    tokio::select! {
+                        _ = tokio::time::sleep_until(turn.into()) => {}
+                        _ = limits.wait_stop() => break,
+                    }
+                }
+                None => break,
+            }
+        }
+        if !limits.take_one(Instant::now()) {
+            break;
+        }
+        requester.run(Instant::now(), &limits).await;
+    }
+}
+
+/// The place of a worker among the users of the stages.
+struct Users {
+    stages: Arc<Stages>,
+    start: Instant,
+    n: u64,
+}
+
+impl Users {
+    /// When the worker has to send again: now if the stage has more than n
+    /// users, later once it ramps up to them, or never.
+    fn next_turn(&self) -> Option<Instant> {
+        self.stages
+            .next_above(self.n as f64, self.start.elapsed())
+            .map(|offset| self.start + offset)
+    }
+}
+
+// Requests are sent at the times of the rate, no matter how long the previous
+// ones take. Their latency is measured from when they should have been sent,
+// so a slow server or a generator that can't keep up is not hidden by sending
+// fewer requests (the coordinated omission problem). The offset tells when
+// the nth request is due, until there are no more.
+async fn open_loop<F>(
+    requester: Requester,
+    limits: Arc<Limits>,
+    offset: F,
+    rate: f64,
+    max_in_flight: Option<u64>,
+) -> Result<Schedule, Error>
+where
+    F: Fn(u64) -> Option<Duration>,
+{
+    let in_flight = Arc::new(Semaphore::new(
+        max_in_flight.map_or(Semaphore::MAX_PERMITS, |max| max as usize),
+    ));
+    let mut schedule = Schedule {
+        rate,
+        late: 0,
+        max_lag: Duration::ZERO,
+    };
+    let start = Instant::now();
+    let mut requests = JoinSet::new();
+    let mut next = offset(0);
+    let mut interval = None;
+    for n in 0.. {
+        let Some(current) = next else {
+            break;
+        };
+        let intended = start + current;
+        if !limits.take_one(intended) {
+            break;
+        }
+        next = offset(n + 1);
+        // The last request has no next one, so it keeps the interval before it.
+        interval = next.map(|next| next - current).or(interval);
+        tokio::select! {
+            _ = tokio::time::sleep_until(intended.into()) => {}
+            _ = limits.wait_stop() => break,
+        }
+        let permit = tokio::select! {
+            permit = in_flight.clone().acquire_owned() => permit.unwrap(),
+            _ = limits.wait_stop() => break,
+        };
+        let lag = intended.elapsed();
+        if interval.is_some_and(|interval| lag > interval.max(TIMER_RESOLUTION)) {
+            schedule.late += 1;
+        }
+        schedule.max_lag = schedule.max_lag.max(lag);
+
+        let requester = requester.clone();
+        let limits = limits.clone();
+        requests.spawn(async move {
+            requester.run(intended, &limits).await;
+            drop(permit);
+        });
+        // The finished requests are already recorded, so only the ones in
+        // flight are kept.
+        while let Some(request) = requests.try_join_next() {
+            request.map_err(|err| Error::General(err.to_string()))?;
+        }
+    }
 
-    let stats = vec![Stats {
-        code: response.status().as_u16(),
-    }];
-    Ok(stats)
+    while let Some(request) = requests.join_next().await {
+        request.map_err(|err| Error::General(err.to_string()))?;
+    }
+    Ok(schedule)
+}
+
+// Timers are not more precise than this, so being late by less is not
+// falling behind the rate.
+const TIMER_RESOLUTION: Duration = Duration::from_millis(1);
+
+/// Sends the requests of the run, logs them and adds them to the totals.
+#[derive(Clone)]
+struct Requester {
+    client: Client,
+    workload: Workload,
+    log: RawLog,
+    totals: Arc<Mutex<Totals>>,
 }
 
+/// What every worker sends, again and again.
+#[derive(Clone)]
+enum Workload {
+    Request(Arc<Request>),
+    Scenarios(Arc<Scenarios>),
+}
+
+impl Requester {
+    /// Sends the request of the run, or the steps of a scenario.
+    async fn run(&self, start: Instant, limits: &Limits) {
+        match &self.workload {
+            Workload::Request(request) => {
+                self.send(request, None, start, |_, _| Ok(())).await;
+            }
+            Workload::Scenarios(scenarios) => self.run_scenario(scenarios, start, limits).await,
+        }
+    }
+
+    // Only the first step is measured from the start, the next ones are sent
+    // right after the previous one and its think time. The scenario is left
+    // once a step fails, as the next ones usually depend on it.
+    async fn run_scenario(&self, scenarios: &Scenarios, start: Instant, limits: &Limits) {
+        let scenario = scenarios.pick();
+        let mut variables = scenarios.variables();
+        let mut start = start;
+        for (n, step) in scenario.steps.iter().enumerate() {
+            if n > 0 {
+                if limits.is_over(Instant::now()) {
+                    break;
+                }
+                start = Instant::now();
+            }
+            let name = format!("{}/{}", scenario.name, step.name);
+            let step_stats = match step.request(&variables) {
+                Ok(request) => {
+                    let extract = |headers: &HeaderMap, body: &[u8]| {
+                        step.extract(headers, body, &mut variables)
+                    };
+                    self.send(&request, Some(name), start, extract).await
+                }
+                Err(err) => {
+                    let step_stats = Stats::failed(err, Some(name), start);
+                    self.record(&step_stats);
+                    step_stats
+                }
+            };
+            if step_stats.status.is_err() || step_stats.extract.is_some() {
+                break;
+            }
+            if let Some(think) = step.think {
+                tokio::select! {
+                    _ = tokio::time::sleep(think) => {}
+                    _ = limits.wait_stop() => break,
+                }
+            }
+        }
+    }
+
+    // The latencies are measured from the start, which may be before the
+    // request is actually sent.
+    async fn send<F>(
+        &self,
+        request: &Request,
+        step: Option<String>,
+        start: Instant,
+        on_response: F,
+    ) -> Stats
+    where
+        F: FnOnce(&HeaderMap, &[u8]) -> Result<(), Error>,
+    {
+        let mut stats = self.exchange(request, start, on_response).await;
+        stats.step = step;
+        self.record(&stats);
+        stats
+    }
+
+    fn record(&self, stats: &Stats) {
+        self.log.record(stats);
+        self.totals.lock().unwrap().record(stats);
+    }
+
+    async fn exchange<F>(&self, request: &Request, start: Instant, on_response: F) -> Stats
+    where
+        F: FnOnce(&HeaderMap, &[u8]) -> Result<(), Error>,
+    {
+        let failed = |err: reqwest::Error| Stats::failed(Error::from(err), None, start);
+        let mut response = match request.build(&self.client).send().await {
+            Ok(response) => response,
+            Err(err) => return failed(err),
+        };
+        let first_byte = start.elapsed();
+        let code = response.status().as_u16();
+        let headers = std::mem::take(response.headers_mut());
+        let body = match response.bytes().await {
+            Ok(body) => body,
+            Err(err) => return failed(err),
+        };
+        let latency = start.elapsed();
+        Stats {
+            status: Ok(code),
+            sent_at: SystemTime::now() - latency,
+            first_byte,
+            latency,
+            bytes: body.len() as u64,
+            step: None,
+            extract: on_response(&headers, &body).err(),
+        }
+    }
+}
+
+/// What every request of the run sends.
 #[derive(Debug)]
+struct Request {
+    method: Method,
+    url: String,
+    headers: HeaderMap,
+    body: Option<Vec<u8>>,
+}
+
+impl Request {
+    fn from(matches: &ArgMatches) -> Result<Request, Error> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in matches
+            .get_many::<(HeaderName, HeaderValue)>("header")
+            .unwrap_or_default()
+        {
+            headers.append(name.clone(), value.clone());
+        }
+        let mut body = matches
+            .get_one::<String>("body")
+            .map(|body| body.as_bytes().to_vec());
+        if let Some(path) = matches.get_one::<String>("body-file") {
+            let contents = std::fs::read(path)
+                .map_err(|err| Error::Io(format!("cannot read {}: {}", path, err)))?;
+            body = Some(contents);
+        }
+        if let Some(json) = matches.get_one::<String>("json") {
+            // Unless another content type is given.
+            headers
+                .entry(CONTENT_TYPE)
+                .or_insert(HeaderValue::from_static("application/json"));
+            body = Some(json.as_bytes().to_vec());
+        }
+        Request::new(
+            matches.get_one::<Method>("method").unwrap().clone(),
+            matches.get_one::<String>("url").unwrap().clone(),
+            headers,
+            body,
+        )
+    }
+
+    fn new(
+        method: Method,
+        url: String,
+        headers: HeaderMap,
+        body: Option<Vec<u8>>,
+    ) -> Result<Request, Error> {
+        match Url::parse(&url) {
+            Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => {}
+            Ok(_) => {
+                return Err(Error::InvalidUrl(format!(
+                    "{}, only http and https are supported",
+                    url
+                )))
+            }
+            Err(err) => return Err(Error::InvalidUrl(format!("{}, {}", url, err))),
+        }
+        Ok(Request {
+            method,
+            url,
+            headers,
+            body,
+        })
+    }
+
+    fn build(&self, client: &Client) -> reqwest::RequestBuilder {
+        let builder = client
+            .request(self.method.clone(), &self.url)
+            .headers(self.headers.clone());
+        match &self.body {
+            Some(body) => builder.body(body.clone()),
+            None => builder,
+        }
+    }
+}
+
+fn parse_method(value: &str) -> Result<Method, String> {
+    Method::from_bytes(value.to_uppercase().as_bytes())
+        .map_err(|_| format!("invalid method {}", value))
+}
+
+/// Parses headers like "Name: value".
+fn parse_header(value: &str) -> Result<(HeaderName, HeaderValue), String> {
+    let (name, header_value) = value
+        .split_once(':')
+        .ok_or_else(|| format!("invalid header {}, expected \"Name: value\"", value))?;
+    let name = HeaderName::from_bytes(name.trim().as_bytes())
+        .map_err(|_| format!("invalid header name in {}", value))?;
+    let header_value = HeaderValue::from_str(header_value.trim())
+        .map_err(|_| format!("invalid header value in {}", value))?;
+    Ok((name, header_value))
+}
+
+/// What stops the workers: the requests budget, the deadline and the
+/// shutdown signal, whatever comes first.
+struct Limits {
+    budget: AtomicU64,
+    deadline: Option<Instant>,
+    stopped: watch::Receiver<bool>,
+}
+
+impl Limits {
+    /// Takes a request from the budget, if it can be sent at the given time.
+    fn take_one(&self, at: Instant) -> bool {
+        if self.is_over(at) {
+            return false;
+        }
+        self.budget
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+                left.checked_sub(1)
+            })
+            .is_ok()
+    }
+
+    /// Whether the run is stopped or past its deadline at the given time.
+    fn is_over(&self, at: Instant) -> bool {
+        *self.stopped.borrow() || matches!(self.deadline, Some(deadline) if at >= deadline)
+    }
+
+    /// Completes once the run is stopped.
+    async fn wait_stop(&self) {
+        let mut stopped = self.stopped.clone();
+        if stopped.wait_for(|stopped| *stopped).await.is_err() {
+            // The run finished without being stopped.
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+/// A number of requests per unit of time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Rate {
+    requests: u64,
+    per: Duration,
+}
+
+impl Rate {
+    /// When the nth request has to be sent, since the start.
+    fn offset(&self, n: u64) -> Duration {
+        let nanos = self.per.as_nanos() * n as u128 / self.requests as u128;
+        Duration::from_nanos(nanos as u64)
+    }
+
+    fn per_second(&self) -> f64 {
+        self.requests as f64 / self.per.as_secs_f64()
+    }
+}
+
+fn parse_output(value: &str) -> Result<Output, String> {
+    match value {
+        "text" => Ok(Output::Text),
+        "json" => Ok(Output::Json),
+        "csv" => Ok(Output::Csv),
+        _ => Err(format!(
+            "invalid output {}, expected text, json or csv",
+            value
+        )),
+    }
+}
+
+/// Parses rates like 500/s, 100/m or 1000/h. Without a unit the rate is per
+/// second.
+fn parse_rate(value: &str) -> Result<Rate, String> {
+    parse_any_rate(value).and_then(|rate| match rate.requests {
+        0 => Err(format!(
+            "invalid rate {}, expected a number of requests like 500/s",
+            value
+        )),
+        _ => Ok(rate),
+    })
+}
+
+// Like parse_rate, but stages can ramp down to no requests.
+fn parse_any_rate(value: &str) -> Result<Rate, String> {
+    let (requests, unit) = value.split_once('/').unwrap_or((value, "s"));
+    let requests: u64 = requests.parse().map_err(|_| {
+        format!(
+            "invalid rate {}, expected a number of requests like 500/s",
+            value
+        )
+    })?;
+    let per = match unit {
+        "s" => Duration::from_secs(1),
+        "m" => Duration::from_secs(60),
+        "h" => Duration::from_secs(60 * 60),
+        _ => {
+            return Err(format!(
+                "invalid rate unit in {}, expected s, m or h",
+                value
+            ))
+        }
+    };
+    Ok(Rate { requests, per })
+}
+
+/// Parses stages like 1m:200 users, or like 30s:500/s with a rate.
+fn parse_stage(value: &str) -> Result<(Duration, Target), String> {
+    let (duration, target) = value.split_once(':').ok_or_else(|| {
+        format!(
+            "invalid stage {}, expected a duration and a target like 1m:200 or 1m:500/s",
+            value
+        )
+    })?;
+    let duration = parse_duration(duration)?;
+    if duration.is_zero() {
+        return Err(format!("invalid stage {}, the duration can't be 0", value));
+    }
+    let target = if target.contains('/') {
+        Target::Rate(parse_any_rate(target)?)
+    } else {
+        let users = target
+            .parse()
+            .map_err(|_| format!("invalid users in stage {}", value))?;
+        Target::Users(users)
+    };
+    Ok((duration, target))
+}
+
+/// Parses durations like 500ms, 30s, 5m or 1h.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let invalid = || {
+        format!(
+            "invalid duration {}, expected a number followed by ms, s, m or h",
+            value
+        )
+    };
+    let amount: u64 = amount.parse().map_err(|_| invalid())?;
+    let seconds = |per_unit: u64| {
+        amount
+            .checked_mul(per_unit)
+            .map(Duration::from_secs)
+            .ok_or_else(invalid)
+    };
+    match unit {
+        "ms" => Ok(Duration::from_millis(amount)),
+        "s" => seconds(1),
+        "m" => seconds(60),
+        "h" => seconds(60 * 60),
+        _ => Err(format!(
+            "invalid duration unit in {}, expected ms, s, m or h",
+            value
+        )),
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Stats {
-    code: u16,
+    /// The status code of the response, or why there was none.
+    pub status: Result<u16, Error>,
+    /// When the request was sent, or should have been with a rate.
+    pub sent_at: SystemTime,
+    /// Until the response headers were received, or the request failed.
+    pub first_byte: Duration,
+    /// Until the whole response body was read, or the request failed.
+    pub latency: Duration,
+    /// Size of the response body.
+    pub bytes: u64,
+    /// The scenario and step of the request, when running scenarios.
+    pub step: Option<String>,
+    /// Why the variables of the step couldn't be extracted from the
+    /// response, which ends the scenario.
+    pub extract: Option<Error>,
 }
 
-#[derive(Debug)]
+impl Stats {
+    fn failed(err: Error, step: Option<String>, start: Instant) -> Stats {
+        let latency = start.elapsed();
+        Stats {
+            status: Err(err),
+            sent_at: SystemTime::now() - latency,
+            first_byte: latency,
+            latency,
+            bytes: 0,
+            step,
+            extract: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Error {
+    /// Not a failure, the help or the version was asked for. It holds the
+    /// text to show.
+    Help(String),
     General(String),
+    InvalidArgs(String),
+    InvalidUrl(String),
+    Connect(String),
+    Dns(String),
+    Reset(String),
+    Timeout,
+    Tls(String),
+    Io(String),
+    Scenario(String),
+    Extract(String),
+}
+
+impl Error {
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Help(_) => ErrorKind::Help,
+            Error::General(_) => ErrorKind::General,
+            Error::InvalidArgs(_) => ErrorKind::InvalidArgs,
+            Error::InvalidUrl(_) => ErrorKind::InvalidUrl,
+            Error::Connect(_) => ErrorKind::Connect,
+            Error::Dns(_) => ErrorKind::Dns,
+            Error::Reset(_) => ErrorKind::Reset,
+            Error::Timeout => ErrorKind::Timeout,
+            Error::Tls(_) => ErrorKind::Tls,
+            Error::Io(_) => ErrorKind::Io,
+            Error::Scenario(_) => ErrorKind::Scenario,
+            Error::Extract(_) => ErrorKind::Extract,
+        }
+    }
+}
+
+/// The kinds of [Error], without their details, to group them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ErrorKind {
+    Help,
+    General,
+    InvalidArgs,
+    InvalidUrl,
+    Connect,
+    Dns,
+    Reset,
+    Timeout,
+    Tls,
+    Io,
+    Scenario,
+    Extract,
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ErrorKind::Help => "help",
+            ErrorKind::General => "general",
+            ErrorKind::InvalidArgs => "invalid args",
+            ErrorKind::InvalidUrl => "invalid url",
+            ErrorKind::Connect => "connect",
+            ErrorKind::Dns => "dns",
+            ErrorKind::Reset => "reset",
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::Tls => "tls",
+            ErrorKind::Io => "io",
+            ErrorKind::Scenario => "scenario",
+            ErrorKind::Extract => "extract",
+        })
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        let cause = root_cause(&err);
+        if err.is_timeout() {
+            Error::Timeout
+        } else if err.is_builder() {
+            Error::InvalidUrl(cause)
+        } else if chain_contains(&err, &["ssl", "tls", "certificate", "handshake"]) {
+            Error::Tls(cause)
+        } else if chain_contains(&err, &["dns error"]) {
+            Error::Dns(cause)
+        } else if is_reset(&err) {
+            Error::Reset(cause)
+        } else if err.is_connect() {
+            Error::Connect(cause)
+        } else {
+            Error::Io(cause)
+        }
+    }
+}
+
+// The reqwest errors only say what the request was doing, the reason is at
+// the end of the chain.
+fn root_cause(err: &dyn std::error::Error) -> String {
+    let mut cause = err;
+    while let Some(source) = cause.source() {
+        cause = source;
+    }
+    cause.to_string()
+}
+
+// The errors of the TLS backend and the resolver are not exposed, so they
+// are told apart by their messages.
+fn chain_contains(err: &dyn std::error::Error, words: &[&str]) -> bool {
+    let mut cause = Some(err);
+    while let Some(err) = cause {
+        let message = err.to_string().to_lowercase();
+        if words.iter().any(|word| message.contains(word)) {
+            return true;
+        }
+        cause = err.source();
+    }
+    false
+}
+
+// The server closed the connection while the request was going on.
+fn is_reset(err: &(dyn std::error::Error + 'static)) -> bool {
+    let mut cause = Some(err);
+    while let Some(err) = cause {
+        if let Some(err) = err.downcast_ref::<std::io::Error>() {
+            if matches!(
+                err.kind(),
+                std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::BrokenPipe
+            ) {
+                return true;
+            }
+        }
+        cause = err.source();
+    }
+    chain_contains(err, &["connection closed before message completed"])
 }
 
 impl std::error::Error for Error {}
@@ -32,14 +953,27 @@ impl std::error::Error for Error {}
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            Error::Help(text) => f.write_str(text.trim_end()),
             Error::General(message) => f.write_str(message),
+            Error::InvalidArgs(message) => f.write_str(message.trim_end()),
+            Error::InvalidUrl(message) => write!(f, "invalid url {}", message),
+            Error::Connect(message) => write!(f, "cannot connect: {}", message),
+            Error::Dns(message) => write!(f, "cannot resolve host: {}", message),
+            Error::Reset(message) => write!(f, "connection reset: {}", message),
+            Error::Timeout => f.write_str("request timed out"),
+            Error::Tls(message) => write!(f, "tls error: {}", message),
+            Error::Io(message) => write!(f, "i/o error: {}", message),
+            Error::Scenario(message) => write!(f, "invalid scenario {}", message),
+            Error::Extract(message) => write!(f, "cannot extract {}", message),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
 
+    use httpmock::Method::{POST, PUT};
     use httpmock::MockServer;
 
     use super::*;
@@ -63,8 +997,8 @@ mod tests {
         args.push(server_url);
 
         match hammer_with_args(args).await {
-            Ok(stats) => {
-                assert_eq!(stats.first().unwrap().code, 200)
+            Ok(report) => {
+                assert_eq!(report.summary().codes.get(&200), Some(&1))
             }
 
             Err(err) => {
@@ -72,4 +1006,984 @@ mod tests {
             }
         }
     }
+
+    fn args(url: String, extra: &[&str]) -> Vec<String> {
+        let mut args = vec!["hammer".to_string(), "-u".to_string(), url];
+        args.extend(extra.iter().map(|arg| arg.to_string()));
+        args
+    }
+
+    #[tokio::test]
+    async fn it_shares_the_requests_between_workers() {
+        let server = MockServer::start_async().await;
+        let mock = server
+            .mock_async(|when, then| {
+                when.path("/hammer");
+                then.status(200);
+            })
+            .await;
+
+        let report = hammer_with_args(args(server.url("/hammer"), &["-c", "4", "-n", "50"]))
+            .await
+            .unwrap();
+
+        mock.assert_hits_async(50).await;
+        assert_eq!(BTreeMap::from([(200, 50)]), report.summary().codes);
+    }
+
+    #[tokio::test]
+    async fn it_does_not_exceed_the_requests_with_more_workers() {
+        let server = MockServer::start_async().await;
+        let mock = server
+            .mock_async(|when, then| {
+                when.path("/hammer");
+                then.status(204);
+            })
+            .await;
+
+        let report = hammer_with_args(args(
+            server.url("/hammer"),
+            &["--concurrency", "10", "--requests", "3"],
+        ))
+        .await
+        .unwrap();
+
+        mock.assert_hits_async(3).await;
+        assert_eq!(3, report.summary().requests);
+    }
+
+    #[tokio::test]
+    async fn it_stops_at_the_deadline() {
+        let server = MockServer::start_async().await;
+        server
+            .mock_async(|when, then| {
+                when.path("/hammer");
+                then.status(200).delay(Duration::from_millis(10));
+            })
+            .await;
+        let start = Instant::now();
+
+        let report = hammer_with_args(args(server.url("/hammer"), &["-c", "2", "-d", "300ms"]))
+            .await
+            .unwrap();
+
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert!(report.summary().requests > 2);
+    }
+
+    #[tokio::test]
+    async fn it_stops_at_the_requests_before_the_deadline() {
+        let server = MockServer::start_async().await;
+        let mock = server
+            .mock_async(|when, then| {
+                when.path("/hammer");
+                then.status(200);
+            })
+            .await;
+
+        let report = hammer_with_args(args(server.url("/hammer"), &["-n", "5", "-d", "1h"]))
+            .await
+            .unwrap();
+
+        mock.assert_hits_async(5).await;
+        assert_eq!(5, report.summary().requests);
+    }
+
+    #[tokio::test]
+    async fn it_returns_the_partial_stats_on_shutdown() {
+        let server = MockServer::start_async().await;
+        let mock = server
+            .mock_async(|when, then| {
+                when.path("/hammer");
+                then.status(200).delay(Duration::from_millis(50));
+            })
+            .await;
+        let shutdown = tokio::time::sleep(Duration::from_millis(300));
+
+        let report = hammer_with_shutdown(
+            args(server.url("/hammer"), &["-c", "3", "-n", "1000000"]),
+            shutdown,
+        )
+        .await
+        .unwrap();
+
+        // The requests in flight when stopping are awaited and counted.
+        let requests = report.summary().requests;
+        mock.assert_hits_async(requests).await;
+        assert!(requests >= 3);
+        assert!(requests < 1000000);
+    }
+
+    #[test]
+    fn it_parses_durations() {
+        assert_eq!(Ok(Duration::from_millis(500)), parse_duration("500ms"));
+        assert_eq!(Ok(Duration::from_secs(30)), parse_duration("30s"));
+        assert_eq!(Ok(Duration::from_secs(300)), parse_duration("5m"));
+        assert_eq!(Ok(Duration::from_secs(7200)), parse_duration("2h"));
+        assert!(parse_duration("30").is_err());
+        assert!(parse_duration("s").is_err());
+        assert!(parse_duration("10d").is_err());
+        assert_eq!(
+            Err(
+                "invalid duration 99999999999999999h, expected a number followed by ms, s, m or h"
+                    .to_string()
+            ),
+            parse_duration("99999999999999999h")
+        );
+    }
+
+    #[tokio::test]
+    async fn it_sends_at_the_rate() {
+        let server = MockServer::start_async().await;
+        let mock = server
+            .mock_async(|when, then| {
+                when.path("/hammer");
+                then.status(200);
+            })
+            .await;
+        let start = Instant::now();
+
+        let report = hammer_with_args(args(
+            server.url("/hammer"),
+            &["--rate", "100/s", "-d", "500ms"],
+        ))
+        .await
+        .unwrap();
+
+        // Scheduled every 10ms, from 0ms to 490ms.
+        mock.assert_hits_async(50).await;
+        assert_eq!(50, report.summary().requests);
+        assert!(start.elapsed() >= Duration::from_millis(490));
+        assert_eq!(100.0, report.schedule.unwrap().rate);
+    }
+
+    #[tokio::test]
+    async fn it_measures_the_latency_from_the_schedule() {
+        let server = MockServer::start_async().await;
+        server
+            .mock_async(|when, then| {
+                when.path("/hammer");
+                then.status(200).delay(Duration::from_millis(100));
+            })
+            .await;
+
+        // Only one request in flight, so each one waits for the previous.
+        let report = hammer_with_args(args(
+            server.url("/hammer"),
+            &["-r", "100/s", "-c", "1", "-n", "5"],
+        ))
+        .await
+        .unwrap();
+
+        let schedule = report.schedule.as_ref().unwrap();
+        assert!(schedule.late >= 4);
+        assert!(schedule.max_lag >= Duration::from_millis(300));
+        assert!(report.summary().latency.max >= Duration::from_millis(400));
+    }
+
+    #[tokio::test]
+    async fn it_stops_waiting_for_the_schedule_on_shutdown() {
+        let server = MockServer::start_async().await;
+        let mock = server
+            .mock_async(|when, then| {
+                when.path("/hammer");
+                then.status(200);
+            })
+            .await;
+        // Long enough for the first request to be answered even when the
+        // tests are busy.
+        let shutdown = tokio::time::sleep(Duration::from_secs(1));
+        let start = Instant::now();
+
+        let report = hammer_with_shutdown(
+            args(server.url("/hammer"), &["-r", "1/m", "-n", "3"]),
+            shutdown,
+        )
+        .await
+        .unwrap();
+
+        assert!(start.elapsed() < Duration::from_secs(5));
+        mock.assert_hits_async(1).await;
+        assert_eq!(1, report.summary().requests);
+    }
+
+    #[tokio::test]
+    async fn it_keeps_the_interval_for_the_last_request() {
+        let server = MockServer::start_async().await;
+        server
+            .mock_async(|when, then| {
+                when.path("/hammer");
+                then.status(200).delay(Duration::from_millis(150));
+            })
+            .await;
+        let request = Request::new(Method::GET, server.url("/hammer"), HeaderMap::new(), None);
+        let requester = Requester {
+            client: Client::new(),
+            workload: Workload::Request(Arc::new(request.unwrap())),
+            log: RawLog::default(),
+            totals: Arc::new(Mutex::new(Totals::new(SystemTime::now(), Vec::new()))),
+        };
+        let (_stop, stopped) = watch::channel(false);
+        let limits = Arc::new(Limits {
+            budget: AtomicU64::new(u64::MAX),
+            deadline: None,
+            stopped,
+        });
+
+        // The second and last request waits 50ms for the first one to be
+        // answered, less than the 100ms between them.
+        let offset = |n| (n < 2).then(|| Duration::from_millis(100 * n));
+        let schedule = open_loop(requester, limits, offset, 10.0, Some(1))
+            .await
+            .unwrap();
+
+        assert_eq!(0, schedule.late);
+        assert!(schedule.max_lag >= Duration::from_millis(40));
+    }
+
+    #[test]
+    fn it_parses_rates() {
+        let rate = |requests, secs| Rate {
+            requests,
+            per: Duration::from_secs(secs),
+        };
+        assert_eq!(Ok(rate(500, 1)), parse_rate("500/s"));
+        assert_eq!(Ok(rate(100, 60)), parse_rate("100/m"));
+        assert_eq!(Ok(rate(1000, 3600)), parse_rate("1000/h"));
+        assert_eq!(Ok(rate(20, 1)), parse_rate("20"));
+        assert!(parse_rate("0/s").is_err());
+        assert!(parse_rate("fast").is_err());
+        assert!(parse_rate("10/d").is_err());
+    }
+
+    #[test]
+    fn it_schedules_the_requests_of_a_rate() {
+        let rate = Rate {
+            requests: 3,
+            per: Duration::from_secs(1),
+        };
+
+        assert_eq!(Duration::ZERO, rate.offset(0));
+        assert_eq!(Duration::from_nanos(333_333_333), rate.offset(1));
+        assert_eq!(Duration::from_secs(1), rate.offset(3));
+        assert_eq!(Duration::from_nanos(3_333_333_333), rate.offset(10));
+        assert_eq!(3.0, rate.per_second());
+    }
+
+    #[tokio::test]
+    async fn it_sends_the_method_headers_and_body() {
+        let server = MockServer::start_async().await;
+        let mock = server
+            .mock_async(|when, then| {
+                when.method(PUT)
+                    .path("/hammer")
+                    .header("x-token", "secret")
+                    .header("accept", "text/plain")
+                    .body("hello hammer");
+                then.status(201);
+            })
+            .await;
+
+        let report = hammer_with_args(args(
+            server.url("/hammer"),
+            &[
+                "-X",
+                "put",
+                "-H",
+                "X-Token: secret",
+                "--header",
+                "Accept:text/plain",
+                "--body",
+                "hello hammer",
+                "-n",
+                "3",
+            ],
+        ))
+        .await
+        .unwrap();
+
+        mock.assert_hits_async(3).await;
+        assert_eq!(BTreeMap::from([(201, 3)]), report.summary().codes);
+    }
+
+    #[tokio::test]
+    async fn it_sends_the_body_of_a_file() {
+        let server = MockServer::start_async().await;
+        let mock = server
+            .mock_async(|when, then| {
+                when.method(POST).path("/hammer").body("from a file\n");
+                then.status(200);
+            })
+            .await;
+        let path = std::env::temp_dir().join(format!("hammer-body-{}.txt", std::process::id()));
+        std::fs::write(&path, "from a file\n").unwrap();
+
+        hammer_with_args(args(
+            server.url("/hammer"),
+            &["-X", "POST", "--body-file", path.to_str().unwrap()],
+        ))
+        .await
+        .unwrap();
+
+        std::fs::remove_file(path).unwrap();
+        mock.assert_hits_async(1).await;
+    }
+
+    #[tokio::test]
+    async fn it_sends_json() {
+        let server = MockServer::start_async().await;
+        let mock = server
+            .mock_async(|when, then| {
+                when.method(POST)
+                    .path("/hammer")
+                    .header("content-type", "application/json")
+                    .body(r#"{"name":"hammer"}"#);
+                then.status(200);
+            })
+            .await;
+
+        hammer_with_args(args(
+            server.url("/hammer"),
+            &["-X", "POST", "--json", r#"{"name":"hammer"}"#],
+        ))
+        .await
+        .unwrap();
+
+        mock.assert_hits_async(1).await;
+    }
+
+    #[test]
+    fn it_lets_headers_override_the_json_content_type() {
+        let matches = command().get_matches_from(args(
+            "http://localhost".to_string(),
+            &[
+                "--json",
+                "{}",
+                "-H",
+                "Content-Type: application/vnd.api+json",
+            ],
+        ));
+
+        let request = Request::from(&matches).unwrap();
+
+        assert_eq!(1, request.headers.get_all(CONTENT_TYPE).iter().count());
+        assert_eq!("application/vnd.api+json", request.headers[CONTENT_TYPE]);
+    }
+
+    #[test]
+    fn it_parses_headers() {
+        let (name, value) = parse_header("X-Request-Id:  abc 123 ").unwrap();
+        assert_eq!("x-request-id", name.as_str());
+        assert_eq!("abc 123", value);
+        assert!(parse_header("no colon").is_err());
+        assert!(parse_header("bad name: value").is_err());
+    }
+
+    #[test]
+    fn it_parses_methods() {
+        assert_eq!(Ok(Method::DELETE), parse_method("delete"));
+        assert_eq!(Ok(Method::PATCH), parse_method("PATCH"));
+        assert!(parse_method("GET POST").is_err());
+    }
+
+    #[tokio::test]
+    async fn it_records_refused_connections() {
+        let report = hammer_with_args(args("http://127.0.0.1:1/hammer".to_string(), &["-n", "2"]))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            BTreeMap::from([(ErrorKind::Connect, 2)]),
+            report.summary().error_kinds
+        );
+    }
+
+    #[tokio::test]
+    async fn it_records_dns_errors() {
+        let report = hammer_with_args(args("http://does-not-exist.invalid/".to_string(), &[]))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            BTreeMap::from([(ErrorKind::Dns, 1)]),
+            report.summary().error_kinds
+        );
+    }
+
+    #[tokio::test]
+    async fn it_records_closed_connections() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hammer", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            // Reads the request and closes without a response.
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut buffer = [0; 1024];
+            let _ = tokio::io::AsyncReadExt::read(&mut stream, &mut buffer).await;
+        });
+
+        let report = hammer_with_args(args(url, &[])).await.unwrap();
+
+        assert_eq!(
+            BTreeMap::from([(ErrorKind::Reset, 1)]),
+            report.summary().error_kinds
+        );
+    }
+
+    #[tokio::test]
+    async fn it_records_timeouts() {
+        let server = MockServer::start_async().await;
+        server
+            .mock_async(|when, then| {
+                when.path("/hammer");
+                then.status(200).delay(Duration::from_millis(500));
+            })
+            .await;
+
+        let report = hammer_with_args(args(server.url("/hammer"), &["--timeout", "100ms"]))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            BTreeMap::from([(ErrorKind::Timeout, 1)]),
+            report.summary().error_kinds
+        );
+    }
+
+    #[tokio::test]
+    async fn it_records_tls_errors() {
+        let server = MockServer::start_async().await;
+        let url = server.url("/hammer").replace("http://", "https://");
+
+        let report = hammer_with_args(args(url, &[])).await.unwrap();
+
+        assert_eq!(
+            BTreeMap::from([(ErrorKind::Tls, 1)]),
+            report.summary().error_kinds
+        );
+    }
+
+    #[tokio::test]
+    async fn it_fails_on_invalid_args() {
+        let err = hammer_with_args(vec!["hammer".to_string()])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidArgs(_)));
+
+        let err = hammer_with_args(args("http://localhost".to_string(), &["-c", "0"]))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::InvalidArgs(_)));
+    }
+
+    #[tokio::test]
+    async fn it_returns_the_help() {
+        for flag in ["--help", "-h"] {
+            let err = hammer_with_args(vec!["hammer".to_string(), flag.to_string()])
+                .await
+                .unwrap_err();
+            assert!(matches!(&err, Error::Help(text) if text.contains("Usage: hammer")));
+        }
+    }
+
+    #[tokio::test]
+    async fn it_fails_on_invalid_urls() {
+        let err = hammer_with_args(args("localhost:8080".to_string(), &[]))
+            .await
+            .unwrap_err();
+        assert_eq!(
+            Error::InvalidUrl("localhost:8080, only http and https are supported".to_string()),
+            err
+        );
+
+        let err = hammer_with_args(args("http://".to_string(), &[]))
+            .await
+            .unwrap_err();
+        assert_eq!(Error::InvalidUrl("http://, empty host".to_string()), err);
+    }
+
+    #[tokio::test]
+    async fn it_fails_on_unreadable_body_files() {
+        let err = hammer_with_args(args(
+            "http://localhost".to_string(),
+            &["--body-file", "/does/not/exist"],
+        ))
+        .await
+        .unwrap_err();
+
+        assert_eq!(
+            Error::Io(
+                "cannot read /does/not/exist: No such file or directory (os error 2)".to_string()
+            ),
+            err
+        );
+    }
+
+    #[test]
+    fn errors_have_display() {
+        assert_eq!(
+            "invalid url x, empty host",
+            format!("{}", Error::InvalidUrl("x, empty host".to_string()))
+        );
+        assert_eq!(
+            "cannot connect: refused",
+            format!("{}", Error::Connect("refused".to_string()))
+        );
+        assert_eq!("request timed out", format!("{}", Error::Timeout));
+        assert_eq!(
+            "tls error: bad cert",
+            format!("{}", Error::Tls("bad cert".to_string()))
+        );
+        assert_eq!(
+            "i/o error: reset",
+            format!("{}", Error::Io("reset".to_string()))
+        );
+        assert_eq!(
+            "cannot resolve host: unknown",
+            format!("{}", Error::Dns("unknown".to_string()))
+        );
+        assert_eq!(
+            "connection reset: by peer",
+            format!("{}", Error::Reset("by peer".to_string()))
+        );
+    }
+
+    #[test]
+    fn errors_have_kinds() {
+        assert_eq!(
+            ErrorKind::Connect,
+            Error::Connect("refused".to_string()).kind()
+        );
+        assert_eq!(ErrorKind::Timeout, Error::Timeout.kind());
+        assert_eq!(
+            "dns",
+            format!("{}", Error::Dns("unknown".to_string()).kind())
+        );
+    }
+
+    #[tokio::test]
+    async fn it_writes_the_raw_log_and_picks_the_output() {
+        let server = MockServer::start_async().await;
+        server
+            .mock_async(|when, then| {
+                when.path("/hammer");
+                then.status(200).body("hammered");
+            })
+            .await;
+        let path = std::env::temp_dir().join(format!("hammer-log-{}.csv", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+
+        let report = hammer_with_args(args(
+            server.url("/hammer"),
+            &["-n", "4", "-c", "2", "--raw-log", &path, "--output", "json"],
+        ))
+        .await
+        .unwrap();
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<_> = log.lines().collect();
+        assert_eq!(5, lines.len());
+        assert_eq!(
+            "timestamp,latency_us,first_byte_us,status,bytes,error,step",
+            lines[0]
+        );
+        assert!(lines[1..].iter().all(|line| line.ends_with(",200,8,,")));
+        assert_eq!(Output::Json, report.output);
+        assert_eq!(4, report.summary().requests);
+    }
+
+    fn scenario_file(name: &str, contents: &str) -> String {
+        let path = temp_path(name);
+        std::fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("hammer-{}-{}", std::process::id(), name));
+        path.to_str().unwrap().to_string()
+    }
+
+    // The lines of a raw log, without its header.
+    fn read_raw_log(path: &str) -> Vec<String> {
+        let log = std::fs::read_to_string(path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        log.lines().skip(1).map(str::to_string).collect()
+    }
+
+    #[tokio::test]
+    async fn it_runs_scenarios_passing_the_extracted_variables() {
+        let server = MockServer::start_async().await;
+        let login = server
+            .mock_async(|when, then| {
+                when.method(POST)
+                    .path("/login")
+                    .header("content-type", "application/json")
+                    .body(r#"{"user":"ana"}"#);
+                then.status(200)
+                    .header("x-session", "s-1")
+                    .body(r#"{"data": {"token": "t-1"}}"#);
+            })
+            .await;
+        let item = server
+            .mock_async(|when, then| {
+                when.path("/items/s-1")
+                    .header("authorization", "Bearer t-1");
+                then.status(200);
+            })
+            .await;
+        let path = scenario_file(
+            "login.yaml",
+            &format!(
+                r#"
+variables:
+  base: {}
+scenarios:
+  - name: shop
+    steps:
+      - name: login
+        method: POST
+        url: "{{{{base}}}}/login"
+        json: {{ user: ana }}
+        extract:
+          token: {{ json: data.token }}
+          session: {{ header: X-Session }}
+        think: 100ms
+      - name: item
+        url: "{{{{base}}}}/items/{{{{session}}}}"
+        headers:
+          Authorization: "Bearer {{{{token}}}}"
+"#,
+                server.base_url()
+            ),
+        );
+
+        let log = temp_path("login.csv");
+
+        let report = hammer_with_args(vec![
+            "hammer".to_string(),
+            "-s".to_string(),
+            path.clone(),
+            "-n".to_string(),
+            "2".to_string(),
+            "--raw-log".to_string(),
+            log.clone(),
+        ])
+        .await
+        .unwrap();
+
+        std::fs::remove_file(path).unwrap();
+        login.assert_hits_async(2).await;
+        item.assert_hits_async(2).await;
+        let lines: Vec<Vec<_>> = read_raw_log(&log)
+            .iter()
+            .map(|line| line.split(',').map(str::to_string).collect())
+            .collect();
+        let steps: Vec<_> = lines
+            .iter()
+            .map(|fields| (fields[6].as_str(), fields[3].as_str()))
+            .collect();
+        assert_eq!(
+            vec![
+                ("shop/login", "200"),
+                ("shop/item", "200"),
+                ("shop/login", "200"),
+                ("shop/item", "200"),
+            ],
+            steps
+        );
+        // The think time is between the steps, not part of their latency.
+        assert!(report.elapsed >= Duration::from_millis(200));
+        assert!(lines
+            .iter()
+            .all(|fields| fields[1].parse::<u64>().unwrap() < 100_000));
+    }
+
+    #[tokio::test]
+    async fn it_leaves_the_scenario_when_a_step_fails() {
+        let server = MockServer::start_async().await;
+        let login = server
+            .mock_async(|when, then| {
+                when.path("/login");
+                then.status(401).body("denied");
+            })
+            .await;
+        let path = scenario_file(
+            "failing.toml",
+            &format!(
+                r#"
+[[scenarios]]
+name = "shop"
+
+[[scenarios.steps]]
+name = "login"
+url = "{}"
+extract = {{ token = {{ json = "token" }} }}
+
+[[scenarios.steps]]
+url = "{}/items?token={{{{token}}}}"
+"#,
+                server.url("/login"),
+                server.base_url()
+            ),
+        );
+
+        let log = temp_path("failing.csv");
+
+        let report = hammer_with_args(vec![
+            "hammer".to_string(),
+            "--scenario".to_string(),
+            path.clone(),
+            "--raw-log".to_string(),
+            log.clone(),
+        ])
+        .await
+        .unwrap();
+
+        std::fs::remove_file(path).unwrap();
+        login.assert_hits_async(1).await;
+        let lines = read_raw_log(&log);
+        assert_eq!(1, lines.len());
+        assert!(
+            lines[0].ends_with(
+                ",401,6,\"cannot extract login, the body is not JSON: expected value at line 1 column 1\",shop/login"
+            ),
+            "{}",
+            lines[0]
+        );
+        let summary = report.summary();
+        assert_eq!(1, summary.requests);
+        assert_eq!((0, 1), (summary.errors, summary.extract_errors));
+        assert_eq!(Some(&1), summary.codes.get(&401));
+        assert!(summary.to_string().contains("\nExtract fails 1\n"));
+    }
+
+    #[tokio::test]
+    async fn it_picks_the_scenarios_by_weight() {
+        let server = MockServer::start_async().await;
+        let browse = server
+            .mock_async(|when, then| {
+                when.path("/browse");
+                then.status(200);
+            })
+            .await;
+        let buy = server
+            .mock_async(|when, then| {
+                when.path("/buy");
+                then.status(200);
+            })
+            .await;
+        let path = scenario_file(
+            "weights.yml",
+            &format!(
+                r#"
+scenarios:
+  - name: browse
+    weight: 9
+    steps: [{{ url: "{}" }}]
+  - name: buy
+    steps: [{{ url: "{}" }}]
+"#,
+                server.url("/browse"),
+                server.url("/buy")
+            ),
+        );
+
+        let report = hammer_with_args(vec![
+            "hammer".to_string(),
+            "-s".to_string(),
+            path.clone(),
+            "-n".to_string(),
+            "20".to_string(),
+            "-c".to_string(),
+            "4".to_string(),
+        ])
+        .await
+        .unwrap();
+
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(20, report.summary().requests);
+        browse.assert_hits_async(18).await;
+        buy.assert_hits_async(2).await;
+    }
+
+    #[tokio::test]
+    async fn it_fails_on_invalid_scenarios() {
+        let path = scenario_file(
+            "unknown.yaml",
+            "scenarios: [{name: s, steps: [{url: 'http://localhost/{{id}}'}]}]",
+        );
+
+        let err = hammer_with_args(vec!["hammer".to_string(), "-s".to_string(), path.clone()])
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            Error::Scenario(format!("{}: scenario s, step 1, unknown variable id", path)),
+            err
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        let err = hammer_with_args(vec![
+            "hammer".to_string(),
+            "-s".to_string(),
+            path,
+            "-u".to_string(),
+            "http://localhost".to_string(),
+        ])
+        .await
+        .unwrap_err();
+        assert!(matches!(err, Error::InvalidArgs(_)));
+    }
+
+    #[tokio::test]
+    async fn it_ramps_the_users_through_the_stages() {
+        let server = MockServer::start_async().await;
+        let mock = server
+            .mock_async(|when, then| {
+                when.path("/hammer");
+                then.status(200).delay(Duration::from_millis(10));
+            })
+            .await;
+
+        let report = hammer_with_args(args(
+            server.url("/hammer"),
+            &[
+                "--stage", "300ms:4", "--stage", "300ms:4", "--stage", "300ms:0",
+            ],
+        ))
+        .await
+        .unwrap();
+
+        mock.assert_hits_async(report.summary().requests).await;
+        assert!(report.elapsed >= Duration::from_millis(900));
+        assert!(report.elapsed < Duration::from_secs(2));
+        let summary = report.summary();
+        let requests: Vec<_> = summary
+            .stages
+            .iter()
+            .map(|stage| stage.summary.requests)
+            .collect();
+        assert_eq!(3, requests.len());
+        assert_eq!(summary.requests, requests.iter().sum::<usize>());
+        // Half the users on average while ramping up.
+        assert!(requests[1] > requests[0], "{:?}", requests);
+        assert!(requests[1] > requests[2], "{:?}", requests);
+    }
+
+    #[tokio::test]
+    async fn it_ramps_the_rate_through_the_stages() {
+        let server = MockServer::start_async().await;
+        let mock = server
+            .mock_async(|when, then| {
+                when.path("/hammer");
+                then.status(200);
+            })
+            .await;
+
+        let report = hammer_with_args(args(
+            server.url("/hammer"),
+            &["--stage", "500ms:100/s", "--stage", "500ms:100/s"],
+        ))
+        .await
+        .unwrap();
+
+        // 25 requests ramping up and 50 holding the rate.
+        mock.assert_hits_async(75).await;
+        assert_eq!(75.0, report.schedule.as_ref().unwrap().rate);
+        let summary = report.summary();
+        let requests: Vec<_> = summary
+            .stages
+            .iter()
+            .map(|stage| stage.summary.requests)
+            .collect();
+        assert_eq!(75, requests.iter().sum::<usize>());
+        assert!((24..=26).contains(&requests[0]), "{:?}", requests);
+    }
+
+    #[tokio::test]
+    async fn it_fails_on_a_rate_without_limits() {
+        let err = hammer_with_args(args("http://localhost".to_string(), &["--rate", "500/s"]))
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            Error::InvalidArgs(
+                "the rate needs a duration (-d) or a number of requests (-n)".to_string()
+            ),
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn it_fails_on_invalid_stages() {
+        let invalid = |extra: &'static [&'static str]| async move {
+            hammer_with_args(args("http://localhost".to_string(), extra))
+                .await
+                .unwrap_err()
+        };
+
+        assert_eq!(
+            Error::InvalidArgs(
+                "the stages mix users and rates, expected only one of them".to_string()
+            ),
+            invalid(&["--stage", "1s:10", "--stage", "1s:10/s"]).await
+        );
+        assert_eq!(
+            Error::InvalidArgs("the concurrency is given by the users of the stages".to_string()),
+            invalid(&["--stage", "1s:10", "-c", "2"]).await
+        );
+        assert!(matches!(
+            invalid(&["--stage", "1s:10", "-r", "10/s"]).await,
+            Error::InvalidArgs(_)
+        ));
+        assert!(matches!(
+            invalid(&["--stage", "1s:10", "-d", "1s"]).await,
+            Error::InvalidArgs(_)
+        ));
+    }
+
+    #[test]
+    fn it_parses_stages() {
+        assert_eq!(
+            Ok((Duration::from_secs(60), Target::Users(200))),
+            parse_stage("1m:200")
+        );
+        assert_eq!(
+            Ok((
+                Duration::from_secs(30),
+                Target::Rate(Rate {
+                    requests: 500,
+                    per: Duration::from_secs(1)
+                })
+            )),
+            parse_stage("30s:500/s")
+        );
+        assert_eq!(
+            Ok((
+                Duration::from_secs(30),
+                Target::Rate(Rate {
+                    requests: 0,
+                    per: Duration::from_secs(60)
+                })
+            )),
+            parse_stage("30s:0/m")
+        );
+        assert_eq!(
+            Ok((Duration::from_millis(500), Target::Users(0))),
+            parse_stage("500ms:0")
+        );
+        assert!(parse_stage("1m").is_err());
+        assert!(parse_stage("0s:10").is_err());
+        assert!(parse_stage("1m:lots").is_err());
+        assert!(parse_stage("1m:10/d").is_err());
+    }
+
+    #[tokio::test]
+    async fn it_fails_on_invalid_outputs() {
+        let err = hammer_with_args(args("http://localhost".to_string(), &["-o", "xml"]))
+            .await
+            .unwrap_err();
+
+        assert!(matches!(err, Error::InvalidArgs(_)));
+    }
 }
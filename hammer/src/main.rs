@@ -4,7 +4,8 @@ use std::env;
 async fn main() {
     let res = hammer::hammer_with_args(env::args().collect()).await;
     match res {
-        Ok(stats) => println!("{:?}", stats),
+        Ok(report) => print!("{}", report.summary().render(report.output)),
+        Err(hammer::Error::Help(text)) => print!("{}", text),
         Err(err) => {
             println!("{}", err);
             std::process::exit(1)
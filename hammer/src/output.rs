@@ -0,0 +1,448 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::time::{Duration, UNIX_EPOCH};
+
+use tokio::fs::File;
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::{Error, Latency, Stats, Summary};
+
+/// How the summary of the run is printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Output {
+    #[default]
+    Text,
+    Json,
+    Csv,
+}
+
+impl Summary {
+    pub fn render(&self, output: Output) -> String {
+        match output {
+            Output::Text => self.to_string(),
+            Output::Json => self.to_json(),
+            Output::Csv => self.to_csv(),
+        }
+    }
+
+    /// The summary as a JSON object, with the durations in microseconds.
+    pub fn to_json(&self) -> String {
+        format!("{}\n", object(&self.json_fields()))
+    }
+
+    fn json_fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![
+            ("requests", self.requests.to_string()),
+            ("errors", self.errors.to_string()),
+            ("duration_us", self.elapsed.as_micros().to_string()),
+            ("rate", format!("{:.2}", self.rate)),
+            ("latency_us", latency_json(&self.latency)),
+            ("first_byte_us", latency_json(&self.first_byte)),
+            ("status_codes", counts_json(&self.codes)),
+            ("status_classes", counts_json(&self.classes)),
+            ("error_kinds", counts_json(&self.error_kinds)),
+            ("extract_errors", self.extract_errors.to_string()),
+        ];
+        if let Some(schedule) = &self.schedule {
+            fields.push((
+                "schedule",
+                object(&[
+                    ("rate", format!("{:.2}", schedule.rate)),
+                    ("late", schedule.late.to_string()),
+                    ("max_lag_us", schedule.max_lag.as_micros().to_string()),
+                ]),
+            ));
+        }
+        if !self.stages.is_empty() {
+            let stages: Vec<_> = self
+                .stages
+                .iter()
+                .map(|stage| {
+                    let mut fields = vec![("name", json_string(&stage.name))];
+                    fields.extend(stage.summary.json_fields());
+                    object(&fields)
+                })
+                .collect();
+            fields.push(("stages", format!("[{}]", stages.join(","))));
+        }
+        fields
+    }
+
+    /// The summary as metric and value rows, with the durations in
+    /// microseconds. The metrics of the stages are prefixed by their number,
+    /// like stage_1_requests.
+    pub fn to_csv(&self) -> String {
+        let mut rows = self.csv_rows("");
+        if let Some(schedule) = &self.schedule {
+            rows.push(("target_rate".to_string(), format!("{:.2}", schedule.rate)));
+            rows.push(("late".to_string(), schedule.late.to_string()));
+            rows.push((
+                "max_lag_us".to_string(),
+                schedule.max_lag.as_micros().to_string(),
+            ));
+        }
+        for (n, stage) in self.stages.iter().enumerate() {
+            let prefix = format!("stage_{}_", n + 1);
+            rows.push((format!("{}name", prefix), csv_field(&stage.name)));
+            rows.extend(stage.summary.csv_rows(&prefix));
+        }
+        let mut csv = String::from("metric,value\n");
+        for (metric, value) in rows {
+            csv.push_str(&format!("{},{}\n", metric, value));
+        }
+        csv
+    }
+
+    fn csv_rows(&self, prefix: &str) -> Vec<(String, String)> {
+        let mut rows = vec![
+            ("requests".to_string(), self.requests.to_string()),
+            ("errors".to_string(), self.errors.to_string()),
+            (
+                "duration_us".to_string(),
+                self.elapsed.as_micros().to_string(),
+            ),
+            ("rate".to_string(), format!("{:.2}", self.rate)),
+        ];
+        for (name, latency) in [("latency", &self.latency), ("first_byte", &self.first_byte)] {
+            for (stat, value) in latency_fields(latency) {
+                rows.push((
+                    format!("{}_{}_us", name, stat),
+                    value.as_micros().to_string(),
+                ));
+            }
+        }
+        for (code, count) in &self.codes {
+            rows.push((format!("status_{}", code), count.to_string()));
+        }
+        for (class, count) in &self.classes {
+            rows.push((format!("status_{}", class), count.to_string()));
+        }
+        for (kind, count) in &self.error_kinds {
+            let kind = kind.to_string().replace(' ', "_");
+            rows.push((format!("error_{}", kind), count.to_string()));
+        }
+        if self.extract_errors > 0 {
+            rows.push((
+                "extract_errors".to_string(),
+                self.extract_errors.to_string(),
+            ));
+        }
+        rows.into_iter()
+            .map(|(metric, value)| (format!("{}{}", prefix, metric), value))
+            .collect()
+    }
+}
+
+fn latency_fields(latency: &Latency) -> [(&'static str, Duration); 9] {
+    [
+        ("min", latency.min),
+        ("mean", latency.mean),
+        ("stddev", latency.stddev),
+        ("p50", latency.p50),
+        ("p90", latency.p90),
+        ("p95", latency.p95),
+        ("p99", latency.p99),
+        ("p999", latency.p999),
+        ("max", latency.max),
+    ]
+}
+
+fn latency_json(latency: &Latency) -> String {
+    let fields: Vec<_> = latency_fields(latency)
+        .iter()
+        .map(|(name, value)| (*name, value.as_micros().to_string()))
+        .collect();
+    object(&fields)
+}
+
+fn counts_json<K: Display>(counts: &BTreeMap<K, usize>) -> String {
+    let fields: Vec<_> = counts
+        .iter()
+        .map(|(key, count)| (key.to_string(), count.to_string()))
+        .collect();
+    object(&fields)
+}
+
+// The values are already JSON.
+fn object<K: AsRef<str>>(fields: &[(K, String)]) -> String {
+    let fields: Vec<_> = fields
+        .iter()
+        .map(|(key, value)| format!("{}:{}", json_string(key.as_ref()), value))
+        .collect();
+    format!("{{{}}}", fields.join(","))
+}
+
+fn json_string(value: &str) -> String {
+    let mut json = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Streams a CSV line per finished request to a file, while the run goes on.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RawLog {
+    lines: Option<mpsc::UnboundedSender<Stats>>,
+}
+
+const RAW_LOG_HEADER: &str = "timestamp,latency_us,first_byte_us,status,bytes,error,step\n";
+
+impl RawLog {
+    /// Creates the file and the task writing to it, which finishes once all
+    /// the copies of the log are dropped.
+    pub(crate) async fn create(
+        path: &str,
+    ) -> Result<(RawLog, JoinHandle<Result<(), Error>>), Error> {
+        let io_error = |err: std::io::Error| Error::Io(format!("cannot write {}: {}", path, err));
+        let file = File::create(path).await.map_err(io_error)?;
+        let (lines, mut received) = mpsc::unbounded_channel::<Stats>();
+        let path = path.to_string();
+        let writer = tokio::spawn(async move {
+            let io_error =
+                |err: std::io::Error| Error::Io(format!("cannot write {}: {}", path, err));
+            let mut file = BufWriter::new(file);
+            file.write_all(RAW_LOG_HEADER.as_bytes())
+                .await
+                .map_err(io_error)?;
+            while let Some(stats) = received.recv().await {
+                file.write_all(raw_line(&stats).as_bytes())
+                    .await
+                    .map_err(io_error)?;
+            }
+            file.flush().await.map_err(io_error)
+        });
+        Ok((RawLog { lines: Some(lines) }, writer))
+    }
+
+    pub(crate) fn record(&self, stats: &Stats) {
+        if let Some(lines) = &self.lines {
+            let _ = lines.send(stats.clone());
+        }
+    }
+}
+
+fn raw_line(stats: &Stats) -> String {
+    let timestamp = stats
+        .sent_at
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+    let (status, error) = match (&stats.status, &stats.extract) {
+        (Ok(code), None) => (code.to_string(), String::new()),
+        (Ok(code), Some(err)) => (code.to_string(), csv_field(&err.to_string())),
+        (Err(err), _) => (String::new(), csv_field(&err.to_string())),
+    };
+    format!(
+        "{:.6},{},{},{},{},{},{}\n",
+        timestamp,
+        stats.latency.as_micros(),
+        stats.first_byte.as_micros(),
+        status,
+        stats.bytes,
+        error,
+        csv_field(stats.step.as_deref().unwrap_or_default())
+    )
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    use crate::{Report, Schedule, Stage, Totals};
+
+    fn stats(status: Result<u16, Error>, micros: u64) -> Stats {
+        Stats {
+            status,
+            sent_at: UNIX_EPOCH + Duration::from_millis(1_700_000_000_250),
+            first_byte: Duration::from_micros(micros / 2),
+            latency: Duration::from_micros(micros),
+            bytes: 12,
+            step: None,
+            extract: None,
+        }
+    }
+
+    fn summary() -> Summary {
+        let mut totals = Totals::new(SystemTime::now(), Vec::new());
+        totals.record(&stats(Ok(200), 1000));
+        totals.record(&stats(Ok(503), 2000));
+        totals.record(&stats(Err(Error::Timeout), 3000));
+        Report {
+            totals,
+            elapsed: Duration::from_secs(2),
+            schedule: Some(Schedule {
+                rate: 1.5,
+                late: 0,
+                max_lag: Duration::from_micros(700),
+            }),
+            output: Output::Text,
+        }
+        .summary()
+    }
+
+    #[test]
+    fn it_renders_json() {
+        assert_eq!(
+            "{\"requests\":3,\"errors\":1,\"duration_us\":2000000,\"rate\":1.50,\
+            \"latency_us\":{\"min\":1000,\"mean\":1500,\"stddev\":500,\"p50\":1000,\"p90\":2000,\
+            \"p95\":2000,\"p99\":2000,\"p999\":2000,\"max\":2000},\
+            \"first_byte_us\":{\"min\":500,\"mean\":750,\"stddev\":250,\"p50\":500,\"p90\":1000,\
+            \"p95\":1000,\"p99\":1000,\"p999\":1000,\"max\":1000},\
+            \"status_codes\":{\"200\":1,\"503\":1},\"status_classes\":{\"2xx\":1,\"5xx\":1},\
+            \"error_kinds\":{\"timeout\":1},\"extract_errors\":0,\
+            \"schedule\":{\"rate\":1.50,\"late\":0,\"max_lag_us\":700}}\n",
+            summary().render(Output::Json)
+        );
+    }
+
+    #[test]
+    fn it_renders_csv() {
+        let csv = summary().render(Output::Csv);
+
+        assert!(
+            csv.starts_with("metric,value\nrequests,3\nerrors,1\nduration_us,2000000\nrate,1.50\n")
+        );
+        assert!(csv.contains("\nlatency_p99_us,2000\n"));
+        assert!(csv.contains("\nfirst_byte_min_us,500\n"));
+        assert!(csv.contains("\nstatus_503,1\nstatus_2xx,1\nstatus_5xx,1\nerror_timeout,1\n"));
+        assert!(csv.ends_with("\ntarget_rate,1.50\nlate,0\nmax_lag_us,700\n"));
+    }
+
+    #[test]
+    fn it_renders_the_stages() {
+        let started = SystemTime::now();
+        let stage = Stage {
+            name: "ramp 0 → 2 users over 1s".to_string(),
+            start: Duration::ZERO,
+            duration: Duration::from_secs(1),
+            from: 0.0,
+            to: 2.0,
+        };
+        let mut totals = Totals::new(started, vec![stage]);
+        totals.record(&Stats {
+            sent_at: started,
+            ..stats(Ok(200), 1000)
+        });
+        let report = Report {
+            totals,
+            elapsed: Duration::from_secs(1),
+            schedule: None,
+            output: Output::Json,
+        };
+        let summary = report.summary();
+
+        let json = summary.render(Output::Json);
+        assert!(json.contains(
+            ",\"stages\":[{\"name\":\"ramp 0 → 2 users over 1s\",\"requests\":1,\"errors\":0,\
+            \"duration_us\":1000000,\"rate\":1.00,\"latency_us\":{\"min\":1000,"
+        ));
+        assert!(json.ends_with(
+            "\"status_codes\":{\"200\":1},\"status_classes\":{\"2xx\":1},\"error_kinds\":{},\"extract_errors\":0}]}\n"
+        ));
+
+        let csv = summary.render(Output::Csv);
+        assert!(csv.contains(
+            "\nstage_1_name,ramp 0 → 2 users over 1s\nstage_1_requests,1\nstage_1_errors,0\n"
+        ));
+        assert!(csv.contains("\nstage_1_latency_min_us,1000\n"));
+        assert!(csv.ends_with("\nstage_1_status_200,1\nstage_1_status_2xx,1\n"));
+    }
+
+    #[test]
+    fn it_renders_text() {
+        let summary = summary();
+        assert_eq!(summary.to_string(), summary.render(Output::Text));
+    }
+
+    #[test]
+    fn it_escapes_json_strings() {
+        assert_eq!(
+            "\"a \\\"b\\\" \\\\ \\n\\u0001\"",
+            json_string("a \"b\" \\ \n\u{1}")
+        );
+    }
+
+    #[test]
+    fn it_formats_raw_lines() {
+        assert_eq!(
+            "1700000000.250000,1000,500,200,12,,\n",
+            raw_line(&stats(Ok(200), 1000))
+        );
+        assert_eq!(
+            "1700000000.250000,1000,500,200,12,,\"browse,slowly/login\"\n",
+            raw_line(&Stats {
+                step: Some("browse,slowly/login".to_string()),
+                ..stats(Ok(200), 1000)
+            })
+        );
+        assert_eq!(
+            "1700000000.250000,3000,1500,,12,\"cannot connect: refused, \"\"111\"\"\",\n",
+            raw_line(&stats(
+                Err(Error::Connect("refused, \"111\"".to_string())),
+                3000
+            ))
+        );
+        assert_eq!(
+            "1700000000.250000,1000,500,401,12,\"cannot extract login, no value at token\",\n",
+            raw_line(&Stats {
+                extract: Some(Error::Extract("login, no value at token".to_string())),
+                ..stats(Ok(401), 1000)
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn it_streams_the_raw_log() {
+        let path = std::env::temp_dir().join(format!("hammer-raw-{}.csv", std::process::id()));
+        let path = path.to_str().unwrap().to_string();
+        let (log, writer) = RawLog::create(&path).await.unwrap();
+
+        log.record(&stats(Ok(200), 1000));
+        log.clone().record(&stats(Err(Error::Timeout), 2000));
+        drop(log);
+        writer.await.unwrap().unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            "timestamp,latency_us,first_byte_us,status,bytes,error,step\n\
+            1700000000.250000,1000,500,200,12,,\n\
+            1700000000.250000,2000,1000,,12,request timed out,\n",
+            contents
+        );
+    }
+
+    #[tokio::test]
+    async fn it_fails_to_create_the_raw_log() {
+        let err = RawLog::create("/does/not/exist.csv").await.unwrap_err();
+
+        assert_eq!(
+            Error::Io(
+                "cannot write /does/not/exist.csv: No such file or directory (os error 2)"
+                    .to_string()
+            ),
+            err
+        );
+    }
+}
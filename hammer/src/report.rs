@@ -0,0 +1,666 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::time::{Duration, SystemTime};
+
+use hdrhistogram::Histogram;
+
+use crate::{ErrorKind, Output, Stage, Stats};
+
+// Latencies are recorded in microseconds, up to an hour.
+const HIGHEST_LATENCY: u64 = 60 * 60 * 1_000_000;
+const SIGNIFICANT_DIGITS: u8 = 3;
+
+// The fewest late requests that warn about the generator.
+const LATE_WARNING: usize = 10;
+
+/// The outcome of a run: its requests aggregated and how long it took.
+#[derive(Debug)]
+pub struct Report {
+    pub totals: Totals,
+    pub elapsed: Duration,
+    /// How well the requests kept to the rate, when sent at a fixed rate.
+    pub schedule: Option<Schedule>,
+    /// How the summary has to be printed.
+    pub output: Output,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Schedule {
+    /// The target requests per second, on average with stages.
+    pub rate: f64,
+    /// Requests sent later than the interval between two requests, because
+    /// the generator could not keep up with the rate.
+    pub late: usize,
+    /// The most a request was sent behind its time.
+    pub max_lag: Duration,
+}
+
+impl Report {
+    /// The latencies and the counts of the outcomes of the requests, for the
+    /// whole run and for each of its stages.
+    pub fn summary(&self) -> Summary {
+        let mut summary = self.totals.run.summary(self.elapsed);
+        summary.schedule = self.schedule.clone();
+        summary.stages = self
+            .totals
+            .stages
+            .iter()
+            .zip(&self.totals.by_stage)
+            .filter(|(stage, _)| stage.start < self.elapsed)
+            .map(|(stage, tally)| StageSummary {
+                name: stage.name.clone(),
+                summary: tally.summary(stage.duration.min(self.elapsed - stage.start)),
+            })
+            .collect();
+        summary
+    }
+}
+
+/// The requests of a run, aggregated in latency histograms and counts of
+/// their outcomes as they finish, so the memory doesn't grow with them. The
+/// details of every request are only kept by the raw log.
+#[derive(Debug, Clone)]
+pub struct Totals {
+    started: SystemTime,
+    /// How the load changed along the run, if it did.
+    stages: Vec<Stage>,
+    run: Tally,
+    by_stage: Vec<Tally>,
+}
+
+impl Totals {
+    pub(crate) fn new(started: SystemTime, stages: Vec<Stage>) -> Totals {
+        Totals {
+            started,
+            by_stage: stages.iter().map(|_| Tally::new()).collect(),
+            stages,
+            run: Tally::new(),
+        }
+    }
+
+    /// Adds a finished request to the run, and to the stage it was sent in.
+    /// The requests sent after the stages count for the last one.
+    pub(crate) fn record(&mut self, stats: &Stats) {
+        self.run.record(stats);
+        let offset = stats
+            .sent_at
+            .duration_since(self.started)
+            .unwrap_or_default();
+        let stage = self
+            .stages
+            .iter()
+            .position(|stage| offset < stage.end())
+            .or(self.stages.len().checked_sub(1));
+        if let Some(n) = stage {
+            self.by_stage[n].record(stats);
+        }
+    }
+}
+
+// Only the requests with a response count for the latency.
+#[derive(Debug, Clone)]
+struct Tally {
+    latency: Histogram<u64>,
+    first_byte: Histogram<u64>,
+    requests: usize,
+    errors: usize,
+    codes: BTreeMap<u16, usize>,
+    error_kinds: BTreeMap<ErrorKind, usize>,
+    extract_errors: usize,
+}
+
+impl Tally {
+    fn new() -> Tally {
+        Tally {
+            latency: histogram(),
+            first_byte: histogram(),
+            requests: 0,
+            errors: 0,
+            codes: BTreeMap::new(),
+            error_kinds: BTreeMap::new(),
+            extract_errors: 0,
+        }
+    }
+
+    fn record(&mut self, stats: &Stats) {
+        self.requests += 1;
+        if stats.extract.is_some() {
+            self.extract_errors += 1;
+        }
+        match &stats.status {
+            Ok(code) => {
+                *self.codes.entry(*code).or_insert(0) += 1;
+                self.latency
+                    .saturating_record(stats.latency.as_micros() as u64);
+                self.first_byte
+                    .saturating_record(stats.first_byte.as_micros() as u64);
+            }
+            Err(err) => {
+                self.errors += 1;
+                *self.error_kinds.entry(err.kind()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    fn summary(&self, elapsed: Duration) -> Summary {
+        let mut classes = BTreeMap::new();
+        for (code, count) in &self.codes {
+            *classes.entry(format!("{}xx", code / 100)).or_insert(0) += count;
+        }
+        let seconds = elapsed.as_secs_f64();
+        Summary {
+            requests: self.requests,
+            errors: self.errors,
+            elapsed,
+            rate: if seconds > 0.0 {
+                self.requests as f64 / seconds
+            } else {
+                0.0
+            },
+            latency: Latency::from(&self.latency),
+            first_byte: Latency::from(&self.first_byte),
+            schedule: None,
+            codes: self.codes.clone(),
+            classes,
+            error_kinds: self.error_kinds.clone(),
+            extract_errors: self.extract_errors,
+            stages: Vec::new(),
+        }
+    }
+}
+
+fn histogram() -> Histogram<u64> {
+    Histogram::new_with_bounds(1, HIGHEST_LATENCY, SIGNIFICANT_DIGITS).unwrap()
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Summary {
+    pub requests: usize,
+    /// Requests without a response.
+    pub errors: usize,
+    pub elapsed: Duration,
+    /// Requests per second.
+    pub rate: f64,
+    /// From sending the request to reading the whole response.
+    pub latency: Latency,
+    /// From sending the request to receiving the response headers.
+    pub first_byte: Latency,
+    pub schedule: Option<Schedule>,
+    /// Responses by status code.
+    pub codes: BTreeMap<u16, usize>,
+    /// Responses by status class, like 2xx or 5xx.
+    pub classes: BTreeMap<String, usize>,
+    /// Requests without a response by the kind of error.
+    pub error_kinds: BTreeMap<ErrorKind, usize>,
+    /// Responses the variables of a scenario step couldn't be extracted
+    /// from.
+    pub extract_errors: usize,
+    /// The summaries of the stages the run got to, if it had any.
+    pub stages: Vec<StageSummary>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct StageSummary {
+    pub name: String,
+    pub summary: Summary,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Latency {
+    pub min: Duration,
+    pub mean: Duration,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+    pub p999: Duration,
+    pub max: Duration,
+    pub stddev: Duration,
+}
+
+impl Latency {
+    fn from(histogram: &Histogram<u64>) -> Latency {
+        let micros = |micros: u64| Duration::from_micros(micros);
+        Latency {
+            min: micros(histogram.min()),
+            mean: micros(histogram.mean().round() as u64),
+            p50: micros(histogram.value_at_quantile(0.5)),
+            p90: micros(histogram.value_at_quantile(0.9)),
+            p95: micros(histogram.value_at_quantile(0.95)),
+            p99: micros(histogram.value_at_quantile(0.99)),
+            p999: micros(histogram.value_at_quantile(0.999)),
+            max: micros(histogram.max()),
+            stddev: micros(histogram.stdev().round() as u64),
+        }
+    }
+}
+
+impl Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Requests      {}", self.requests)?;
+        write!(f, "Errors        {}", self.errors)?;
+        if !self.error_kinds.is_empty() {
+            write!(f, " ({})", counts(&self.error_kinds))?;
+        }
+        writeln!(f)?;
+        if self.extract_errors > 0 {
+            writeln!(f, "Extract fails {}", self.extract_errors)?;
+        }
+        writeln!(f, "Duration      {}", human(self.elapsed))?;
+        writeln!(f, "Throughput    {:.2} req/s", self.rate)?;
+        if let Some(schedule) = &self.schedule {
+            writeln!(
+                f,
+                "Target rate   {:.2} req/s, {} sent late, up to {} behind",
+                schedule.rate,
+                schedule.late,
+                human(schedule.max_lag)
+            )?;
+            // A few late requests are just hiccups of the scheduler, even
+            // if they are more than 1% of a short run.
+            if schedule.late >= LATE_WARNING && schedule.late * 100 > self.requests {
+                writeln!(
+                    f,
+                    "Warning: the generator could not keep up with the target rate"
+                )?;
+            }
+        }
+        writeln!(f)?;
+        if !self.codes.is_empty() {
+            writeln!(f, "Status        {}", counts(&self.classes))?;
+            for (code, count) in &self.codes {
+                writeln!(f, "  {:<10}  {}", code, count)?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "Latency       {:>12}  {:>12}", "total", "first byte")?;
+        let rows = [
+            ("min", self.latency.min, self.first_byte.min),
+            ("mean", self.latency.mean, self.first_byte.mean),
+            ("stddev", self.latency.stddev, self.first_byte.stddev),
+            ("p50", self.latency.p50, self.first_byte.p50),
+            ("p90", self.latency.p90, self.first_byte.p90),
+            ("p95", self.latency.p95, self.first_byte.p95),
+            ("p99", self.latency.p99, self.first_byte.p99),
+            ("p99.9", self.latency.p999, self.first_byte.p999),
+            ("max", self.latency.max, self.first_byte.max),
+        ];
+        for (name, latency, first_byte) in rows {
+            writeln!(
+                f,
+                "  {:<10}  {:>12}  {:>12}",
+                name,
+                human(latency),
+                human(first_byte)
+            )?;
+        }
+        if !self.stages.is_empty() {
+            let width = self
+                .stages
+                .iter()
+                .map(|stage| stage.name.chars().count())
+                .max()
+                .unwrap_or_default();
+            writeln!(f)?;
+            writeln!(
+                f,
+                "{:<width$}  {:>9}  {:>7}  {:>10}  {:>10}  {:>10}  {:>10}  {:>10}",
+                "Stages",
+                "requests",
+                "errors",
+                "req/s",
+                "p50",
+                "p90",
+                "p99",
+                "max",
+                width = width + 2
+            )?;
+            for stage in &self.stages {
+                let summary = &stage.summary;
+                writeln!(
+                    f,
+                    "  {:<width$}  {:>9}  {:>7}  {:>10.2}  {:>10}  {:>10}  {:>10}  {:>10}",
+                    stage.name,
+                    summary.requests,
+                    summary.errors,
+                    summary.rate,
+                    human(summary.latency.p50),
+                    human(summary.latency.p90),
+                    human(summary.latency.p99),
+                    human(summary.latency.max),
+                    width = width
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Formats counts like "2xx 95, 5xx 5".
+fn counts<K: Display>(counts: &BTreeMap<K, usize>) -> String {
+    counts
+        .iter()
+        .map(|(key, count)| format!("{} {}", key, count))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Formats a duration with the unit that fits it best.
+fn human(duration: Duration) -> String {
+    let micros = duration.as_micros();
+    if micros < 1_000 {
+        format!("{}µs", micros)
+    } else if micros < 1_000_000 {
+        format!("{:.2}ms", micros as f64 / 1_000.0)
+    } else {
+        format!("{:.2}s", duration.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    use crate::Error;
+
+    fn stats(micros: u64) -> Stats {
+        Stats {
+            status: Ok(200),
+            sent_at: SystemTime::now(),
+            first_byte: Duration::from_micros(micros / 2),
+            latency: Duration::from_micros(micros),
+            bytes: 0,
+            step: None,
+            extract: None,
+        }
+    }
+
+    fn totals(all_stats: Vec<Stats>) -> Totals {
+        staged(SystemTime::now(), Vec::new(), all_stats)
+    }
+
+    fn staged(started: SystemTime, stages: Vec<Stage>, all_stats: Vec<Stats>) -> Totals {
+        let mut totals = Totals::new(started, stages);
+        for stats in &all_stats {
+            totals.record(stats);
+        }
+        totals
+    }
+
+    #[test]
+    fn it_summarizes_the_latencies() {
+        let report = Report {
+            totals: totals((1..=1000).map(stats).collect()),
+            elapsed: Duration::from_secs(2),
+            schedule: None,
+            output: Output::Text,
+        };
+
+        let summary = report.summary();
+
+        assert_eq!(1000, summary.requests);
+        assert_eq!(500.0, summary.rate);
+        let micros = Duration::from_micros;
+        assert_eq!(
+            Latency {
+                min: micros(1),
+                mean: micros(501),
+                p50: micros(500),
+                p90: micros(900),
+                p95: micros(950),
+                p99: micros(990),
+                p999: micros(999),
+                max: micros(1000),
+                stddev: micros(289),
+            },
+            summary.latency
+        );
+        assert_eq!(micros(0), summary.first_byte.min);
+        assert_eq!(micros(500), summary.first_byte.max);
+    }
+
+    #[test]
+    fn it_leaves_the_errors_out_of_the_latencies() {
+        let mut timeout = stats(5_000_000);
+        timeout.status = Err(Error::Timeout);
+        let report = Report {
+            totals: totals(vec![stats(100), timeout, stats(300)]),
+            elapsed: Duration::from_secs(1),
+            schedule: None,
+            output: Output::Text,
+        };
+
+        let summary = report.summary();
+
+        assert_eq!(3, summary.requests);
+        assert_eq!(1, summary.errors);
+        assert_eq!(Duration::from_micros(300), summary.latency.max);
+    }
+
+    #[test]
+    fn it_breaks_down_the_outcomes() {
+        let outcome = |status: Result<u16, Error>| Stats {
+            status,
+            ..stats(100)
+        };
+        let report = Report {
+            totals: totals(vec![
+                outcome(Ok(200)),
+                outcome(Ok(201)),
+                outcome(Ok(200)),
+                outcome(Ok(404)),
+                outcome(Ok(503)),
+                outcome(Err(Error::Timeout)),
+                outcome(Err(Error::Connect("refused".to_string()))),
+                outcome(Err(Error::Timeout)),
+            ]),
+            elapsed: Duration::from_secs(1),
+            schedule: None,
+            output: Output::Text,
+        };
+
+        let summary = report.summary();
+
+        assert_eq!(
+            BTreeMap::from([(200, 2), (201, 1), (404, 1), (503, 1)]),
+            summary.codes
+        );
+        assert_eq!(
+            BTreeMap::from([
+                ("2xx".to_string(), 3),
+                ("4xx".to_string(), 1),
+                ("5xx".to_string(), 1)
+            ]),
+            summary.classes
+        );
+        assert_eq!(
+            BTreeMap::from([(ErrorKind::Connect, 1), (ErrorKind::Timeout, 2)]),
+            summary.error_kinds
+        );
+
+        let summary = format!("{}", summary);
+
+        assert!(summary.contains("Errors        3 (connect 1, timeout 2)\n"));
+        assert!(summary.contains(
+            "\nStatus        2xx 3, 4xx 1, 5xx 1\n  \
+            200         2\n  \
+            201         1\n  \
+            404         1\n  \
+            503         1\n\n"
+        ));
+    }
+
+    #[test]
+    fn it_summarizes_no_requests() {
+        let report = Report {
+            totals: totals(Vec::new()),
+            elapsed: Duration::ZERO,
+            schedule: None,
+            output: Output::Text,
+        };
+
+        let summary = report.summary();
+
+        assert_eq!(0, summary.requests);
+        assert_eq!(0.0, summary.rate);
+        assert_eq!(Duration::ZERO, summary.latency.max);
+    }
+
+    #[test]
+    fn it_displays_the_summary() {
+        let report = Report {
+            totals: totals(vec![stats(800), stats(1500), stats(2_500_000)]),
+            elapsed: Duration::from_secs(3),
+            schedule: None,
+            output: Output::Text,
+        };
+
+        let summary = format!("{}", report.summary());
+
+        assert!(summary.starts_with(
+            "Requests      3\nErrors        0\nDuration      3.00s\nThroughput    1.00 req/s\n"
+        ));
+        assert!(summary.contains("  min                800µs         400µs\n"));
+        assert!(summary.contains("  max                2.50s         1.25s\n"));
+    }
+
+    #[test]
+    fn it_displays_the_schedule() {
+        let schedule = |late, max_lag| {
+            Some(Schedule {
+                rate: 500.0,
+                late,
+                max_lag,
+            })
+        };
+        let report = Report {
+            totals: totals(vec![stats(800); 1000]),
+            elapsed: Duration::from_secs(2),
+            schedule: schedule(0, Duration::from_micros(300)),
+            output: Output::Text,
+        };
+
+        let summary = format!("{}", report.summary());
+
+        assert!(summary.contains("Target rate   500.00 req/s, 0 sent late, up to 300µs behind\n\n"));
+
+        let report = Report {
+            schedule: schedule(10, Duration::from_millis(9)),
+            ..report
+        };
+
+        let summary = format!("{}", report.summary());
+
+        assert!(summary.contains("10 sent late, up to 9.00ms behind\n\n"));
+
+        let report = Report {
+            schedule: schedule(12, Duration::from_millis(25)),
+            ..report
+        };
+
+        let summary = format!("{}", report.summary());
+
+        assert!(summary.contains(
+            "12 sent late, up to 25.00ms behind\n\
+            Warning: the generator could not keep up with the target rate\n"
+        ));
+
+        // In a short run.
+        let report = Report {
+            totals: totals(vec![stats(800); 5]),
+            schedule: schedule(1, Duration::from_millis(2)),
+            ..report
+        };
+
+        let summary = format!("{}", report.summary());
+
+        assert!(summary.contains("1 sent late, up to 2.00ms behind\n\n"));
+    }
+
+    #[test]
+    fn it_breaks_down_the_stages() {
+        let started = SystemTime::now();
+        let sent = |secs: u64, micros: u64| Stats {
+            sent_at: started + Duration::from_secs(secs),
+            ..stats(micros)
+        };
+        let stage = |name: &str, start: u64, to: f64| Stage {
+            name: name.to_string(),
+            start: Duration::from_secs(start),
+            duration: Duration::from_secs(2),
+            from: 0.0,
+            to,
+        };
+        let stages = vec![
+            stage("ramp 0 → 2 users over 2s", 0, 2.0),
+            stage("hold 2 users for 2s", 2, 2.0),
+            stage("ramp 2 → 0 users over 2s", 4, 0.0),
+        ];
+        let all_stats = vec![
+            sent(0, 1000),
+            sent(1, 3000),
+            sent(2, 2000),
+            sent(3, 4000),
+            sent(6, 3500),
+        ];
+        let report = Report {
+            totals: staged(started, stages, all_stats),
+            elapsed: Duration::from_secs(7),
+            schedule: None,
+            output: Output::Text,
+        };
+
+        let summary = report.summary();
+
+        assert_eq!(5, summary.requests);
+        let stages: Vec<_> = summary
+            .stages
+            .iter()
+            .map(|stage| {
+                let summary = &stage.summary;
+                (stage.name.as_str(), summary.requests, summary.latency.min)
+            })
+            .collect();
+        // The request sent after the stages counts for the last one.
+        let micros = Duration::from_micros;
+        assert_eq!(
+            vec![
+                ("ramp 0 → 2 users over 2s", 2, micros(1000)),
+                ("hold 2 users for 2s", 2, micros(2000)),
+                ("ramp 2 → 0 users over 2s", 1, micros(3500)),
+            ],
+            stages
+        );
+        assert_eq!(Duration::from_secs(2), summary.stages[2].summary.elapsed);
+        assert_eq!(0.5, summary.stages[2].summary.rate);
+
+        let text = format!("{}", summary);
+
+        assert!(text.ends_with(
+            "\n\
+            Stages                       requests   errors       req/s         p50         p90         p99         max\n  \
+            ramp 0 → 2 users over 2s          2        0        1.00      1.00ms      3.00ms      3.00ms      3.00ms\n  \
+            hold 2 users for 2s               2        0        1.00      2.00ms      4.00ms      4.00ms      4.00ms\n  \
+            ramp 2 → 0 users over 2s          1        0        0.50      3.50ms      3.50ms      3.50ms      3.50ms\n"
+        ));
+
+        // Stopped during the second stage.
+        let report = Report {
+            elapsed: Duration::from_secs(3),
+            ..report
+        };
+
+        let summary = report.summary();
+
+        assert_eq!(2, summary.stages.len());
+        assert_eq!(Duration::from_secs(1), summary.stages[1].summary.elapsed);
+    }
+
+    #[test]
+    fn it_formats_durations() {
+        assert_eq!("999µs", human(Duration::from_micros(999)));
+        assert_eq!("1.50ms", human(Duration::from_micros(1500)));
+        assert_eq!("2.00s", human(Duration::from_secs(2)));
+    }
+}
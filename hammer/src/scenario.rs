@@ -0,0 +1,744 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use reqwest::Method;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{parse_duration, parse_method, Error, Request};
+
+/// The variables of a scenario run, by name. They keep the type they have in
+/// the file or in the extracted JSON, so they are sent with it in JSON bodies.
+pub(crate) type Variables = BTreeMap<String, Value>;
+
+/// Sequences of requests read from a YAML or TOML file. Every run picks one
+/// of them by their weights and sends its steps in order, passing the values
+/// extracted from the responses to the next steps.
+#[derive(Debug)]
+pub(crate) struct Scenarios {
+    variables: Variables,
+    scenarios: Vec<Scenario>,
+    // Smooth weighted round robin, so the scenarios are picked in the
+    // proportion of their weights, evenly spread.
+    current_weights: Mutex<Vec<i64>>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Scenario {
+    pub(crate) name: String,
+    weight: u32,
+    pub(crate) steps: Vec<Step>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Step {
+    pub(crate) name: String,
+    method: Method,
+    url: Template,
+    headers: Vec<(HeaderName, Template)>,
+    body: Option<Body>,
+    extract: Vec<(String, Extract)>,
+    /// How long to wait after the step, like a user would.
+    pub(crate) think: Option<Duration>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Body {
+    Text(Template),
+    Json(JsonTemplate),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Extract {
+    /// A path in the JSON body, like data.items[0].id.
+    Json(String),
+    /// A response header.
+    Header(String),
+}
+
+impl Scenarios {
+    /// Reads the scenarios of a file, in YAML or TOML by its extension.
+    pub(crate) fn load(path: &str) -> Result<Scenarios, Error> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|err| Error::Io(format!("cannot read {}: {}", path, err)))?;
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str());
+        let file: FileSpec = match extension {
+            Some("yaml" | "yml") => serde_yaml::from_str(&contents).map_err(|err| err.to_string()),
+            Some("toml") => toml::from_str(&contents).map_err(|err| err.to_string()),
+            _ => Err("expected a .yaml, .yml or .toml file".to_string()),
+        }
+        .map_err(|err| Error::Scenario(format!("{}: {}", path, err.trim_end())))?;
+        Scenarios::from(file).map_err(|err| Error::Scenario(format!("{}: {}", path, err)))
+    }
+
+    fn from(file: FileSpec) -> Result<Scenarios, String> {
+        if file.scenarios.is_empty() {
+            return Err("there are no scenarios".to_string());
+        }
+        let scenarios = file
+            .scenarios
+            .into_iter()
+            .map(|scenario| Scenario::from(scenario, &file.variables))
+            .collect::<Result<Vec<_>, _>>()?;
+        if scenarios.iter().all(|scenario| scenario.weight == 0) {
+            return Err("all the scenarios have weight 0".to_string());
+        }
+        Ok(Scenarios {
+            variables: file.variables,
+            current_weights: Mutex::new(vec![0; scenarios.len()]),
+            scenarios,
+        })
+    }
+
+    pub(crate) fn variables(&self) -> Variables {
+        self.variables.clone()
+    }
+
+    pub(crate) fn pick(&self) -> &Scenario {
+        let mut current = self.current_weights.lock().unwrap();
+        let total: i64 = self
+            .scenarios
+            .iter()
+            .map(|scenario| scenario.weight as i64)
+            .sum();
+        let mut picked = 0;
+        for (index, scenario) in self.scenarios.iter().enumerate() {
+            current[index] += scenario.weight as i64;
+            if current[index] > current[picked] {
+                picked = index;
+            }
+        }
+        current[picked] -= total;
+        &self.scenarios[picked]
+    }
+}
+
+impl Scenario {
+    fn from(spec: ScenarioSpec, variables: &Variables) -> Result<Scenario, String> {
+        // The variables are known once a previous step extracts them.
+        let mut known: BTreeSet<&str> = variables.keys().map(String::as_str).collect();
+        let mut steps = Vec::with_capacity(spec.steps.len());
+        for (index, step) in spec.steps.iter().enumerate() {
+            let name = step
+                .name
+                .clone()
+                .unwrap_or_else(|| format!("step {}", index + 1));
+            let step = Step::from(step, name, &known)
+                .map_err(|err| format!("scenario {}, {}", spec.name, err))?;
+            steps.push(step);
+            known.extend(spec.steps[index].extract.keys().map(String::as_str));
+        }
+        if steps.is_empty() {
+            return Err(format!("scenario {} has no steps", spec.name));
+        }
+        Ok(Scenario {
+            name: spec.name,
+            weight: spec.weight,
+            steps,
+        })
+    }
+}
+
+impl Step {
+    fn from(spec: &StepSpec, name: String, known: &BTreeSet<&str>) -> Result<Step, String> {
+        let template = |text: &str| {
+            let template = Template::parse(text).map_err(|err| format!("{}, {}", name, err))?;
+            let unknown = template
+                .variables()
+                .find(|variable| !known.contains(variable))
+                .map(str::to_string);
+            match unknown {
+                Some(variable) => Err(format!("{}, unknown variable {}", name, variable)),
+                None => Ok(template),
+            }
+        };
+        let mut headers = Vec::with_capacity(spec.headers.len());
+        for (header, value) in &spec.headers {
+            let header = HeaderName::from_bytes(header.as_bytes())
+                .map_err(|_| format!("{}, invalid header name {}", name, header))?;
+            headers.push((header, template(value)?));
+        }
+        let body = match (&spec.body, &spec.json) {
+            (Some(_), Some(_)) => return Err(format!("{}, has both a body and json", name)),
+            (Some(body), None) => Some(Body::Text(template(body)?)),
+            (None, Some(json)) => {
+                // Unless another content type is given.
+                if !headers.iter().any(|(header, _)| header == CONTENT_TYPE) {
+                    headers.push((CONTENT_TYPE, Template::text("application/json")));
+                }
+                Some(Body::Json(JsonTemplate::parse(json, &template)?))
+            }
+            (None, None) => None,
+        };
+        let think = match &spec.think {
+            Some(think) => Some(parse_duration(think).map_err(|err| format!("{}, {}", name, err))?),
+            None => None,
+        };
+        Ok(Step {
+            method: parse_method(&spec.method).map_err(|err| format!("{}, {}", name, err))?,
+            url: template(&spec.url)?,
+            headers,
+            body,
+            extract: spec
+                .extract
+                .iter()
+                .map(|(variable, extract)| match extract {
+                    ExtractSpec {
+                        json: Some(path),
+                        header: None,
+                    } => Ok((variable.clone(), Extract::Json(path.clone()))),
+                    ExtractSpec {
+                        json: None,
+                        header: Some(header),
+                    } => Ok((variable.clone(), Extract::Header(header.clone()))),
+                    _ => Err(format!(
+                        "{}, expected either json or header to extract {}",
+                        name, variable
+                    )),
+                })
+                .collect::<Result<_, _>>()?,
+            think,
+            name,
+        })
+    }
+
+    /// The request of the step, with the variables in place.
+    pub(crate) fn request(&self, variables: &Variables) -> Result<Request, Error> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let value = value.render(variables);
+            let value = HeaderValue::from_str(&value).map_err(|_| {
+                Error::Scenario(format!(
+                    "{}, invalid value of header {}: {}",
+                    self.name, name, value
+                ))
+            })?;
+            headers.append(name.clone(), value);
+        }
+        Request::new(
+            self.method.clone(),
+            self.url.render(variables),
+            headers,
+            self.body.as_ref().map(|body| match body {
+                Body::Text(template) => template.render(variables).into_bytes(),
+                Body::Json(template) => template.render(variables).to_string().into_bytes(),
+            }),
+        )
+    }
+
+    /// Sets the variables extracted from the response.
+    pub(crate) fn extract(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+        variables: &mut Variables,
+    ) -> Result<(), Error> {
+        let failed = |reason: String| Error::Extract(format!("{}, {}", self.name, reason));
+        let mut json = None;
+        for (variable, extract) in &self.extract {
+            let value = match extract {
+                Extract::Header(header) => headers
+                    .get(header)
+                    .and_then(|value| value.to_str().ok())
+                    .map(Value::from)
+                    .ok_or_else(|| failed(format!("no header {}", header)))?,
+                Extract::Json(path) => {
+                    if json.is_none() {
+                        let value: Value = serde_json::from_slice(body)
+                            .map_err(|err| failed(format!("the body is not JSON: {}", err)))?;
+                        json = Some(value);
+                    }
+                    json_path(json.as_ref().unwrap(), path)
+                        .cloned()
+                        .ok_or_else(|| failed(format!("no value at {}", path)))?
+                }
+            };
+            variables.insert(variable.clone(), value);
+        }
+        Ok(())
+    }
+}
+
+/// Finds a value by a path like data.items[0].id, optionally starting with $.
+fn json_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let path = path.strip_prefix('$').unwrap_or(path);
+    let mut current = value;
+    for segment in path.split('.').filter(|segment| !segment.is_empty()) {
+        let (key, indexes) = segment.split_at(segment.find('[').unwrap_or(segment.len()));
+        if !key.is_empty() {
+            current = current.get(key)?;
+        }
+        for index in indexes.split('[').skip(1) {
+            let index: usize = index.strip_suffix(']')?.parse().ok()?;
+            current = current.get(index)?;
+        }
+    }
+    Some(current)
+}
+
+/// Text with {{variable}} placeholders.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Template {
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Variable(String),
+}
+
+impl Template {
+    fn text(text: &str) -> Template {
+        Template {
+            parts: vec![Part::Text(text.to_string())],
+        }
+    }
+
+    fn parse(text: &str) -> Result<Template, String> {
+        let mut parts = Vec::new();
+        let mut rest = text;
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find("}}")
+                .ok_or_else(|| format!("unclosed {{{{ in {}", text))?;
+            let variable = rest[start + 2..start + end].trim();
+            if variable.is_empty() {
+                return Err(format!("empty {{{{}}}} in {}", text));
+            }
+            parts.push(Part::Variable(variable.to_string()));
+            rest = &rest[start + end + 2..];
+        }
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+        Ok(Template { parts })
+    }
+
+    fn variables(&self) -> impl Iterator<Item = &str> {
+        self.parts.iter().filter_map(|part| match part {
+            Part::Variable(variable) => Some(variable.as_str()),
+            Part::Text(_) => None,
+        })
+    }
+
+    // The variables were checked when loading, so they are there. Strings go
+    // without their quotes, and any other value as JSON.
+    fn render(&self, variables: &Variables) -> String {
+        let mut text = String::new();
+        for part in &self.parts {
+            match (part, self.variable(part, variables)) {
+                (Part::Text(part), _) => text.push_str(part),
+                (_, Some(Value::String(value))) => text.push_str(value),
+                (_, Some(value)) => text.push_str(&value.to_string()),
+                (_, None) => {}
+            }
+        }
+        text
+    }
+
+    fn variable<'a>(&self, part: &Part, variables: &'a Variables) -> Option<&'a Value> {
+        match part {
+            Part::Variable(variable) => variables.get(variable),
+            Part::Text(_) => None,
+        }
+    }
+}
+
+/// A JSON value with {{variable}} placeholders in its strings and keys. It is
+/// rendered value by value, so the variables are escaped as needed.
+#[derive(Debug, Clone, PartialEq)]
+enum JsonTemplate {
+    /// Null, a boolean or a number.
+    Value(Value),
+    String(Template),
+    Array(Vec<JsonTemplate>),
+    Object(Vec<(Template, JsonTemplate)>),
+}
+
+impl JsonTemplate {
+    fn parse(
+        value: &Value,
+        template: &dyn Fn(&str) -> Result<Template, String>,
+    ) -> Result<JsonTemplate, String> {
+        Ok(match value {
+            Value::String(text) => JsonTemplate::String(template(text)?),
+            Value::Array(items) => JsonTemplate::Array(
+                items
+                    .iter()
+                    .map(|item| JsonTemplate::parse(item, template))
+                    .collect::<Result<_, _>>()?,
+            ),
+            Value::Object(fields) => JsonTemplate::Object(
+                fields
+                    .iter()
+                    .map(|(key, value)| Ok((template(key)?, JsonTemplate::parse(value, template)?)))
+                    .collect::<Result<_, String>>()?,
+            ),
+            value => JsonTemplate::Value(value.clone()),
+        })
+    }
+
+    fn render(&self, variables: &Variables) -> Value {
+        match self {
+            JsonTemplate::Value(value) => value.clone(),
+            JsonTemplate::String(template) => match template.parts.as_slice() {
+                // A string that is just a variable takes its value, so numbers
+                // can be sent too.
+                [part @ Part::Variable(_)] => template
+                    .variable(part, variables)
+                    .cloned()
+                    .unwrap_or(Value::Null),
+                _ => Value::String(template.render(variables)),
+            },
+            JsonTemplate::Array(items) => {
+                Value::Array(items.iter().map(|item| item.render(variables)).collect())
+            }
+            JsonTemplate::Object(fields) => Value::Object(
+                fields
+                    .iter()
+                    .map(|(key, value)| (key.render(variables), value.render(variables)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileSpec {
+    #[serde(default)]
+    variables: Variables,
+    #[serde(default)]
+    scenarios: Vec<ScenarioSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioSpec {
+    name: String,
+    #[serde(default = "default_weight")]
+    weight: u32,
+    #[serde(default)]
+    steps: Vec<StepSpec>,
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StepSpec {
+    name: Option<String>,
+    #[serde(default = "default_method")]
+    method: String,
+    url: String,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+    body: Option<String>,
+    json: Option<Value>,
+    #[serde(default)]
+    extract: BTreeMap<String, ExtractSpec>,
+    think: Option<String>,
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ExtractSpec {
+    json: Option<String>,
+    header: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const YAML: &str = r#"
+variables:
+  base: http://localhost:8080
+  user: ana
+scenarios:
+  - name: browse
+    weight: 3
+    steps:
+      - name: login
+        method: post
+        url: "{{base}}/login"
+        json: { user: "{{user}}" }
+        extract:
+          token: { json: "$.token" }
+          session: { header: x-session }
+        think: 100ms
+      - name: item
+        url: "{{base}}/items/{{ token }}"
+        headers:
+          Authorization: "Bearer {{token}}"
+  - name: health
+    steps:
+      - url: "{{base}}/health"
+"#;
+
+    const TOML: &str = r#"
+[variables]
+base = "http://localhost:8080"
+
+[[scenarios]]
+name = "health"
+weight = 2
+
+[[scenarios.steps]]
+url = "{{base}}/health"
+think = "1s"
+
+[scenarios.steps.extract]
+version = { header = "x-version" }
+"#;
+
+    fn load(contents: &str, extension: &str) -> Result<Scenarios, Error> {
+        let path = std::env::temp_dir().join(format!(
+            "hammer-scenario-{}-{:?}.{}",
+            std::process::id(),
+            std::thread::current().id(),
+            extension
+        ));
+        std::fs::write(&path, contents).unwrap();
+        let scenarios = Scenarios::load(path.to_str().unwrap());
+        std::fs::remove_file(path).unwrap();
+        scenarios
+    }
+
+    #[test]
+    fn it_loads_yaml() {
+        let scenarios = load(YAML, "yaml").unwrap();
+
+        assert_eq!(2, scenarios.scenarios.len());
+        let browse = &scenarios.scenarios[0];
+        assert_eq!(("browse", 3), (browse.name.as_str(), browse.weight));
+        let login = &browse.steps[0];
+        assert_eq!("login", login.name);
+        assert_eq!(Method::POST, login.method);
+        assert_eq!(Some(Duration::from_millis(100)), login.think);
+        assert_eq!(
+            vec![
+                (
+                    "session".to_string(),
+                    Extract::Header("x-session".to_string())
+                ),
+                ("token".to_string(), Extract::Json("$.token".to_string())),
+            ],
+            login.extract
+        );
+        assert_eq!("step 1", scenarios.scenarios[1].steps[0].name);
+        assert_eq!(1, scenarios.scenarios[1].weight);
+    }
+
+    #[test]
+    fn it_loads_toml() {
+        let scenarios = load(TOML, "toml").unwrap();
+
+        let health = &scenarios.scenarios[0];
+        assert_eq!(("health", 2), (health.name.as_str(), health.weight));
+        assert_eq!(Some(Duration::from_secs(1)), health.steps[0].think);
+        assert_eq!(
+            vec![(
+                "version".to_string(),
+                Extract::Header("x-version".to_string())
+            )],
+            health.steps[0].extract
+        );
+    }
+
+    #[test]
+    fn it_renders_the_requests() {
+        let scenarios = load(YAML, "yml").unwrap();
+        let mut variables = scenarios.variables();
+        let steps = &scenarios.scenarios[0].steps;
+
+        let login = steps[0].request(&variables).unwrap();
+
+        assert_eq!(Method::POST, login.method);
+        assert_eq!("http://localhost:8080/login", login.url);
+        assert_eq!("application/json", login.headers[CONTENT_TYPE]);
+        assert_eq!(Some(br#"{"user":"ana"}"#.to_vec()), login.body);
+
+        variables.insert("token".to_string(), Value::from("abc"));
+        let item = steps[1].request(&variables).unwrap();
+
+        assert_eq!("http://localhost:8080/items/abc", item.url);
+        assert_eq!("Bearer abc", item.headers["authorization"]);
+        assert_eq!(None, item.body);
+    }
+
+    #[test]
+    fn it_extracts_variables() {
+        let scenarios = load(YAML, "yaml").unwrap();
+        let login = &scenarios.scenarios[0].steps[0];
+        let mut headers = HeaderMap::new();
+        headers.insert("x-session", HeaderValue::from_static("s-1"));
+        let mut variables = Variables::new();
+
+        login
+            .extract(&headers, br#"{"token": "t-1"}"#, &mut variables)
+            .unwrap();
+
+        assert_eq!(Some(&json!("t-1")), variables.get("token"));
+        assert_eq!(Some(&json!("s-1")), variables.get("session"));
+
+        login
+            .extract(&headers, br#"{"token": 7}"#, &mut variables)
+            .unwrap();
+        assert_eq!(Some(&json!(7)), variables.get("token"));
+
+        let err = login
+            .extract(&headers, br#"{"other": 1}"#, &mut variables)
+            .unwrap_err();
+        assert_eq!(
+            Error::Extract("login, no value at $.token".to_string()),
+            err
+        );
+
+        let err = login
+            .extract(&HeaderMap::new(), br#"{"token": 1}"#, &mut variables)
+            .unwrap_err();
+        assert_eq!(
+            Error::Extract("login, no header x-session".to_string()),
+            err
+        );
+    }
+
+    #[test]
+    fn it_picks_the_scenarios_by_weight() {
+        let scenarios = load(YAML, "yaml").unwrap();
+
+        let picked: Vec<_> = (0..8).map(|_| scenarios.pick().name.as_str()).collect();
+
+        assert_eq!(
+            vec!["browse", "browse", "health", "browse", "browse", "browse", "health", "browse"],
+            picked
+        );
+    }
+
+    #[test]
+    fn it_rejects_invalid_scenarios() {
+        let err = |contents: &str| match load(contents, "yaml") {
+            Err(Error::Scenario(err)) => err.split_once(": ").unwrap().1.to_string(),
+            other => panic!("unexpected {:?}", other),
+        };
+
+        assert_eq!(
+            "scenario s, step 1, unknown variable token",
+            err("scenarios: [{name: s, steps: [{url: 'http://x/{{token}}'}]}]")
+        );
+        assert_eq!(
+            "scenario s, first, unclosed {{ in http://x/{{a",
+            err("scenarios: [{name: s, steps: [{name: first, url: 'http://x/{{a'}]}]")
+        );
+        assert_eq!("there are no scenarios", err("variables: {}"));
+        assert_eq!("scenario s has no steps", err("scenarios: [{name: s}]"));
+        assert_eq!(
+            "all the scenarios have weight 0",
+            err("scenarios: [{name: s, weight: 0, steps: [{url: 'http://x'}]}]")
+        );
+        assert_eq!(
+            "scenario s, step 1, invalid duration unit in 2d, expected ms, s, m or h",
+            err("scenarios: [{name: s, steps: [{url: 'http://x', think: 2d}]}]")
+        );
+        assert_eq!(
+            "scenario s, step 1, expected either json or header to extract v",
+            err("scenarios: [{name: s, steps: [{url: 'http://x', extract: {v: {}}}]}]")
+        );
+        assert!(
+            err("scenarios: [{name: s, steps: [{url: 'http://x', wat: 1}]}]")
+                .contains("unknown field `wat`")
+        );
+    }
+
+    #[test]
+    fn it_rejects_unknown_file_types() {
+        let err = load("", "json").unwrap_err();
+
+        assert!(
+            matches!(err, Error::Scenario(message) if message.ends_with(": expected a .yaml, .yml or .toml file"))
+        );
+    }
+
+    #[test]
+    fn it_parses_templates() {
+        let template = Template::parse("{{a}}/x/{{ b }}{{c}}").unwrap();
+
+        assert_eq!(
+            vec![
+                Part::Variable("a".to_string()),
+                Part::Text("/x/".to_string()),
+                Part::Variable("b".to_string()),
+                Part::Variable("c".to_string()),
+            ],
+            template.parts
+        );
+        let variables = Variables::from([
+            ("a".to_string(), json!("1")),
+            ("b".to_string(), json!(2)),
+            ("c".to_string(), json!(true)),
+        ]);
+        assert_eq!("1/x/2true", template.render(&variables));
+        assert!(Template::parse("{{}}").is_err());
+    }
+
+    #[test]
+    fn it_renders_json_bodies_value_by_value() {
+        let template = |text: &str| Template::parse(text);
+        let body = json!({
+            "name": "{{name}}",
+            "greeting": "hi {{name}}",
+            "id": "{{id}}",
+            "{{name}}": [1, null, "{{missing}}"]
+        });
+        let body = JsonTemplate::parse(&body, &template).unwrap();
+        let variables = Variables::from([
+            ("name".to_string(), json!(r#"a "quoted" \ name"#)),
+            ("id".to_string(), json!(42)),
+        ]);
+
+        assert_eq!(
+            json!({
+                "name": r#"a "quoted" \ name"#,
+                "greeting": r#"hi a "quoted" \ name"#,
+                "id": 42,
+                r#"a "quoted" \ name"#: [1, null, null]
+            }),
+            body.render(&variables)
+        );
+    }
+
+    #[test]
+    fn it_finds_json_paths() {
+        let value =
+            json!({"data": {"items": [{"id": 7}, {"id": 8, "tags": ["a", "b"]}]}, "ok": true});
+
+        assert_eq!(Some(&json!(7)), json_path(&value, "data.items[0].id"));
+        assert_eq!(
+            Some(&json!("b")),
+            json_path(&value, "$.data.items[1].tags[1]")
+        );
+        assert_eq!(Some(&json!(true)), json_path(&value, "$.ok"));
+        assert_eq!(Some(&value), json_path(&value, "$"));
+        assert_eq!(None, json_path(&value, "data.items[2]"));
+        assert_eq!(None, json_path(&value, "data.missing"));
+        assert_eq!(None, json_path(&value, "data.items[x]"));
+    }
+}
@@ -0,0 +1,312 @@
+use std::time::Duration;
+
+use crate::Rate;
+
+/// What a stage ramps: the workers sending requests one after the other, or
+/// the rate they are sent at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Target {
+    Users(u64),
+    Rate(Rate),
+}
+
+/// A part of the run where the load goes linearly from where the previous
+/// stage left it, or from 0, to its target.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stage {
+    /// Like "ramp 0 → 200 users over 1m".
+    pub name: String,
+    /// Since the start of the run.
+    pub start: Duration,
+    pub duration: Duration,
+    /// The users or requests per second at the start of the stage.
+    pub from: f64,
+    /// The users or requests per second at the end of the stage.
+    pub to: f64,
+}
+
+impl Stage {
+    pub fn end(&self) -> Duration {
+        self.start + self.duration
+    }
+
+    /// The load at a time of the stage.
+    fn at(&self, elapsed: Duration) -> f64 {
+        let fraction =
+            elapsed.saturating_sub(self.start).as_secs_f64() / self.duration.as_secs_f64();
+        self.from + (self.to - self.from) * fraction.min(1.0)
+    }
+
+    /// The requests sent during the stage, when it ramps a rate.
+    fn requests(&self) -> f64 {
+        (self.from + self.to) / 2.0 * self.duration.as_secs_f64()
+    }
+}
+
+/// The stages of a run, all of them ramping users or all of them ramping a
+/// rate.
+#[derive(Debug)]
+pub(crate) struct Stages {
+    pub(crate) stages: Vec<Stage>,
+    pub(crate) rate: bool,
+}
+
+impl Stages {
+    pub(crate) fn new(targets: &[(Duration, Target)]) -> Result<Stages, String> {
+        let rate = matches!(targets.first(), Some((_, Target::Rate(_))));
+        let mut stages = Vec::with_capacity(targets.len());
+        let mut start = Duration::ZERO;
+        let mut from = 0.0;
+        for (duration, target) in targets {
+            let to = match (target, rate) {
+                (Target::Users(users), false) => *users as f64,
+                (Target::Rate(rate), true) => rate.per_second(),
+                _ => {
+                    return Err(
+                        "the stages mix users and rates, expected only one of them".to_string()
+                    )
+                }
+            };
+            let unit = if rate { "/s" } else { " users" };
+            let name = if from == to {
+                format!("hold {}{} for {}", amount(to), unit, short(*duration))
+            } else {
+                format!(
+                    "ramp {} → {}{} over {}",
+                    amount(from),
+                    amount(to),
+                    unit,
+                    short(*duration)
+                )
+            };
+            stages.push(Stage {
+                name,
+                start,
+                duration: *duration,
+                from,
+                to,
+            });
+            start = start
+                .checked_add(*duration)
+                .ok_or("the stages are too long")?;
+            from = to;
+        }
+        Ok(Stages { stages, rate })
+    }
+
+    pub(crate) fn duration(&self) -> Duration {
+        self.stages.last().map_or(Duration::ZERO, Stage::end)
+    }
+
+    /// The most users of any stage.
+    pub(crate) fn max_users(&self) -> u64 {
+        self.stages
+            .iter()
+            .map(|stage| stage.to as u64)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The earliest time, from the given one on, when the load is above the
+    /// given level, or just getting to it. None if it never is again.
+    pub(crate) fn next_above(&self, level: f64, elapsed: Duration) -> Option<Duration> {
+        for stage in self.stages.iter().filter(|stage| stage.end() > elapsed) {
+            let from = elapsed.max(stage.start);
+            if stage.at(from) > level {
+                return Some(from);
+            }
+            if stage.to > level {
+                // Ramping up, it gets to the level during the stage.
+                let fraction = (level - stage.from) / (stage.to - stage.from);
+                return Some(
+                    stage.start + stage.duration.mul_f64(fraction).max(from - stage.start),
+                );
+            }
+        }
+        None
+    }
+
+    /// When the nth request has to be sent, since the start, when ramping a
+    /// rate. None once past the last stage.
+    pub(crate) fn offset(&self, n: u64) -> Option<Duration> {
+        let mut left = n as f64;
+        for stage in &self.stages {
+            let requests = stage.requests();
+            if left < requests {
+                // Solves from * t + slope * t² / 2 = left, in a way that
+                // holds for flat stages too.
+                let seconds = stage.duration.as_secs_f64();
+                let slope = (stage.to - stage.from) / seconds;
+                let t = if left > 0.0 {
+                    2.0 * left / (stage.from + (stage.from.powi(2) + 2.0 * slope * left).sqrt())
+                } else {
+                    0.0
+                };
+                return Some(stage.start + Duration::from_secs_f64(t.min(seconds)));
+            }
+            left -= requests;
+        }
+        None
+    }
+
+    /// The requests per second of the whole run, when ramping a rate.
+    pub(crate) fn mean_rate(&self) -> f64 {
+        let requests: f64 = self.stages.iter().map(Stage::requests).sum();
+        requests / self.duration().as_secs_f64()
+    }
+}
+
+/// Formats an amount without trailing zeros, like 200 or 1.67.
+fn amount(value: f64) -> String {
+    let value = format!("{:.2}", value);
+    value
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+/// Formats a duration like it is given, like 500ms, 30s or 5m.
+fn short(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if duration.subsec_nanos() != 0 {
+        format!("{}ms", duration.as_millis())
+    } else if secs != 0 && secs.is_multiple_of(3600) {
+        format!("{}h", secs / 3600)
+    } else if secs != 0 && secs.is_multiple_of(60) {
+        format!("{}m", secs / 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn users(stages: &[(u64, u64)]) -> Stages {
+        let targets: Vec<_> = stages
+            .iter()
+            .map(|(secs, users)| (Duration::from_secs(*secs), Target::Users(*users)))
+            .collect();
+        Stages::new(&targets).unwrap()
+    }
+
+    fn rates(stages: &[(u64, u64)]) -> Stages {
+        let targets: Vec<_> = stages
+            .iter()
+            .map(|(secs, requests)| {
+                let rate = Rate {
+                    requests: *requests,
+                    per: Duration::from_secs(1),
+                };
+                (Duration::from_secs(*secs), Target::Rate(rate))
+            })
+            .collect();
+        Stages::new(&targets).unwrap()
+    }
+
+    #[test]
+    fn it_names_the_stages() {
+        let stages = users(&[(60, 200), (300, 200), (60, 0)]);
+
+        let names: Vec<_> = stages
+            .stages
+            .iter()
+            .map(|stage| stage.name.as_str())
+            .collect();
+        assert_eq!(
+            vec![
+                "ramp 0 → 200 users over 1m",
+                "hold 200 users for 5m",
+                "ramp 200 → 0 users over 1m"
+            ],
+            names
+        );
+        assert_eq!(Duration::from_secs(360), stages.stages[2].start);
+        assert_eq!(Duration::from_secs(420), stages.duration());
+        assert_eq!(200, stages.max_users());
+
+        let targets = [(
+            Duration::from_millis(1500),
+            Target::Rate(Rate {
+                requests: 100,
+                per: Duration::from_secs(60),
+            }),
+        )];
+        let stages = Stages::new(&targets).unwrap();
+        assert_eq!("ramp 0 → 1.67/s over 1500ms", stages.stages[0].name);
+        assert!(stages.rate);
+    }
+
+    #[test]
+    fn it_rejects_mixed_stages() {
+        let targets = [
+            (Duration::from_secs(1), Target::Users(10)),
+            (
+                Duration::from_secs(1),
+                Target::Rate(Rate {
+                    requests: 10,
+                    per: Duration::from_secs(1),
+                }),
+            ),
+        ];
+
+        assert!(Stages::new(&targets).is_err());
+    }
+
+    #[test]
+    fn it_rejects_too_long_stages() {
+        let targets = [
+            (Duration::MAX, Target::Users(10)),
+            (Duration::from_secs(1), Target::Users(10)),
+        ];
+
+        assert_eq!(
+            "the stages are too long",
+            Stages::new(&targets).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn it_tells_when_the_users_are_needed() {
+        let stages = users(&[(10, 10), (10, 10), (10, 0)]);
+        let secs = |secs: f64| Some(Duration::from_secs_f64(secs));
+
+        assert_eq!(secs(0.0), stages.next_above(0.0, Duration::ZERO));
+        assert_eq!(secs(5.0), stages.next_above(5.0, Duration::ZERO));
+        assert_eq!(secs(9.0), stages.next_above(9.0, Duration::from_secs(2)));
+        assert_eq!(secs(12.0), stages.next_above(9.0, Duration::from_secs(12)));
+        assert_eq!(secs(22.0), stages.next_above(7.0, Duration::from_secs(22)));
+        assert_eq!(None, stages.next_above(7.0, Duration::from_secs(23)));
+        assert_eq!(None, stages.next_above(10.0, Duration::ZERO));
+    }
+
+    #[test]
+    fn it_schedules_ramped_rates() {
+        let stages = rates(&[(1, 10), (1, 10), (1, 0)]);
+        let offsets: Vec<_> = (0..17)
+            .map(|n| stages.offset(n).map(|offset| offset.as_millis()))
+            .collect();
+
+        // Ramping up, the nth request is at sqrt(n / 5).
+        assert_eq!(Some(0), offsets[0]);
+        assert_eq!(Some(447), offsets[1]);
+        assert_eq!(Some(894), offsets[4]);
+        // Holding, every 100ms.
+        assert_eq!(Some(1000), offsets[5]);
+        assert_eq!(Some(1500), offsets[10]);
+        // Ramping down, slower every time.
+        assert_eq!(Some(2000), offsets[15]);
+        assert_eq!(Some(2105), offsets[16]);
+        assert_eq!(None, stages.offset(20));
+        assert_eq!(20.0 / 3.0, stages.mean_rate());
+    }
+
+    #[test]
+    fn it_formats_short_durations() {
+        assert_eq!("500ms", short(Duration::from_millis(500)));
+        assert_eq!("90s", short(Duration::from_secs(90)));
+        assert_eq!("2m", short(Duration::from_secs(120)));
+        assert_eq!("1h", short(Duration::from_secs(3600)));
+    }
+}